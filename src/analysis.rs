@@ -0,0 +1,196 @@
+use egui::{Pos2, Rect};
+
+/// Ray casting point-in-polygon test. The polygon is treated as closed,
+/// so the last point connects back to the first.
+pub fn point_in_polygon(point: Pos2, polygon: &[Pos2]) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[j];
+        // Count crossings of a horizontal ray going right from the point
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Distances from a canvas position to each visual edge of the canvas,
+/// independent of the coordinate origin. Negative outside that edge.
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Insets {
+    pub fn of(position: Pos2, canvas_size: (f32, f32)) -> Self {
+        let (width, height) = canvas_size;
+        Self {
+            top: position.y,
+            right: width - position.x,
+            bottom: height - position.y,
+            left: position.x,
+        }
+    }
+}
+
+/// Signed distance from `value` to the closest guide, positive when the
+/// value lies after (right of / below) it
+pub fn nearest_guide(value: f32, guides: impl Iterator<Item = f32>) -> Option<f32> {
+    guides
+        .map(|guide| value - guide)
+        .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+}
+
+/// Angle in degrees at `vertex` turning from the arm through `a` to the arm
+/// through `b`, in -180..=180. Positive is counter-clockwise with +Y up, so
+/// in Y-down coordinates it is clockwise on screen. None for a zero-length arm.
+pub fn signed_angle(vertex: Pos2, a: Pos2, b: Pos2) -> Option<f32> {
+    let (arm_a, arm_b) = (a - vertex, b - vertex);
+    if arm_a.length() == 0.0 || arm_b.length() == 0.0 {
+        return None;
+    }
+    let cross = arm_a.x * arm_b.y - arm_a.y * arm_b.x;
+    Some(cross.atan2(arm_a.dot(arm_b)).to_degrees())
+}
+
+/// Linearly remaps positions so their bounding box fills the canvas. With
+/// `preserve_aspect` both axes share one scale and the shorter axis is
+/// centred. An axis with no extent is centred rather than stretched.
+pub fn normalise_to_canvas(positions: &[Pos2], canvas_size: (f32, f32), preserve_aspect: bool) -> Vec<Pos2> {
+    if positions.is_empty() {
+        return Vec::new();
+    }
+    let bounds = Rect::from_points(positions);
+    let (width, height) = canvas_size;
+    let axis_scale = |extent: f32, size: f32| if extent > 0.0 { Some(size / extent) } else { None };
+    let (mut scale_x, mut scale_y) = (axis_scale(bounds.width(), width), axis_scale(bounds.height(), height));
+    if preserve_aspect {
+        let uniform = match (scale_x, scale_y) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        };
+        scale_x = scale_x.and(uniform);
+        scale_y = scale_y.and(uniform);
+    }
+
+    // Leftover space on an axis is split evenly so the result stays centred
+    let remap = |value: f32, min: f32, extent: f32, size: f32, scale: Option<f32>| match scale {
+        Some(scale) => (value - min) * scale + (size - extent * scale) / 2.0,
+        None => size / 2.0,
+    };
+    positions
+        .iter()
+        .map(|pos| {
+            Pos2::new(
+                remap(pos.x, bounds.min.x, bounds.width(), width, scale_x),
+                remap(pos.y, bounds.min.y, bounds.height(), height, scale_y),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Vec<Pos2> {
+        vec![
+            Pos2::new(0.0, 0.0),
+            Pos2::new(10.0, 0.0),
+            Pos2::new(10.0, 10.0),
+            Pos2::new(0.0, 10.0),
+        ]
+    }
+
+    #[test]
+    fn points_inside_and_outside_a_square() {
+        assert!(point_in_polygon(Pos2::new(5.0, 5.0), &square()));
+        assert!(!point_in_polygon(Pos2::new(15.0, 5.0), &square()));
+        assert!(!point_in_polygon(Pos2::new(5.0, -1.0), &square()));
+    }
+
+    #[test]
+    fn concave_notches_are_outside() {
+        // A U shape open at the top between x = 4 and x = 6
+        let polygon = [
+            Pos2::new(0.0, 0.0),
+            Pos2::new(4.0, 0.0),
+            Pos2::new(4.0, 8.0),
+            Pos2::new(6.0, 8.0),
+            Pos2::new(6.0, 0.0),
+            Pos2::new(10.0, 0.0),
+            Pos2::new(10.0, 10.0),
+            Pos2::new(0.0, 10.0),
+        ];
+        assert!(!point_in_polygon(Pos2::new(5.0, 4.0), &polygon));
+        assert!(point_in_polygon(Pos2::new(2.0, 4.0), &polygon));
+        assert!(point_in_polygon(Pos2::new(5.0, 9.0), &polygon));
+    }
+
+    #[test]
+    fn degenerate_polygons_contain_nothing() {
+        assert!(!point_in_polygon(Pos2::ZERO, &[]));
+        assert!(!point_in_polygon(Pos2::new(1.0, 0.0), &[Pos2::ZERO, Pos2::new(2.0, 0.0)]));
+    }
+
+    #[test]
+    fn insets_measure_to_each_canvas_edge() {
+        let insets = Insets::of(Pos2::new(30.0, 20.0), (100.0, 50.0));
+        assert_eq!((insets.top, insets.right, insets.bottom, insets.left), (20.0, 70.0, 30.0, 30.0));
+        let outside = Insets::of(Pos2::new(-5.0, 60.0), (100.0, 50.0));
+        assert_eq!((outside.left, outside.bottom), (-5.0, -10.0));
+    }
+
+    #[test]
+    fn nearest_guide_is_signed() {
+        let guides = [10.0, 50.0, 100.0];
+        assert_eq!(nearest_guide(47.0, guides.into_iter()), Some(-3.0));
+        assert_eq!(nearest_guide(104.0, guides.into_iter()), Some(4.0));
+        assert_eq!(nearest_guide(5.0, std::iter::empty()), None);
+    }
+
+    #[test]
+    fn normalising_stretches_the_bounds_to_the_canvas() {
+        let positions = [Pos2::new(0.0, 0.0), Pos2::new(10.0, 5.0)];
+        let stretched = normalise_to_canvas(&positions, (100.0, 100.0), false);
+        assert_eq!(stretched, [Pos2::new(0.0, 0.0), Pos2::new(100.0, 100.0)]);
+        let uniform = normalise_to_canvas(&positions, (100.0, 100.0), true);
+        assert_eq!(uniform, [Pos2::new(0.0, 25.0), Pos2::new(100.0, 75.0)]);
+    }
+
+    #[test]
+    fn flat_axes_are_centred() {
+        let single = normalise_to_canvas(&[Pos2::new(3.0, 4.0)], (100.0, 60.0), false);
+        assert_eq!(single, [Pos2::new(50.0, 30.0)]);
+        let vertical = normalise_to_canvas(&[Pos2::new(5.0, 0.0), Pos2::new(5.0, 10.0)], (100.0, 60.0), true);
+        assert_eq!(vertical, [Pos2::new(50.0, 0.0), Pos2::new(50.0, 60.0)]);
+        assert!(normalise_to_canvas(&[], (100.0, 60.0), true).is_empty());
+    }
+
+    #[test]
+    fn signed_angles_turn_from_the_first_arm() {
+        let vertex = Pos2::new(2.0, 2.0);
+        assert_eq!(signed_angle(vertex, Pos2::new(3.0, 2.0), Pos2::new(2.0, 3.0)), Some(90.0));
+        assert_eq!(signed_angle(vertex, Pos2::new(3.0, 2.0), Pos2::new(2.0, 1.0)), Some(-90.0));
+        assert_eq!(signed_angle(vertex, Pos2::new(3.0, 2.0), Pos2::new(0.0, 2.0)), Some(180.0));
+        let diagonal = signed_angle(vertex, Pos2::new(4.0, 2.0), Pos2::new(3.0, 3.0)).unwrap();
+        assert!((diagonal - 45.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn zero_length_arms_have_no_angle() {
+        let vertex = Pos2::new(2.0, 2.0);
+        assert_eq!(signed_angle(vertex, vertex, Pos2::new(5.0, 5.0)), None);
+        assert_eq!(signed_angle(vertex, Pos2::new(5.0, 5.0), vertex), None);
+    }
+}
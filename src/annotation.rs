@@ -0,0 +1,41 @@
+use egui::Pos2;
+
+// Segments used to approximate curves when drawing
+pub const CURVE_SEGMENTS: usize = 64;
+
+// Shapes drawn over the canvas that aren't coordinate markers. Points are in
+// canvas coordinates.
+#[derive(Clone)]
+pub enum Annotation {
+    Bezier { p0: Pos2, p1: Pos2, p2: Pos2, p3: Pos2 },
+}
+
+impl Annotation {
+    /// Polyline through the shape, with `segments` pieces per curve
+    pub fn flatten(&self, segments: usize) -> Vec<Pos2> {
+        match *self {
+            Annotation::Bezier { p0, p1, p2, p3 } => (0..=segments)
+                .map(|i| cubic_bezier(p0, p1, p2, p3, i as f32 / segments as f32))
+                .collect(),
+        }
+    }
+
+    /// SVG path data, e.g. "M 0 0 C 10 0 20 10 20 20"
+    pub fn svg_path(&self) -> String {
+        match self {
+            Annotation::Bezier { p0, p1, p2, p3 } => format!(
+                "M {} {} C {} {} {} {} {} {}",
+                p0.x, p0.y, p1.x, p1.y, p2.x, p2.y, p3.x, p3.y
+            ),
+        }
+    }
+}
+
+fn cubic_bezier(p0: Pos2, p1: Pos2, p2: Pos2, p3: Pos2, t: f32) -> Pos2 {
+    let u = 1.0 - t;
+    let point = p0.to_vec2() * (u * u * u)
+        + p1.to_vec2() * (3.0 * u * u * t)
+        + p2.to_vec2() * (3.0 * u * t * t)
+        + p3.to_vec2() * (t * t * t);
+    point.to_pos2()
+}
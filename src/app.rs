@@ -1,21 +1,81 @@
-use crate::canvas::Canvas;
-use crate::coordinate::CoordinateSystem;
-use crate::grid::Grid;
-use crate::marker::Marker;
-use crate::ui::UiState;
+use crate::analysis;
+use crate::annotation::{self, Annotation};
+use crate::canvas::{self, Canvas};
+use crate::colormap::{self, ColorBy, Colormap};
+use crate::command::GridCommand;
+use crate::coordinate::{
+    self, AffineTransform, CoordinateSystem, CoordinateUnits, OriginMode, OutputTransform, PixelRatio, ScalePreset,
+};
+use crate::export::{
+    self, CanvasLayers, CodeLanguage, ColorProfile, CompositeMode, CsvColumn, CsvDelimiter, ExportFormat,
+    ExportSettings, ImageLayer, RenderView,
+};
+use crate::format;
+use crate::grid::{self, Grid, GridLayer, GridPreset, GridStyle, RoundingMode, SnapMode};
+use crate::group::GroupManager;
+use crate::input::{self, BindingModifier, MouseBindings};
+use crate::marker::{Marker, MarkerKind, MarkerList};
+use crate::measurement::{self, Measurement, MeasurementKind, MeasurementRecord};
+use crate::project::ProjectExport;
+use crate::recent;
+use crate::region::{self, RegionAnalysis};
+use crate::ruler::{Ruler, RulerAxis};
+use crate::settings::Settings;
+use crate::stamp::{self, Stamp};
+use crate::system_color;
+use crate::undo::{self, MarkerCommand, Snapshot, UndoStack};
+use crate::validation::{self, ValidationResult};
+#[cfg(feature = "websocket")]
+use crate::stream::PositionStream;
+use crate::ui::{
+    CopyTemplate, CopyUnits, CrosshairSettings, MinimapCorner, CrosshairShape, MarkerEdit, OriginMigration,
+    PanelSection, PendingOriginChange, QuantizeSettings, Toast, Tool, UiState, ViewBookmark,
+};
 use clipboard::ClipboardContext;
 use clipboard::ClipboardProvider;
 use egui::{Color32, Context, Stroke, Ui};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::time::SystemTime;
+
+// One outline color per secondary canvas size, which also caps how many can be added
+const NESTED_SIZE_COLORS: [Color32; 4] = [
+    Color32::from_rgb(0, 180, 255),
+    Color32::from_rgb(255, 120, 0),
+    Color32::from_rgb(200, 80, 255),
+    Color32::from_rgb(0, 200, 120),
+];
+
+// Kept measurements, apart from the Measure tool's yellow and the Angle tool's orange
+const MEASUREMENT_COLOR: Color32 = Color32::from_rgb(0, 190, 170);
+
+// How close a click must be to a marker to hit it, in screen px
+const MARKER_SCREEN_THRESHOLD: f32 = 10.0;
 
 pub struct CoordinatePickerApp {
     canvas: Canvas,
     grid: Grid,
     coordinate_system: CoordinateSystem,
-    markers: Vec<Marker>,
+    markers: MarkerList,
+    annotations: Vec<Annotation>,
+    measurements: Vec<Measurement>, // Kept distances, angles, rects and circles
+    background_image: Option<BackgroundImage>, // Screenshot or mockup behind the markers, 1 image px per canvas px
+    groups: GroupManager,
+    undo_stack: UndoStack,
     ui_state: UiState,
+    saved_settings: Settings, // Last settings written to the config file
     clipboard: Option<ClipboardContext>,
     resolution_presets: HashMap<String, (f32, f32)>,
+    secondary_sizes: Vec<(String, f32, f32)>, // Target resolutions outlined together for responsive layouts
+    grid_visibility_alpha: f32, // Fades the grid in/out when toggled
+    marker_mesh: Option<(MarkerMeshKey, egui::Mesh)>, // Cached fast-path mesh and the state it was built from
+    window_title: String,                   // Last title sent to the window, to skip redundant updates
+    region_analysis: Option<RegionAnalysis>, // Running color analysis of a large region
+    canvas_target_offset: Option<egui::Vec2>, // Where pan_to is easing the canvas offset
+    canvas_pan_speed: f32,                    // Rate of that easing; higher is faster
+    #[cfg(feature = "websocket")]
+    position_stream: Option<PositionStream>,
 }
 
 // Main implementation of the coordinate picker app
@@ -38,295 +98,5802 @@ impl CoordinatePickerApp {
         let mut app = Self {
             canvas: Canvas::new(1920.0, 1080.0),
             grid: Grid::new(45.0, true),
-            coordinate_system: CoordinateSystem::new(true),
-            markers: Vec::new(),
+            coordinate_system: CoordinateSystem::new(OriginMode::TopLeft),
+            markers: MarkerList::default(),
+            annotations: Vec::new(),
+            measurements: Vec::new(),
+            background_image: None,
+            groups: GroupManager::default(),
+            undo_stack: UndoStack::new(undo::DEFAULT_DEPTH),
             ui_state: UiState::default(),
+            saved_settings: Settings::default(),
             clipboard,
             resolution_presets,
+            secondary_sizes: Vec::new(),
+            grid_visibility_alpha: 0.0,
+            marker_mesh: None,
+            window_title: String::new(),
+            region_analysis: None,
+            canvas_target_offset: None,
+            canvas_pan_speed: 5.0,
+            #[cfg(feature = "websocket")]
+            position_stream: None,
         };
 
-        app.grid.set_size(app.ui_state.grid_size);
-        app.grid.set_visible(app.ui_state.show_grid);
-        app.grid.set_snapping(app.ui_state.enable_snapping);
-        app.coordinate_system.set_origin_top_left(app.ui_state.origin_top_left);
+        if let Some(settings) = Settings::load() {
+            settings.apply(&mut app.ui_state);
+            app.saved_settings = settings;
+        }
+
+        app.apply_grid_settings();
+        app.grid_visibility_alpha = if app.grid.is_visible() { 1.0 } else { 0.0 };
+        app.coordinate_system.set_origin_mode(app.ui_state.origin_mode);
         app.update_canvas_resolution();
 
         app
     }
 
-    pub fn copy_to_clipboard(&mut self, text: String) -> bool {
-        if let Some(clipboard) = &mut self.clipboard {
-            clipboard.set_contents(text).is_ok()
-        } else {
-            false
+    // System position as shown to the user, after the output transform
+    fn output_position(&self, system_pos: egui::Pos2) -> egui::Pos2 {
+        let transformed = self
+            .ui_state
+            .output_transform
+            .apply(system_pos, self.canvas.get_size());
+        self.ui_state.output_matrix.apply(transformed)
+    }
+
+    // Reminder that shown coordinates are rotated or mirrored
+    fn output_transform_badge(&self, ui: &mut Ui) {
+        let transform = self.ui_state.output_transform;
+        if transform != OutputTransform::None {
+            ui.label(
+                egui::RichText::new(format!("🔄 {}", transform.label()))
+                    .color(Color32::BLACK)
+                    .background_color(Color32::from_rgb(255, 200, 0)),
+            )
+            .on_hover_text("Output transform is active; stored positions are unchanged");
+        }
+        if self.ui_state.output_matrix != AffineTransform::IDENTITY {
+            ui.label(
+                egui::RichText::new("🔄 Matrix")
+                    .color(Color32::BLACK)
+                    .background_color(Color32::from_rgb(255, 200, 0)),
+            )
+            .on_hover_text("A custom transform matrix is active; stored positions are unchanged");
         }
     }
 
-    fn update_canvas_resolution(&mut self) {
-        if let Some((width, height)) = self.resolution_presets.get(&self.ui_state.selected_resolution) {
-            if self.ui_state.selected_resolution == "Custom" {
-                self.canvas.set_size(self.ui_state.custom_width, self.ui_state.custom_height);
-                self.coordinate_system.update_canvas_height(self.ui_state.custom_height);
-            } else {
-                self.canvas.set_size(*width, *height);
-                self.ui_state.custom_width = *width;
-                self.ui_state.custom_height = *height;
-                self.coordinate_system.update_canvas_height(*height);
+    // 3x3 affine matrix editor with presets and a live preview
+    fn show_transform_editor(&mut self, ui: &mut Ui) {
+        let matrix = &mut self.ui_state.output_matrix;
+        egui::Grid::new("transform_matrix").num_columns(3).show(ui, |ui| {
+            ui.add(egui::DragValue::new(&mut matrix.a).speed(0.01));
+            ui.add(egui::DragValue::new(&mut matrix.b).speed(0.01));
+            ui.add(egui::DragValue::new(&mut matrix.tx).speed(1.0));
+            ui.end_row();
+            ui.add(egui::DragValue::new(&mut matrix.c).speed(0.01));
+            ui.add(egui::DragValue::new(&mut matrix.d).speed(0.01));
+            ui.add(egui::DragValue::new(&mut matrix.ty).speed(1.0));
+            ui.end_row();
+            ui.label("0");
+            ui.label("0");
+            ui.label("1");
+            ui.end_row();
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            let presets = [
+                ("Identity", AffineTransform::IDENTITY),
+                ("Flip X", AffineTransform::FLIP_X),
+                ("Flip Y", AffineTransform::FLIP_Y),
+                ("Rotate 90°", AffineTransform::ROTATE_90),
+                ("Scale 2×", AffineTransform::SCALE_2),
+            ];
+            for (name, preset) in presets {
+                if ui.small_button(name).clicked() {
+                    self.ui_state.output_matrix = preset;
+                }
             }
+        });
+
+        self.draw_transform_preview(ui);
+
+        ui.separator();
+        ui.checkbox(&mut self.ui_state.normalise_preserve_aspect, "Preserve aspect ratio");
+        if ui
+            .add_enabled(self.markers.len() >= 2, egui::Button::new("Normalise Coordinates"))
+            .on_hover_text("Stretch the markers' bounding box to fill the canvas")
+            .clicked()
+        {
+            self.normalise_markers();
         }
     }
 
-    // Snap cursor position to nearest grid point if enabled
-    fn apply_grid_snapping(&self, pos: egui::Pos2) -> egui::Pos2 {
-        if self.grid.is_snapping_enabled() {
-            let grid_size = self.grid.get_size();
-            let (canvas_width, canvas_height) = self.canvas.get_size();
+    // Remap marker data that came from another coordinate space onto the canvas
+    fn normalise_markers(&mut self) {
+        let positions: Vec<egui::Pos2> = self.markers.iter().map(|marker| marker.position).collect();
+        let normalised = analysis::normalise_to_canvas(
+            &positions,
+            self.canvas.get_size(),
+            self.ui_state.normalise_preserve_aspect,
+        );
+        let before = self.markers.clone();
+        for (marker, position) in self.markers.iter_mut().zip(normalised) {
+            marker.position = position;
+            marker.system_position = self.coordinate_system.to_system_coordinates(position);
+        }
+        self.record_replace(before);
+        self.check_canvas_bounds();
+    }
 
-            let x = (pos.x / grid_size).round() * grid_size;
-            let y = (pos.y / grid_size).round() * grid_size;
+    // Thumbnail of the canvas outline, markers and unit axes after the transform
+    fn draw_transform_preview(&self, ui: &mut Ui) {
+        let (response, painter) = ui.allocate_painter(egui::vec2(200.0, 120.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, self.background_color());
 
-            if pos.x < grid_size / 2.0 {
-                egui::pos2(0.0, y)
-            } else if pos.x > canvas_width - grid_size / 2.0 {
-                egui::pos2(canvas_width, y)
-            } else if pos.y < grid_size / 2.0 {
-                egui::pos2(x, 0.0)
-            } else if pos.y > canvas_height - grid_size / 2.0 {
-                egui::pos2(x, canvas_height)
-            } else {
-                egui::pos2(x, y)
+        let (width, height) = self.canvas.get_size();
+        let corners = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            .map(|(x, y)| self.output_position(self.coordinate_system.to_system_coordinates(egui::pos2(x, y))));
+        let markers: Vec<egui::Pos2> = self
+            .markers
+            .iter()
+            .map(|marker| self.output_position(marker.system_position))
+            .collect();
+        let origin = self.ui_state.output_matrix.apply(egui::Pos2::ZERO);
+
+        // Fit everything into the thumbnail, keeping the aspect ratio
+        let bounds = egui::Rect::from_points(&[corners[0], corners[1], corners[2], corners[3], origin]);
+        let scale = (rect.shrink(10.0).width() / bounds.width().max(1.0))
+            .min(rect.shrink(10.0).height() / bounds.height().max(1.0));
+        let to_screen = |pos: egui::Pos2| rect.center() + (pos - bounds.center()) * scale;
+
+        let mut outline: Vec<egui::Pos2> = corners.iter().map(|&corner| to_screen(corner)).collect();
+        outline.push(outline[0]);
+        painter.add(egui::Shape::line(outline, Stroke::new(1.0, self.border_color())));
+        for marker in markers {
+            painter.circle_filled(to_screen(marker), 2.0, self.ui_state.marker_color);
+        }
+
+        let matrix = self.ui_state.output_matrix;
+        let axis_length = 30.0;
+        let axes = [
+            (egui::vec2(matrix.a, matrix.c), Color32::from_rgb(230, 50, 50)),
+            (egui::vec2(matrix.b, matrix.d), Color32::from_rgb(50, 200, 50)),
+        ];
+        for (direction, color) in axes {
+            if direction.length() == 0.0 {
+                continue;
             }
-        } else {
-            pos
+            painter.arrow(
+                to_screen(origin),
+                direction.normalized() * axis_length,
+                Stroke::new(2.0, color),
+            );
         }
     }
 
-    // Handle mouse interactions with the canvas
-    fn handle_canvas_interactions(&mut self, ui: &mut Ui, response: egui::Response) {
-        let canvas_rect = response.rect;
-
-        if response.dragged_by(egui::PointerButton::Middle)
-            || (response.dragged_by(egui::PointerButton::Primary) && ui.input(|i| i.modifiers.alt))
+    // Readout for a system position, e.g. "CSS: (206, 48)  device: (412, 96)"
+    fn format_position(&self, system_pos: egui::Pos2) -> String {
+        if !self.ui_state.dual_readout
+            || self.coordinate_system.units() != CoordinateUnits::Pixels
+            || self.coordinate_system.scale() != 1.0
         {
-            self.canvas.pan(response.drag_delta());
+            return self.position_text(system_pos);
         }
+        let output = self.output_position(system_pos);
+        let device = egui::pos2(output.x as i32 as f32, output.y as i32 as f32);
+        let css = self.ui_state.pixel_ratio.to_css(device);
+        format!(
+            "CSS: ({})  device: ({})",
+            coordinate::format_pixels(css),
+            coordinate::format_pixels(device)
+        )
+    }
 
-        if response.hovered() {
-            let scroll_delta = ui.input(|i| i.scroll_delta.y);
-            if scroll_delta != 0.0 {
-                let zoom_factor = if scroll_delta > 0.0 { 1.1 } else { 1.0 / 1.1 };
-                let mouse_pos = ui.input(|i| i.pointer.hover_pos());
-                if let Some(pos) = mouse_pos {
-                    self.canvas.zoom_at(zoom_factor, pos, canvas_rect);
+    // "(x, y)" in the current units, as drawn next to markers
+    fn position_text(&self, system_pos: egui::Pos2) -> String {
+        match self.coordinate_system.units() {
+            CoordinateUnits::Pixels => {
+                let text = if self.coordinate_system.scale() == 1.0 {
+                    let output = self.output_position(system_pos);
+                    format!("({}, {})", output.x as i32, output.y as i32)
+                } else {
+                    format!("({})", self.copy_coordinates(system_pos))
+                };
+                match self.coordinate_system.unit_label() {
+                    "" => text,
+                    unit_label => format!("{} {}", text, unit_label),
                 }
             }
+            CoordinateUnits::Normalized => format!("({})", self.copy_coordinates(system_pos)),
+            // "32.5%, 14.0%", as the percent signs already say what the numbers are
+            CoordinateUnits::Percent => self.copy_coordinates(system_pos),
         }
+    }
 
-        if let Some(mouse_pos) = response.hover_pos() {
-            let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
-            let snapped_pos = if self.grid.is_snapping_enabled() {
-                self.apply_grid_snapping(canvas_pos)
-            } else {
-                canvas_pos
+    // Scale preset picker, with free entry of the scale and unit label for anything else
+    fn scale_settings(&mut self, ui: &mut Ui) {
+        let preset = self.coordinate_system.scale_preset();
+        let mut scale = self.coordinate_system.scale();
+        let mut unit_label = self.coordinate_system.unit_label().to_string();
+        egui::ComboBox::from_label("Scale")
+            .selected_text(match preset {
+                Some(preset) => preset.label(),
+                None => "Custom",
+            })
+            .show_ui(ui, |ui| {
+                for option in ScalePreset::ALL {
+                    if ui.selectable_label(preset == Some(option), option.label()).clicked() {
+                        scale = option.scale();
+                        unit_label = option.unit_label().to_string();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Shown and copied values are pixels times the scale; markers stay where they are");
+        ui.horizontal(|ui| {
+            ui.label("Scale:");
+            ui.add(
+                egui::DragValue::new(&mut scale)
+                    .speed(0.001)
+                    .max_decimals(6)
+                    .clamp_range(0.000001..=1000.0),
+            );
+            ui.label("Unit:");
+            ui.add(egui::TextEdit::singleline(&mut unit_label).desired_width(40.0));
+        });
+        if scale != 1.0 {
+            ui.horizontal(|ui| {
+                ui.label("Decimals:");
+                ui.add(egui::DragValue::new(&mut self.ui_state.scale_decimals).clamp_range(0..=6));
+            });
+        }
+        self.coordinate_system.set_scale(scale, &unit_label);
+    }
+
+    // Output position as a fraction of the (transformed) canvas size
+    fn normalized_position(&self, system_pos: egui::Pos2) -> egui::Pos2 {
+        let size = self.ui_state.output_transform.output_size(self.canvas.get_size());
+        coordinate::normalize(self.output_position(system_pos), size)
+    }
+
+    // Output position in the units picked for copying
+    fn copy_position(&self, system_pos: egui::Pos2) -> egui::Pos2 {
+        // The origin flip is part of the system position, so percentages
+        // measure from whichever corner the origin is in
+        match self.coordinate_system.units() {
+            CoordinateUnits::Pixels => {}
+            CoordinateUnits::Normalized => return self.normalized_position(system_pos),
+            CoordinateUnits::Percent => return (self.normalized_position(system_pos).to_vec2() * 100.0).to_pos2(),
+        }
+        let output = self.output_position(system_pos);
+        let device = egui::pos2(output.x as i32 as f32, output.y as i32 as f32);
+        let pixels = if self.ui_state.dual_readout && self.ui_state.copy_units == CopyUnits::Css {
+            self.ui_state.pixel_ratio.to_css(device)
+        } else {
+            device
+        };
+        self.coordinate_system.apply_scale(pixels)
+    }
+
+    // "x, y" in the units picked for copying
+    fn copy_coordinates(&self, system_pos: egui::Pos2) -> String {
+        let [x, y] = self.format_copy_position(self.copy_position(system_pos));
+        format!("{}, {}", x, y)
+    }
+
+    // Both axes of a copy_position value
+    fn format_copy_position(&self, position: egui::Pos2) -> [String; 2] {
+        match self.coordinate_system.units() {
+            CoordinateUnits::Pixels if self.coordinate_system.scale() == 1.0 => {
+                [position.x, position.y].map(coordinate::format_pixel)
+            }
+            CoordinateUnits::Pixels => {
+                let decimals = self.ui_state.scale_decimals;
+                [position.x, position.y].map(|value| coordinate::format_fraction(value, decimals))
+            }
+            CoordinateUnits::Normalized => {
+                let decimals = self.ui_state.normalized_decimals;
+                [position.x, position.y].map(|value| coordinate::format_fraction(value, decimals))
+            }
+            CoordinateUnits::Percent => {
+                let [x_decimals, y_decimals] = self.ui_state.percent_decimals;
+                [
+                    format!("{}%", coordinate::format_fraction(position.x, x_decimals)),
+                    format!("{}%", coordinate::format_fraction(position.y, y_decimals)),
+                ]
+            }
+        }
+    }
+
+    // What a Copy button puts on the clipboard for one position. Percentages
+    // become a CSS snippet, e.g. "left: 32.5%; top: 14.0%;"
+    fn copy_button_text(&self, system_pos: egui::Pos2, label: Option<&str>) -> String {
+        if self.coordinate_system.units() != CoordinateUnits::Percent {
+            let coordinates = format!("({})", self.copy_coordinates(system_pos));
+            return match label {
+                Some(label) => format!("{}: {}", label, coordinates),
+                None => coordinates,
             };
+        }
+        let [left, top] = self.format_copy_position(self.copy_position(system_pos));
+        let css = format!("left: {}; top: {};", left, top);
+        match label {
+            Some(label) => format!("/* {} */ {}", label.replace("*/", "* /"), css),
+            None => css,
+        }
+    }
 
-            self.ui_state.current_position = self.coordinate_system.to_system_coordinates(snapped_pos);
-            self.ui_state.current_position_raw = self.coordinate_system.to_system_coordinates(canvas_pos);
+    // Grid visibility, size, snapping and presets
+    fn grid_section(&mut self, ui: &mut Ui) {
+        let grid_visible_changed = ui
+            .checkbox(&mut self.ui_state.show_grid, "Show Grid")
+            .changed();
+
+        let mut grid_size_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Grid Size:");
+            for (size, prefix) in [(&mut self.ui_state.grid_size_x, "x: "), (&mut self.ui_state.grid_size_y, "y: ")] {
+                grid_size_changed |= ui
+                    .add(
+                        egui::DragValue::new(size)
+                            .prefix(prefix)
+                            .speed(1.0)
+                            .clamp_range(1.0..=100.0),
+                    )
+                    .changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Style:");
+            for style in GridStyle::ALL {
+                grid_size_changed |= ui
+                    .selectable_value(&mut self.ui_state.grid_style, style, style.label())
+                    .changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Subdivisions:");
+            grid_size_changed |= ui
+                .add(egui::DragValue::new(&mut self.ui_state.grid_subdivisions).clamp_range(1..=16))
+                .on_hover_text("Minor lines split each cell into this many parts when zoomed in far enough")
+                .changed();
+            ui.label("Rotation:");
+            grid_size_changed |= ui
+                .add(
+                    egui::DragValue::new(&mut self.ui_state.grid_rotation)
+                        .suffix("°")
+                        .speed(0.5)
+                        .clamp_range(-90.0..=90.0),
+                )
+                .changed();
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Line Spacing:");
+            ui.add(
+                egui::DragValue::new(&mut self.ui_state.grid_min_spacing)
+                    .suffix(" px")
+                    .clamp_range(5.0..=200.0),
+            );
+            ui.label("to");
+            let min_spacing = self.ui_state.grid_min_spacing;
+            ui.add(
+                egui::DragValue::new(&mut self.ui_state.grid_max_spacing)
+                    .suffix(" px")
+                    .clamp_range(min_spacing * 2.0..=400.0),
+            );
+        })
+        .response
+        .on_hover_text("Drawn lines are thinned out or subdivided to keep this on-screen gap as you zoom");
+        // The range only clamps while dragging
+        self.ui_state.grid_max_spacing = self.ui_state.grid_max_spacing.max(self.ui_state.grid_min_spacing * 2.0);
+
+        let mut grid_snap_changed = ui
+            .checkbox(&mut self.ui_state.enable_snapping, "Snap to Grid")
+            .changed();
+        if self.ui_state.enable_snapping {
+            ui.horizontal(|ui| {
+                ui.label("Snap to:");
+                for mode in SnapMode::ALL {
+                    grid_snap_changed |= ui
+                        .selectable_value(&mut self.ui_state.snap_mode, mode, mode.label())
+                        .changed();
+                }
+            });
+            if self.ui_state.snap_mode == SnapMode::NearestMarker {
+                ui.checkbox(&mut self.ui_state.show_snap_scores, "Show snap scores");
+            }
         }
 
-        if response.clicked() {
-            if let Some(pos) = response.hover_pos() {
-                let border_rect = self.canvas.get_screen_rect(canvas_rect);
-                if border_rect.contains(pos) {
-                    let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
-                    let snapped_pos = if self.grid.is_snapping_enabled() {
-                        self.apply_grid_snapping(canvas_pos)
-                    } else {
-                        canvas_pos
-                    };
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.ui_state.snap_to_markers, "Snap to Markers")
+                .on_hover_text("Line new and dragged markers up with the X or Y of existing ones");
+            ui.add_enabled(
+                self.ui_state.snap_to_markers,
+                egui::DragValue::new(&mut self.ui_state.marker_snap_threshold)
+                    .suffix(" px")
+                    .clamp_range(1.0..=50.0),
+            );
+        });
 
-                    let (canvas_width, canvas_height) = self.canvas.get_size();
+        ui.horizontal(|ui| {
+            let pixel_grid_toggled = ui
+                .toggle_value(&mut self.ui_state.pixel_grid.enabled, "Pixel Grid")
+                .clicked();
+            if pixel_grid_toggled && self.ui_state.pixel_grid.enabled {
+                self.ui_state.grid_size_x = 1.0;
+                self.ui_state.grid_size_y = 1.0;
+                self.ui_state.grid_subdivisions = 1;
+                self.ui_state.grid_rotation = 0.0;
+                grid_size_changed = true;
+            }
+            ui.add_enabled(
+                self.ui_state.pixel_grid.enabled,
+                egui::Checkbox::new(
+                    &mut self.ui_state.pixel_grid.show_pixel_index,
+                    "Show pixel index",
+                ),
+            );
+        });
+        if self.ui_state.pixel_grid.enabled {
+            ui.label("Pixel grid appears at 800% zoom and above");
+        }
 
-                    if snapped_pos.x >= 0.0
-                        && snapped_pos.x <= canvas_width
-                        && snapped_pos.y >= 0.0
-                        && snapped_pos.y <= canvas_height
-                    {
-                        let system_pos = self.coordinate_system.to_system_coordinates(snapped_pos);
-                        let marker = Marker::new(snapped_pos, system_pos, self.ui_state.marker_color);
-                        self.markers.push(marker);
-                    }
+        ui.checkbox(&mut self.ui_state.force_integer_positions, "Integer positions only");
+        if self.ui_state.force_integer_positions {
+            ui.colored_label(
+                Color32::from_rgb(220, 150, 0),
+                "Applies even with grid snapping off. With snapping on, positions are rounded \
+                 after snapping, so points of a fractional grid move to the nearest whole pixel.",
+            );
+        }
+
+        let section_open = self.ui_state.panel_layout.is_open("Grid Presets");
+        if collapsing_section(ui, "Grid Presets", section_open, |ui| {
+            let selected = self
+                .ui_state
+                .selected_grid_preset
+                .min(self.ui_state.grid_presets.len().saturating_sub(1));
+            let selected_name = match self.ui_state.grid_presets.get(selected) {
+                Some(preset) => preset.name.clone(),
+                None => "None".to_string(),
+            };
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("grid_preset")
+                    .selected_text(selected_name)
+                    .show_ui(ui, |ui| {
+                        for (i, preset) in self.ui_state.grid_presets.iter().enumerate() {
+                            ui.selectable_value(&mut self.ui_state.selected_grid_preset, i, &preset.name);
+                        }
+                    });
+                let has_preset = selected < self.ui_state.grid_presets.len();
+                if ui.add_enabled(has_preset, egui::Button::new("Apply")).clicked() {
+                    let preset = &self.ui_state.grid_presets[selected];
+                    self.ui_state.grid_size_x = preset.size_x;
+                    self.ui_state.grid_size_y = preset.size_y;
+                    self.ui_state.grid_style = preset.style;
+                    self.ui_state.grid_subdivisions = preset.subdivisions;
+                    self.ui_state.grid_rotation = preset.rotation;
+                    self.ui_state.pixel_grid.enabled = false;
+                    grid_size_changed = true;
                 }
+                if ui.add_enabled(has_preset, egui::Button::new("Delete Preset")).clicked() {
+                    self.ui_state.grid_presets.remove(selected);
+                    self.ui_state.selected_grid_preset = selected.saturating_sub(1);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.ui_state.new_grid_preset_name)
+                        .hint_text("Preset name")
+                        .desired_width(120.0),
+                );
+                let name = self.ui_state.new_grid_preset_name.trim();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Add Current as Preset"))
+                    .clicked()
+                {
+                    let preset = GridPreset::from_grid(name, &self.grid);
+                    self.ui_state.grid_presets.push(preset);
+                    self.ui_state.selected_grid_preset = self.ui_state.grid_presets.len() - 1;
+                    self.ui_state.new_grid_preset_name.clear();
+                }
+            });
+        }) {
+            self.ui_state.panel_layout.toggle("Grid Presets");
+        }
+
+        if grid_visible_changed || grid_size_changed || grid_snap_changed {
+            self.apply_grid_settings();
+        }
+    }
+
+    // Copy the grid settings from UiState to the grid
+    fn apply_grid_settings(&mut self) {
+        self.grid.set_size(egui::vec2(self.ui_state.grid_size_x, self.ui_state.grid_size_y));
+        self.grid.set_style(self.ui_state.grid_style);
+        self.grid.set_subdivisions(self.ui_state.grid_subdivisions);
+        self.grid.set_rotation(self.ui_state.grid_rotation);
+        self.grid.set_visible(self.ui_state.show_grid);
+        self.grid.set_snapping(self.ui_state.enable_snapping);
+        self.grid.set_snap_mode(self.ui_state.snap_mode);
+    }
+
+    // Marker color, placement and display options
+    fn markers_section(&mut self, ui: &mut Ui, frame: &mut eframe::Frame) {
+        ui.horizontal(|ui| {
+            ui.label("Marker Color:");
+            egui::color_picker::color_edit_button_srgba(
+                ui,
+                &mut self.ui_state.marker_color,
+                egui::color_picker::Alpha::Opaque,
+            );
+            if system_color::SUPPORTED && ui.button("System Picker…").clicked() {
+                if let Some(color) = system_color::pick_color(self.ui_state.marker_color) {
+                    self.ui_state.marker_color = color;
+                }
+                // The dialog belongs to another process, so take focus back
+                frame.focus();
             }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Duplicate Offset:");
+            ui.add(egui::DragValue::new(&mut self.ui_state.duplicate_offset_x).prefix("x: "));
+            ui.add(egui::DragValue::new(&mut self.ui_state.duplicate_offset_y).prefix("y: "));
+        });
+        if ui.button("Quantize Markers…").clicked() {
+            let grid_size = self.grid.get_size();
+            self.ui_state.quantize = Some(QuantizeSettings {
+                step_x: grid_size.x,
+                step_y: grid_size.y,
+                selection_only: !self.ui_state.selected_markers.is_empty(),
+                align_to_grid_lines: false,
+                rounding: RoundingMode::HalfUp,
+            });
         }
+        if ui.button("Validate against…").clicked() {
+            self.load_expected_points();
+        }
+        ui.horizontal(|ui| {
+            ui.label("Color by:");
+            egui::ComboBox::from_id_source("color_by")
+                .selected_text(self.ui_state.color_by.label())
+                .show_ui(ui, |ui| {
+                    for color_by in ColorBy::ALL {
+                        ui.selectable_value(&mut self.ui_state.color_by, color_by, color_by.label());
+                    }
+                });
+            if self.ui_state.color_by != ColorBy::Stored {
+                egui::ComboBox::from_id_source("colormap")
+                    .selected_text(self.ui_state.colormap.label())
+                    .show_ui(ui, |ui| {
+                        for colormap in Colormap::ALL {
+                            ui.selectable_value(&mut self.ui_state.colormap, colormap, colormap.label());
+                        }
+                    });
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.ui_state.show_marker_dots, "Marker Dots");
+            ui.toggle_value(&mut self.ui_state.show_marker_labels, "Marker Labels");
+            ui.toggle_value(&mut self.ui_state.show_offscreen_indicators, "Off-screen Arrows");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Fast rendering above:");
+            ui.add(egui::DragValue::new(&mut self.ui_state.marker_mesh_threshold).suffix(" markers"))
+                .on_hover_text("Draw markers as a single mesh and only label hovered or selected ones");
+        });
+        ui.horizontal(|ui| {
+            let mut limit_disabled = self.ui_state.max_markers == usize::MAX;
+            if !limit_disabled {
+                ui.label("Marker limit:");
+                ui.add(
+                    egui::DragValue::new(&mut self.ui_state.max_markers)
+                        .speed(1.0)
+                        .clamp_range(10..=10000),
+                );
+            }
+            if ui.checkbox(&mut limit_disabled, "Disable limit").changed() {
+                self.ui_state.max_markers = if limit_disabled { usize::MAX } else { 500 };
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Undo steps:");
+            let response = ui.add(egui::DragValue::new(&mut self.ui_state.undo_depth).clamp_range(1..=1000));
+            if response.changed() {
+                self.undo_stack.set_depth(self.ui_state.undo_depth);
+            }
+        });
+    }
 
-        if response.secondary_clicked() {
-            if let Some(pos) = response.hover_pos() {
-                let border_rect = self.canvas.get_screen_rect(canvas_rect);
-                if border_rect.contains(pos) {
-                    let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
-                    self.remove_nearby_marker(canvas_pos);
+    // Export format, options and the Export button
+    fn export_section(&mut self, ui: &mut Ui, ctx: &Context) {
+        egui::ComboBox::from_label("Format")
+            .selected_text(self.ui_state.export_format.label())
+            .show_ui(ui, |ui| {
+                for format in ExportFormat::ALL {
+                    ui.selectable_value(
+                        &mut self.ui_state.export_format,
+                        format,
+                        format.label(),
+                    );
                 }
+            });
+
+        if matches!(
+            self.ui_state.export_format,
+            ExportFormat::Svg | ExportFormat::Png
+        ) {
+            ui.horizontal(|ui| {
+                ui.label("Export DPI:");
+                ui.add(
+                    egui::DragValue::new(&mut self.ui_state.export_dpi)
+                        .speed(1.0)
+                        .clamp_range(72.0..=600.0),
+                );
+            });
+        }
+
+        if self.ui_state.export_format == ExportFormat::HtmlArea {
+            ui.horizontal(|ui| {
+                ui.label("Area radius:");
+                ui.add(
+                    egui::DragValue::new(&mut self.ui_state.html_area_radius)
+                        .speed(1.0)
+                        .clamp_range(1..=500)
+                        .suffix(" px"),
+                );
+            });
+        }
+
+        if self.ui_state.export_format == ExportFormat::AndroidPointResource {
+            ui.horizontal(|ui| {
+                ui.label("dp per px:");
+                ui.add(
+                    egui::DragValue::new(&mut self.ui_state.android_dp_scale)
+                        .speed(0.01)
+                        .clamp_range(0.1..=4.0),
+                )
+                .on_hover_text("1.0 for a 160 dpi screenshot, 0.5 for 320 dpi");
+            });
+            ui.label("Save as res/values/coordinates.xml in your app module");
+        }
+
+        if self.ui_state.export_format == ExportFormat::Png {
+            let layout = &mut self.ui_state.png_layout;
+            egui::Grid::new("png_layout").num_columns(2).show(ui, |ui| {
+                ui.label("Scale:");
+                ui.add(
+                    egui::DragValue::new(&mut layout.scale)
+                        .speed(0.05)
+                        .clamp_range(0.5..=4.0)
+                        .suffix("x"),
+                );
+                ui.end_row();
+                ui.label("Margin:");
+                ui.add(
+                    egui::DragValue::new(&mut layout.margin)
+                        .speed(1.0)
+                        .clamp_range(0.0..=1000.0)
+                        .suffix(" px"),
+                );
+                ui.end_row();
+                ui.label("Background:");
+                ui.color_edit_button_srgba(&mut layout.background);
+                ui.end_row();
+            });
+            ui.checkbox(&mut layout.labels, "Coordinate labels");
+            ui.checkbox(&mut layout.title, "Title with name and date");
+
+            let (width, height) = layout.output_size(self.canvas.get_size());
+            let megapixels = width as f32 * height as f32 / 1_000_000.0;
+            ui.label(format!("Output: {} x {} px ({:.1} MP)", width, height, megapixels));
+            if megapixels > export::LARGE_EXPORT_MEGAPIXELS {
+                ui.colored_label(
+                    Color32::from_rgb(230, 120, 0),
+                    "Very large image; export may be slow and use a lot of memory",
+                );
+            }
+        }
+
+        ui.checkbox(&mut self.ui_state.export_split_by_group, "Split by group");
+        if self.ui_state.export_split_by_group {
+            ui.horizontal(|ui| {
+                ui.label("Base name:");
+                ui.text_edit_singleline(&mut self.ui_state.export_basename);
+            });
+            ui.checkbox(&mut self.ui_state.export_hidden_groups, "Include hidden groups");
+        }
+
+        if self.ui_state.export_format == ExportFormat::CssGrid {
+            ui.checkbox(&mut self.ui_state.css_grid_fractional, "Use fr units");
+            let mut css = export::to_css_grid(&self.markers, self.ui_state.css_grid_fractional);
+            ui.add(
+                egui::TextEdit::multiline(&mut css)
+                    .code_editor()
+                    .desired_rows(5)
+                    .interactive(false),
+            );
+            if ui.button("Copy CSS").clicked() {
+                self.copy_to_clipboard(css);
+            }
+        }
+
+        let export_label = match self.ui_state.export_format {
+            ExportFormat::CssGrid => "Export CSS Grid…",
+            ExportFormat::AndroidPointResource => "Export Android Resource…",
+            _ => "Export…",
+        };
+        if ui.button(export_label).clicked() {
+            if self.ui_state.export_split_by_group {
+                self.export_split_by_group(ctx);
+            } else {
+                self.export_to_file();
+            }
+        }
+
+        ui.separator();
+        ui.label("Project JSON").on_hover_text("Markers with canvas size, origin and grid size");
+        ui.horizontal(|ui| {
+            if ui.button("Copy as JSON").clicked() {
+                self.copy_to_clipboard(self.project_json());
+            }
+            if ui.button("Save JSON…").clicked() {
+                self.save_project_json();
+            }
+            if ui.button("Import JSON…").clicked() {
+                self.import_project_json();
             }
+        });
+
+        ui.separator();
+        let indices = self.copy_all_indices();
+        ui.horizontal(|ui| {
+            ui.label("CSS clip-path decimals:");
+            ui.add(egui::DragValue::new(&mut self.ui_state.clip_path_decimals).clamp_range(0..=4));
+        });
+        if ui
+            .add_enabled(indices.len() >= 3, egui::Button::new("Copy clip-path"))
+            .on_hover_text("clip-path: polygon(...) through the Copy All markers in list order")
+            .on_disabled_hover_text("Needs at least three markers")
+            .clicked()
+        {
+            let positions: Vec<egui::Pos2> = indices.iter().map(|&i| self.markers[i].position).collect();
+            let css = export::to_clip_path(&positions, self.canvas.get_size(), self.ui_state.clip_path_decimals);
+            self.copy_to_clipboard(css);
+        }
+
+        ui.separator();
+        ui.label("SVG outline").on_hover_text("A line through the Copy All markers in list order");
+        ui.checkbox(&mut self.ui_state.svg_close_path, "Close path");
+        ui.checkbox(&mut self.ui_state.svg_marker_circles, "Marker circles");
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(indices.len() >= 2, |ui| {
+                if ui.button("Copy SVG").clicked() {
+                    self.copy_to_clipboard(self.svg_outline());
+                }
+                if ui.button("Save SVG…").clicked() {
+                    self.save_svg_outline(ctx);
+                }
+            })
+            .response
+            .on_disabled_hover_text("Needs at least two markers");
+        });
+
+        if let Some(status) = &self.ui_state.export_status {
+            ui.label(status);
         }
     }
 
-    fn remove_nearby_marker(&mut self, position: egui::Pos2) {
-        const CLICK_THRESHOLD: f32 = 10.0;
+    fn svg_outline(&self) -> String {
+        let colored = self.export_marker_list();
+        let markers: Vec<Marker> = self.copy_all_indices().iter().map(|&i| colored[i].clone()).collect();
+        export::to_svg_outline(
+            self.canvas.get_size(),
+            &markers,
+            self.ui_state.svg_close_path,
+            self.ui_state.svg_marker_circles,
+        )
+    }
 
-        if let Some(index) = self.markers.iter().position(|marker| {
-            let delta = marker.position - position;
-            delta.length() < CLICK_THRESHOLD
-        }) {
-            self.markers.remove(index);
+    fn save_svg_outline(&mut self, ctx: &Context) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("SVG", &["svg"])
+            .set_file_name("outline.svg");
+        if let Some(path) = dialog.save_file() {
+            let message = match export::write_atomic(&path, self.svg_outline().as_bytes()) {
+                Ok(()) => format!("Saved {}", path.display()),
+                Err(err) => format!("SVG export failed: {}", err),
+            };
+            self.show_toast(ctx, message);
         }
     }
 
-    // Draw the main canvas and all its elements
-    fn draw_canvas(&self, ui: &mut Ui) -> egui::Response {
-        let (response, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
-        let canvas_rect = response.rect;
-        let bg_color = if self.ui_state.dark_mode {
-            Color32::from_rgb(20, 20, 20)
-        } else {
-            Color32::from_rgb(240, 240, 240)
-        };
-        painter.rect_filled(canvas_rect, 0.0, bg_color);
+    fn project_json(&self) -> String {
+        let mut project = ProjectExport::new(
+            self.canvas.get_size(),
+            &self.coordinate_system,
+            self.grid.get_size().x,
+            &self.groups,
+            &self.markers,
+        );
+        project.measurements = self.measurement_records();
+        project.measurement_units = self.coordinate_system.unit_label().to_string();
+        project.to_json()
+    }
 
-        let border_rect = self.canvas.get_screen_rect(canvas_rect);
+    fn save_project_json(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("Project JSON", &["json"])
+            .set_file_name("markers.json");
+        if let Some(path) = dialog.save_file() {
+            self.ui_state.export_status = Some(match std::fs::write(&path, self.project_json()) {
+                Ok(()) => {
+                    recent::push_recent_file(&mut self.ui_state.recent_sessions, &path);
+                    format!("Exported {} markers", self.markers.len())
+                }
+                Err(err) => format!("Export failed: {}", err),
+            });
+        }
+    }
 
-        if self.grid.is_visible() {
-            self.draw_grid(&painter, canvas_rect, border_rect);
+    // Appends the markers of a project file to the current ones. Nothing is
+    // imported if any marker is invalid or outside the canvas.
+    fn import_project_json(&mut self) {
+        if let Some(path) = rfd::FileDialog::new().add_filter("Project JSON", &["json"]).pick_file() {
+            self.import_project_file(&path);
         }
+    }
 
-        let border_color = if self.ui_state.dark_mode {
-            Color32::from_rgb(150, 150, 150)
-        } else {
-            Color32::from_rgb(100, 100, 100)
-        };
-        painter.rect_stroke(border_rect, 0.0, Stroke::new(2.0, border_color));
+    fn import_project_file(&mut self, path: &Path) {
+        let result = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| ProjectExport::from_json(&json))
+            .map_err(|err| vec![err])
+            .and_then(|project| {
+                let markers = project.to_markers(&self.coordinate_system, self.groups.active(), self.canvas.get_size());
+                match (markers, project.to_measurements()) {
+                    (Ok(markers), Ok(measurements)) => Ok((project, markers, measurements)),
+                    (markers, measurements) => Err(markers
+                        .err()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .chain(measurements.err().unwrap_or_default())
+                        .collect()),
+                }
+            });
+        match result {
+            Ok((project, mut markers, measurements)) => {
+                if let Some(error) = self.marker_limit_error(markers.len()) {
+                    self.ui_state.import_errors = vec![error];
+                    return;
+                }
+                // Layers are only created once the whole file is known to be valid.
+                // Markers from files without layers stay in the active group.
+                let before = self.snapshot();
+                let layers = project.merge_layers(&mut self.groups);
+                for (marker, entry) in markers.iter_mut().zip(&project.markers) {
+                    if let Some(&id) = layers.get(&entry.layer) {
+                        marker.group = id;
+                    }
+                }
+                self.ui_state.export_status = Some(if measurements.is_empty() {
+                    format!("Imported {} markers", markers.len())
+                } else {
+                    format!("Imported {} markers and {} measurements", markers.len(), measurements.len())
+                });
+                recent::push_recent_file(&mut self.ui_state.recent_sessions, path);
+                // One undo step removes the markers together with the layers and measurements they brought
+                self.markers.extend(markers);
+                self.measurements.extend(measurements);
+                self.record_snapshot(before);
+            }
+            Err(errors) => self.ui_state.import_errors = errors,
+        }
+    }
+
+    fn show_import_errors(&mut self, ctx: &Context) {
+        if self.ui_state.import_errors.is_empty() {
+            return;
+        }
+        let mut close = false;
+        egui::Window::new("Import Failed")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Nothing was imported:");
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    for error in &self.ui_state.import_errors {
+                        ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+                    }
+                });
+                close = ui.button("OK").clicked();
+            });
+        if close {
+            self.ui_state.import_errors.clear();
+        }
+    }
+
+    // Theme, rulers and crosshair
+    fn appearance_section(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.ui_state.dark_mode, "Dark Mode");
+        if ui
+            .checkbox(&mut self.ui_state.compact_mode, "Compact Mode")
+            .on_hover_text("Collapse the settings panel to a strip of section icons")
+            .changed()
+        {
+            self.ui_state.compact_section = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Gamma:");
+            ui.add(
+                egui::DragValue::new(&mut self.ui_state.gamma)
+                    .speed(0.01)
+                    .clamp_range(1.0..=3.0),
+            );
+        });
+        egui::ComboBox::from_label("Color Profile")
+            .selected_text(self.ui_state.color_profile.label())
+            .show_ui(ui, |ui| {
+                for profile in ColorProfile::ALL {
+                    ui.selectable_value(&mut self.ui_state.color_profile, profile, profile.label());
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.ui_state.show_minimap, "Show Minimap");
+            ui.add_enabled_ui(self.ui_state.show_minimap, |ui| {
+                egui::ComboBox::from_id_source("minimap_corner")
+                    .selected_text(self.ui_state.minimap_corner.label())
+                    .show_ui(ui, |ui| {
+                        for corner in MinimapCorner::ALL {
+                            ui.selectable_value(&mut self.ui_state.minimap_corner, corner, corner.label());
+                        }
+                    });
+            });
+        });
+        ui.checkbox(&mut self.ui_state.smooth_zoom, "Smooth Zoom")
+            .on_hover_text("Ease into the new zoom level when scrolling instead of jumping to it");
+        ui.checkbox(&mut self.ui_state.latch_hide_overlays, "Tab toggles overlays")
+            .on_hover_text("Press Tab to hide overlays and again to restore them, instead of holding it");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.ui_state.show_rulers, "Rulers");
+            ui.add_enabled(
+                self.ui_state.show_rulers,
+                egui::Slider::new(&mut self.ui_state.ruler_thickness, 10.0..=40.0).text("Thickness"),
+            );
+        });
+
+        let section_open = self.ui_state.panel_layout.is_open("Crosshair");
+        if collapsing_section(ui, "Crosshair", section_open, |ui| {
+            let crosshair = &mut self.ui_state.crosshair;
+            egui::ComboBox::from_label("Shape")
+                .selected_text(crosshair.shape.label())
+                .show_ui(ui, |ui| {
+                    for shape in CrosshairShape::ALL {
+                        ui.selectable_value(&mut crosshair.shape, shape, shape.label());
+                    }
+                });
+        }) {
+            self.ui_state.panel_layout.toggle("Crosshair");
+        }
+    }
+
+    // Narrow strip of section icons; a clicked icon pops its section out in a
+    // window that closes again on a click elsewhere
+    fn show_compact_panel(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        let strip = egui::SidePanel::right("compact_panel")
+            .resizable(false)
+            .exact_width(40.0)
+            .show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    if ui.button("⏴").on_hover_text("Expand the settings panel").clicked() {
+                        self.ui_state.compact_mode = false;
+                        self.ui_state.compact_section = None;
+                    }
+                    ui.separator();
+                    for section in PanelSection::ALL {
+                        let open = self.ui_state.compact_section == Some(section);
+                        if ui.selectable_label(open, section.icon()).on_hover_text(section.label()).clicked() {
+                            self.ui_state.compact_section = if open { None } else { Some(section) };
+                        }
+                    }
+                });
+            });
+
+        let section = match self.ui_state.compact_section {
+            Some(section) => section,
+            None => return,
+        };
+        // Clicks inside combo boxes land outside the window, so don't count them
+        let popup_was_open = ctx.memory(|memory| memory.any_popup_open());
+        let mut open = true;
+        let popout = egui::Window::new(section.label())
+            .open(&mut open)
+            .collapsible(false)
+            .default_pos(strip.response.rect.left_top() - egui::vec2(300.0, 0.0))
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| match section {
+                    PanelSection::Grid => self.grid_section(ui),
+                    PanelSection::Markers => self.markers_section(ui, frame),
+                    PanelSection::Export => self.export_section(ui, ctx),
+                    PanelSection::Appearance => self.appearance_section(ui),
+                });
+            });
+
+        let clicked_outside = ctx.input(|i| {
+            i.pointer.primary_clicked()
+                && i.pointer.interact_pos().is_some_and(|pos| {
+                    let in_popout = popout.as_ref().is_some_and(|popout| popout.response.rect.contains(pos));
+                    !in_popout && !strip.response.rect.contains(pos)
+                })
+        });
+        if !open || (clicked_outside && !popup_was_open) {
+            self.ui_state.compact_section = None;
+        }
+    }
+
+    fn show_settings_panel(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        egui::SidePanel::right("settings_panel")
+            .resizable(true)
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                if self.ui_state.panel_layout.pin_current_position {
+                    self.show_current_position(ui);
+                    ui.separator();
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.heading("Settings");
+                        if ui
+                            .small_button("Reset to Defaults")
+                            .on_hover_text("Delete the saved settings file and restore the defaults")
+                            .clicked()
+                        {
+                            self.reset_settings();
+                        }
+                    });
+                    if let Some(error) = &self.ui_state.settings_error {
+                        ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+                    }
+                    ui.separator();
+
+                    let section_open = self.ui_state.panel_layout.is_open("Canvas Size");
+                    if collapsing_section(ui, "Canvas Size", section_open, |ui| {
+                        egui::ComboBox::from_label("Resolution")
+                            .selected_text(&self.ui_state.selected_resolution)
+                            .show_ui(ui, |ui| {
+                                for preset in self.resolution_presets.keys() {
+                                    ui.selectable_value(
+                                        &mut self.ui_state.selected_resolution,
+                                        preset.clone(),
+                                        preset,
+                                    );
+                                }
+                            });
+
+                        if self.ui_state.selected_resolution == "Custom" {
+                            ui.horizontal(|ui| {
+                                ui.label("Width:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.ui_state.custom_width)
+                                        .speed(1.0)
+                                        .clamp_range(100.0..=10000.0),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Height:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.ui_state.custom_height)
+                                        .speed(1.0)
+                                        .clamp_range(100.0..=10000.0),
+                                );
+                            });
+                        }
+
+                        let section_open = self.ui_state.panel_layout.is_open("Multiple Canvas Sizes");
+                        if collapsing_section(ui, "Multiple Canvas Sizes", section_open, |ui| {
+                            self.nested_sizes_section(ui)
+                        }) {
+                            self.ui_state.panel_layout.toggle("Multiple Canvas Sizes");
+                        }
+
+                        ui.checkbox(&mut self.ui_state.rescale_on_resize, "Rescale markers on canvas resize")
+                            .on_hover_text("Keep markers at the same relative position when the resolution changes");
+                        if self.update_canvas_resolution() {
+                            self.show_toast(ctx, "Markers rescaled".to_string());
+                        }
+
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label("Device Pixel Ratio:");
+                            egui::ComboBox::from_id_source("pixel_ratio")
+                                .selected_text(format!("{}x", self.ui_state.pixel_ratio.ratio))
+                                .show_ui(ui, |ui| {
+                                    for ratio in PixelRatio::PRESETS {
+                                        ui.selectable_value(
+                                            &mut self.ui_state.pixel_ratio.ratio,
+                                            ratio,
+                                            format!("{}x", ratio),
+                                        );
+                                    }
+                                });
+                            ui.add(
+                                egui::DragValue::new(&mut self.ui_state.pixel_ratio.ratio)
+                                    .speed(0.05)
+                                    .clamp_range(0.5..=8.0),
+                            )
+                            .on_hover_text("Custom ratio");
+                        });
+                        ui.checkbox(&mut self.ui_state.dual_readout, "Show CSS and device pixels");
+                        ui.add_enabled(
+                            self.ui_state.dual_readout,
+                            egui::Checkbox::new(
+                                &mut self.ui_state.pixel_ratio.keep_half_pixels,
+                                "Keep .5 CSS pixels",
+                            ),
+                        );
+                    }) {
+                        self.ui_state.panel_layout.toggle("Canvas Size");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Grid");
+                    if collapsing_section(ui, "Grid", section_open, |ui| self.grid_section(ui)) {
+                        self.ui_state.panel_layout.toggle("Grid");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Coordinate System");
+                    if collapsing_section(ui, "Coordinate System", section_open, |ui| {
+                        // Edits a copy so the switch can wait on the migration dialog
+                        let mut origin_mode = self.ui_state.origin_mode;
+                        let mut changed = false;
+                        for mode in OriginMode::ALL {
+                            changed |= ui.radio_value(&mut origin_mode, mode, mode.label()).changed();
+                        }
+
+                        if changed {
+                            match self.ui_state.origin_migration {
+                                Some(migration) => self.change_origin(origin_mode, migration),
+                                None if self.markers.is_empty() => {
+                                    self.change_origin(origin_mode, OriginMigration::KeepVisual);
+                                }
+                                None => {
+                                    self.ui_state.pending_origin_change = Some(PendingOriginChange {
+                                        origin_mode,
+                                        remember: false,
+                                    });
+                                }
+                            }
+                        }
+
+                        if let Some(migration) = self.ui_state.origin_migration {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("On origin change: {}", migration.label()));
+                                if ui.small_button("Ask Again").clicked() {
+                                    self.ui_state.origin_migration = None;
+                                }
+                            });
+                        }
+
+                        ui.separator();
+                        match self.coordinate_system.custom_origin() {
+                            Some(origin) => {
+                                ui.label("Custom origin (canvas px):");
+                                let mut edited = origin;
+                                let (x_response, y_response) = ui
+                                    .horizontal(|ui| {
+                                        ui.label("X:");
+                                        let x_response = ui.add(egui::DragValue::new(&mut edited.x).speed(1.0));
+                                        ui.label("Y:");
+                                        let y_response = ui.add(egui::DragValue::new(&mut edited.y).speed(1.0));
+                                        (x_response, y_response)
+                                    })
+                                    .inner;
+                                if edited != origin {
+                                    if x_response.dragged() || y_response.dragged() {
+                                        self.drag_custom_origin(Some(edited));
+                                    } else {
+                                        self.set_custom_origin(Some(edited));
+                                    }
+                                }
+                                if x_response.drag_released() || y_response.drag_released() {
+                                    self.finish_origin_drag();
+                                }
+                                if ui.button("Reset Origin").clicked() {
+                                    self.set_custom_origin(None);
+                                }
+                            }
+                            None => {
+                                ui.label("Drag the origin marker, or use the Set Origin tool, to move the origin");
+                            }
+                        }
+
+                        ui.separator();
+                        let mut units = self.coordinate_system.units();
+                        egui::ComboBox::from_label("Units")
+                            .selected_text(units.label())
+                            .show_ui(ui, |ui| {
+                                for option in CoordinateUnits::ALL {
+                                    ui.selectable_value(&mut units, option, option.label());
+                                }
+                            })
+                            .response
+                            .on_hover_text("Changes displayed and copied values only; markers stay where they are");
+                        self.coordinate_system.set_units(units);
+                        match units {
+                            CoordinateUnits::Pixels => self.scale_settings(ui),
+                            CoordinateUnits::Normalized => {
+                                ui.horizontal(|ui| {
+                                    ui.label("Decimals:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.ui_state.normalized_decimals).clamp_range(2..=6),
+                                    );
+                                });
+                            }
+                            CoordinateUnits::Percent => {
+                                let [x_decimals, y_decimals] = &mut self.ui_state.percent_decimals;
+                                ui.horizontal(|ui| {
+                                    ui.label("Decimals X:");
+                                    ui.add(egui::DragValue::new(x_decimals).clamp_range(0..=6));
+                                    ui.label("Y:");
+                                    ui.add(egui::DragValue::new(y_decimals).clamp_range(0..=6));
+                                });
+                            }
+                        }
+
+                        ui.separator();
+                        egui::ComboBox::from_label("Output Transform")
+                            .selected_text(self.ui_state.output_transform.label())
+                            .show_ui(ui, |ui| {
+                                for transform in OutputTransform::ALL {
+                                    ui.selectable_value(
+                                        &mut self.ui_state.output_transform,
+                                        transform,
+                                        transform.label(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("Applied to displayed, copied and exported values only");
+
+                        let section_open = self.ui_state.panel_layout.is_open("Transform Editor");
+                        if collapsing_section(ui, "Transform Editor", section_open, |ui| {
+                            self.show_transform_editor(ui);
+                        }) {
+                            self.ui_state.panel_layout.toggle("Transform Editor");
+                        }
+                    }) {
+                        self.ui_state.panel_layout.toggle("Coordinate System");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Copy Format");
+                    if collapsing_section(ui, "Copy Format", section_open, |ui| {
+                        let template = &mut self.ui_state.copy_template;
+                        ui.checkbox(&mut template.enabled, "Use custom format");
+                        ui.add_enabled_ui(template.enabled, |ui| {
+                            egui::Grid::new("copy_template").num_columns(2).show(ui, |ui| {
+                                ui.label("Item:");
+                                ui.add(egui::TextEdit::singleline(&mut template.item).code_editor());
+                                ui.end_row();
+                                ui.label("Separator:");
+                                ui.add(egui::TextEdit::singleline(&mut template.separator).code_editor());
+                                ui.end_row();
+                                ui.label("Prefix:");
+                                ui.add(egui::TextEdit::singleline(&mut template.prefix).code_editor());
+                                ui.end_row();
+                                ui.label("Suffix:");
+                                ui.add(egui::TextEdit::singleline(&mut template.suffix).code_editor());
+                                ui.end_row();
+                            });
+                            ui.label(format!(
+                                "Placeholders: {}",
+                                format::TEMPLATE_PLACEHOLDERS.map(|name| format!("{{{}}}", name)).join(" ")
+                            ));
+                            ui.label("Write {{x}} for a literal {x}, \\n for a new line");
+                            if ui.button("Reset to Default").clicked() {
+                                *template = CopyTemplate {
+                                    enabled: true,
+                                    ..CopyTemplate::default()
+                                };
+                            }
+                        });
+
+                        if template.enabled {
+                            if let Err(err) = format::validate_template(&template.item) {
+                                ui.colored_label(Color32::from_rgb(230, 160, 0), format!("{}, copied as written", err));
+                            }
+                            if self.markers.is_empty() {
+                                ui.label("Preview (current position):");
+                                ui.monospace(self.current_position_copy_text());
+                            } else {
+                                ui.label("Preview (first marker):");
+                                ui.monospace(self.copy_text(&[0]));
+                            }
+                        }
+                    }) {
+                        self.ui_state.panel_layout.toggle("Copy Format");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Stamps");
+                    if collapsing_section(ui, "Stamps", section_open, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.ui_state.new_stamp_name)
+                                    .hint_text("Stamp name")
+                                    .desired_width(120.0),
+                            );
+                            let has_selection = !self.ui_state.selected_markers.is_empty();
+                            if ui
+                                .add_enabled(has_selection, egui::Button::new("Save from Selection"))
+                                .clicked()
+                            {
+                                self.save_stamp_from_selection();
+                            }
+                        });
+
+                        let mut stamp_to_remove = None;
+                        for (i, stamp) in self.ui_state.stamps.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.radio(self.ui_state.active_stamp == Some(i), "").clicked() {
+                                    self.ui_state.active_stamp = Some(i);
+                                    self.ui_state.active_tool = Tool::Stamp;
+                                }
+                                ui.add(egui::TextEdit::singleline(&mut stamp.name).desired_width(100.0));
+                                ui.label(format!("{} pts", stamp.offsets.len()));
+                                if ui.button("Delete").clicked() {
+                                    stamp_to_remove = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(index) = stamp_to_remove {
+                            self.ui_state.stamps.remove(index);
+                            self.ui_state.active_stamp = match self.ui_state.active_stamp {
+                                Some(active) if active == index => None,
+                                Some(active) if active > index => Some(active - 1),
+                                active => active,
+                            };
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Scale:");
+                            ui.add(
+                                egui::DragValue::new(&mut self.ui_state.stamp_scale)
+                                    .speed(0.01)
+                                    .clamp_range(0.1..=10.0),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Import…").clicked() {
+                                self.import_stamps();
+                            }
+                            if ui
+                                .add_enabled(!self.ui_state.stamps.is_empty(), egui::Button::new("Export…"))
+                                .clicked()
+                            {
+                                self.export_stamps();
+                            }
+                        });
+                    }) {
+                        self.ui_state.panel_layout.toggle("Stamps");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Views");
+                    if collapsing_section(ui, "Views", section_open, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.ui_state.new_bookmark_name)
+                                    .hint_text("View name")
+                                    .desired_width(120.0),
+                            );
+                            if ui.button("Save View").clicked() {
+                                let name = match self.ui_state.new_bookmark_name.trim() {
+                                    "" => format!("View {}", self.ui_state.view_bookmarks.len() + 1),
+                                    name => name.to_string(),
+                                };
+                                self.save_view_bookmark(None, name);
+                                self.ui_state.new_bookmark_name.clear();
+                            }
+                        });
+
+                        let mut recall = None;
+                        let mut bookmark_to_remove = None;
+                        for (i, bookmark) in self.ui_state.view_bookmarks.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                // Only the first nine views get number keys
+                                let slot = if i < 9 { format!("{}", i + 1) } else { "–".to_string() };
+                                ui.label(slot);
+                                ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(100.0));
+                                if ui.button("Go").clicked() {
+                                    recall = Some(i + 1);
+                                }
+                                if ui.button("Delete").clicked() {
+                                    bookmark_to_remove = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(slot) = recall {
+                            self.recall_view_bookmark(ctx, slot);
+                        }
+                        if let Some(index) = bookmark_to_remove {
+                            self.ui_state.view_bookmarks.remove(index);
+                        }
+                        ui.label("Ctrl+1-9 saves the current view, 1-9 recalls it");
+                    }) {
+                        self.ui_state.panel_layout.toggle("Views");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Groups");
+                    if collapsing_section(ui, "Groups", section_open, |ui| {
+                        let active = self.groups.active();
+                        let mut new_active = None;
+                        let mut group_to_remove = None;
+                        let mut recolored = None;
+
+                        for group in self.groups.groups_mut() {
+                            let count = self
+                                .markers
+                                .iter()
+                                .filter(|marker| marker.group == group.id)
+                                .count();
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .radio(group.id == active, "")
+                                    .on_hover_text("Place new markers in this group")
+                                    .clicked()
+                                {
+                                    new_active = Some(group.id);
+                                }
+                                ui.checkbox(&mut group.visible, "").on_hover_text("Show group");
+                                if egui::color_picker::color_edit_button_srgba(
+                                    ui,
+                                    &mut group.color,
+                                    egui::color_picker::Alpha::Opaque,
+                                )
+                                .changed()
+                                {
+                                    recolored = Some((group.id, group.color));
+                                }
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut group.name).desired_width(100.0),
+                                );
+                                ui.label(format!("({})", count));
+                                if group.id != GroupManager::DEFAULT_GROUP
+                                    && ui.small_button("Delete").clicked()
+                                {
+                                    group_to_remove = Some(group.id);
+                                }
+                            });
+                        }
+
+                        if let Some((id, color)) = recolored {
+                            let before = self.markers.clone();
+                            for marker in self.markers.iter_mut().filter(|marker| marker.group == id) {
+                                marker.color = color;
+                            }
+                            self.record_replace(before);
+                            if id == active {
+                                self.ui_state.marker_color = color;
+                            }
+                        }
+                        if let Some(id) = new_active {
+                            self.set_active_group(id);
+                        }
+                        if let Some(id) = group_to_remove {
+                            if self.groups.remove(id) {
+                                // Markers of a deleted group fall back to the default group
+                                let before = self.markers.clone();
+                                for marker in &mut self.markers {
+                                    if marker.group == id {
+                                        marker.group = GroupManager::DEFAULT_GROUP;
+                                    }
+                                }
+                                self.record_replace(before);
+                            }
+                        }
+
+                        if ui.button("Add Group").clicked() {
+                            let name = format!("Group {}", self.groups.groups().len() + 1);
+                            let id = self.groups.add(name);
+                            self.set_active_group(id);
+                        }
+                    }) {
+                        self.ui_state.panel_layout.toggle("Groups");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Markers");
+                    if collapsing_section(ui, "Markers", section_open, |ui| self.markers_section(ui, frame)) {
+                        self.ui_state.panel_layout.toggle("Markers");
+                    }
+                    let section_open = self.ui_state.panel_layout.is_open("Measurements");
+                    if collapsing_section(ui, "Measurements", section_open, |ui| self.measurements_section(ui, ctx)) {
+                        self.ui_state.panel_layout.toggle("Measurements");
+                    }
+                    let section_open = self.ui_state.panel_layout.is_open("Place at Coordinate");
+                    if collapsing_section(ui, "Place at Coordinate", section_open, |ui| self.place_at_section(ui)) {
+                        self.ui_state.panel_layout.toggle("Place at Coordinate");
+                    }
+                    let section_open = self.ui_state.panel_layout.is_open("Background Image");
+                    if collapsing_section(ui, "Background Image", section_open, |ui| self.background_image_section(ui, ctx)) {
+                        self.ui_state.panel_layout.toggle("Background Image");
+                    }
+                    let section_open = self.ui_state.panel_layout.is_open("Region Colors");
+                    if collapsing_section(ui, "Region Colors", section_open, |ui| self.region_colors_section(ui)) {
+                        self.ui_state.panel_layout.toggle("Region Colors");
+                    }
+
+                    ui.separator();
+
+                    if !self.ui_state.panel_layout.pin_current_position {
+                        self.show_current_position(ui);
+                        ui.separator();
+                    }
+
+                    ui.heading("Saved Markers");
+
+                    if self.markers.len() >= 2 {
+                        self.show_playback_controls(ui);
+                    }
+
+                    if self.markers.len() >= self.ui_state.max_markers {
+                        ui.colored_label(
+                            Color32::from_rgb(220, 50, 50),
+                            format!(
+                                "Marker limit reached ({}). Delete some or increase the limit.",
+                                self.ui_state.max_markers
+                            ),
+                        );
+                    }
+
+                    if !self.ui_state.selected_markers.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{} selected", self.ui_state.selected_markers.len()));
+                            if ui.button("Fit to Selection").clicked() {
+                                self.fit_to_selection();
+                            }
+                        });
+                    }
+
+                    let mut marker_to_remove: Option<usize> = None;
+                    let mut marker_to_duplicate: Option<usize> = None;
+
+                    ui.horizontal(|ui| {
+                        if !self.markers.is_empty() && ui.button("Copy All Coordinates").clicked() {
+                            let all_coords = self.copy_text(&self.copy_all_indices());
+                            self.copy_to_clipboard(all_coords);
+                        }
+                        self.copy_as_menu(ui);
+                    });
+
+                    if !self.markers.is_empty() {
+                        if ui.button("Export CSV…").clicked() {
+                            self.ui_state.show_csv_dialog = true;
+                        }
+                        if ui.button("Duplicate All").clicked() {
+                            let all: Vec<usize> = (0..self.markers.len()).collect();
+                            self.duplicate_markers(ctx, &all);
+                        }
+                        if ui.button("Ghost All").on_hover_text("Turn every marker into a reference point").clicked() {
+                            let before = self.markers.clone();
+                            for marker in &mut self.markers {
+                                marker.ghost = true;
+                            }
+                            self.record_replace(before);
+                        }
+                    }
+
+                    // Fill whatever is left of the visible panel, but never shrink below 200px
+                    let remaining_height = ui.clip_rect().bottom() - ui.cursor().top();
+                    egui::ScrollArea::vertical()
+                        .max_height(remaining_height.max(200.0))
+                        .show(ui, |ui| {
+                            let markers_data: Vec<(usize, String, String)> = self
+                                .markers
+                                .iter()
+                                .enumerate()
+                                .map(|(i, marker)| {
+                                    let readout = self.format_position(marker.system_position);
+                                    let coords = if self.ui_state.copy_template.enabled {
+                                        self.copy_text(&[i])
+                                    } else {
+                                        // e.g. "OK button: (412, 391)"
+                                        self.copy_button_text(marker.system_position, marker.label_text())
+                                    };
+                                    (i, readout, coords)
+                                })
+                                .collect();
+
+                            for (i, readout, coords) in markers_data {
+                                let marker_text = format!("{}. {}", i + 1, readout);
+                                let expanded = self.ui_state.expanded_notes.contains(&i);
+                                ui.horizontal(|ui| {
+                                    let arrow = if expanded { "⏷" } else { "⏵" };
+                                    if ui.small_button(arrow).on_hover_text("Details and note").clicked() {
+                                        if expanded {
+                                            self.ui_state.expanded_notes.remove(&i);
+                                        } else {
+                                            self.ui_state.expanded_notes.insert(i);
+                                        }
+                                    }
+
+                                    let mut label_text = self.markers[i].label.clone().unwrap_or_default();
+                                    let label_edit = egui::TextEdit::singleline(&mut label_text)
+                                        .hint_text("Label")
+                                        .desired_width(90.0);
+                                    let label_response = ui.add(label_edit);
+                                    if label_response.changed() {
+                                        self.markers[i].label = Some(label_text).filter(|text| !text.is_empty());
+                                    }
+                                    self.track_text_edit(&label_response);
+
+                                    let label = ui.label(marker_text);
+                                    let mut tooltip = Vec::new();
+                                    if self.ui_state.inset_readout {
+                                        tooltip.push(self.inset_summary(self.markers[i].position, Some(i)));
+                                    }
+                                    if !self.markers[i].note.is_empty() {
+                                        tooltip.push(self.markers[i].note_preview());
+                                    }
+                                    if !tooltip.is_empty() {
+                                        label.on_hover_text(tooltip.join("\n"));
+                                    }
+
+                                    let copy = ui.button("Copy").on_hover_text("Right-click to copy as JSON");
+                                    if copy.clicked() {
+                                        self.copy_to_clipboard(coords.clone());
+                                    }
+                                    copy.context_menu(|ui| {
+                                        if ui.button("Copy as JSON").clicked() {
+                                            let json = self.marker_json(i);
+                                            self.copy_to_clipboard(json);
+                                            ui.close_menu();
+                                        }
+                                    });
+
+                                    if ui.button("Duplicate").clicked() {
+                                        marker_to_duplicate = Some(i);
+                                    }
+
+                                    let ghost = self.markers[i].ghost;
+                                    if ui.button(if ghost { "Make Real" } else { "Make Ghost" }).clicked() {
+                                        let before = self.markers.clone();
+                                        self.markers[i].ghost = !ghost;
+                                        self.record_replace(before);
+                                    }
+
+                                    if ui.button("Delete").clicked() {
+                                        marker_to_remove = Some(i);
+                                    }
+                                });
+
+                                if expanded {
+                                    self.show_marker_details(ui, i);
+                                }
+                            }
+                        });
+
+                    if let Some(index) = marker_to_duplicate {
+                        self.duplicate_markers(ctx, &[index]);
+                    }
+
+                    if let Some(index) = marker_to_remove {
+                        if index < self.markers.len() {
+                            self.remove_marker(index);
+                        }
+                    }
+
+                    ui.separator();
+
+                    let section_open = self.ui_state.panel_layout.is_open("Session Info");
+                    if collapsing_section(ui, "Session Info", section_open, |ui| {
+                        let session = &mut self.ui_state.session;
+                        egui::Grid::new("session_info").num_columns(2).show(ui, |ui| {
+                            ui.label("Title:");
+                            ui.text_edit_singleline(&mut session.title);
+                            ui.end_row();
+                            ui.label("Author:");
+                            ui.text_edit_singleline(&mut session.author);
+                            ui.end_row();
+                        });
+                        ui.label("Description:");
+                        ui.add(egui::TextEdit::multiline(&mut session.description).desired_rows(3));
+
+                        ui.label("Fields:");
+                        let mut removed = None;
+                        for (i, (key, value)) in session.fields.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::TextEdit::singleline(key).hint_text("Key").desired_width(80.0));
+                                ui.add(egui::TextEdit::singleline(value).hint_text("Value").desired_width(120.0));
+                                if ui.small_button("✖").clicked() {
+                                    removed = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = removed {
+                            session.fields.remove(i);
+                        }
+                        if let Some(key) = session.duplicate_key() {
+                            ui.colored_label(
+                                Color32::from_rgb(220, 50, 50),
+                                format!("Duplicate key \"{}\"; only the first row is exported", key),
+                            );
+                        }
+                        if ui.button("Add Field").clicked() {
+                            session.fields.push((String::new(), String::new()));
+                        }
+                    }) {
+                        self.ui_state.panel_layout.toggle("Session Info");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Export");
+                    if collapsing_section(ui, "Export", section_open, |ui| self.export_section(ui, ctx)) {
+                        self.ui_state.panel_layout.toggle("Export");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Appearance");
+                    if collapsing_section(ui, "Appearance", section_open, |ui| self.appearance_section(ui)) {
+                        self.ui_state.panel_layout.toggle("Appearance");
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Input");
+                    if collapsing_section(ui, "Input", section_open, |ui| {
+                        let mut bindings = self.ui_state.mouse_bindings;
+                        egui::Grid::new("mouse_bindings").show(ui, |ui| {
+                            for (name, binding) in bindings.entries_mut() {
+                                ui.label(name);
+                                egui::ComboBox::from_id_source((name, "button"))
+                                    .selected_text(input::button_label(binding.button))
+                                    .show_ui(ui, |ui| {
+                                        for button in input::BUTTONS {
+                                            ui.selectable_value(&mut binding.button, button, input::button_label(button));
+                                        }
+                                    });
+                                egui::ComboBox::from_id_source((name, "modifier"))
+                                    .selected_text(binding.modifier.label())
+                                    .show_ui(ui, |ui| {
+                                        for modifier in BindingModifier::ALL {
+                                            ui.selectable_value(&mut binding.modifier, modifier, modifier.label());
+                                        }
+                                    });
+                                ui.end_row();
+                            }
+                        });
+
+                        // Conflicting edits are rejected and the previous bindings kept
+                        if bindings != self.ui_state.mouse_bindings {
+                            self.ui_state.binding_warning = bindings.conflict();
+                            if self.ui_state.binding_warning.is_none() {
+                                self.ui_state.mouse_bindings = bindings;
+                            }
+                        }
+                        if let Some(warning) = &self.ui_state.binding_warning {
+                            ui.colored_label(Color32::from_rgb(255, 170, 0), warning);
+                        }
+
+                        if ui.button("Reset to Defaults").clicked() {
+                            self.ui_state.mouse_bindings = MouseBindings::default();
+                            self.ui_state.binding_warning = None;
+                        }
+                    }) {
+                        self.ui_state.panel_layout.toggle("Input");
+                    }
+
+                    #[cfg(feature = "websocket")]
+                    {
+                        let section_open = self.ui_state.panel_layout.is_open("Diagnostics");
+                        if collapsing_section(ui, "Diagnostics", section_open, |ui| {
+                            ui.horizontal(|ui| {
+                                let toggled = ui
+                                    .checkbox(&mut self.ui_state.stream_enabled, "Stream position over WebSocket")
+                                    .changed();
+                                ui.add_enabled(
+                                    !self.ui_state.stream_enabled,
+                                    egui::DragValue::new(&mut self.ui_state.stream_port).prefix("port "),
+                                );
+                                if toggled {
+                                    self.update_position_stream();
+                                }
+                            });
+                            match (&self.position_stream, &self.ui_state.stream_error) {
+                                (Some(stream), _) => {
+                                    ui.label(format!("Listening on ws://{}", stream.address()));
+                                }
+                                (None, Some(error)) => {
+                                    ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+                                }
+                                (None, None) => {
+                                    ui.label("Stream stopped");
+                                }
+                            }
+                        }) {
+                            self.ui_state.panel_layout.toggle("Diagnostics");
+                        }
+                    }
+
+                    let section_open = self.ui_state.panel_layout.is_open("Help");
+                    if collapsing_section(ui, "Help", section_open, |ui| {
+                        let bindings = self.ui_state.mouse_bindings;
+                        ui.label(format!("• {}-click to place a marker", bindings.place.label()));
+                        ui.label("• Use the Lasso tool and drag to select markers");
+                        ui.label("• Place column/row line markers to build a CSS Grid export");
+                        ui.label("• Save selected markers as a stamp, then click with the Stamp tool to place it");
+                        ui.label("• Press G and type col,row or @x,y to place a marker precisely");
+                        ui.label("• Ctrl+Shift+C copies the visible canvas as an image");
+                        ui.label("• Press F1 for a list of keyboard shortcuts");
+                        ui.label("• Hold Tab to hide everything drawn over the canvas");
+                        ui.label(format!("• {}-click to remove a marker at cursor position", bindings.delete.label()));
+                        ui.label("• Double-click a marker to edit its coordinates");
+                        ui.label("• Drag a marker to move it");
+                        ui.label("• Use 'Delete' button to remove specific markers from the list");
+                        ui.label("• Use 'Copy All Coordinates' to copy all marker coordinates at once");
+                        if bindings.alt_drag_pans() {
+                            ui.label(format!("• {}-drag or Alt+drag to pan", bindings.pan.label()));
+                        } else {
+                            ui.label(format!("• {}-drag to pan", bindings.pan.label()));
+                        }
+                        ui.label("• Drag the origin marker to move the origin, double-click it to reset");
+                        ui.label("• Scroll to zoom in/out");
+                        ui.label("• Ctrl+Shift+drag a rectangle to zoom to it, Esc cancels");
+                        ui.label("• Shift+drag a rectangle to select the markers inside it");
+                        ui.label("• Adjust grid settings for precise positioning");
+                        ui.label("• Grid snapping finds the nearest grid intersection to your cursor");
+                    }) {
+                        self.ui_state.panel_layout.toggle("Help");
+                    }
+                });
+            });
+    }
+
+    // Cursor readout, either pinned above the settings or inline before the markers
+    fn show_current_position(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Current Position");
+            self.output_transform_badge(ui);
+            ui.toggle_value(&mut self.ui_state.panel_layout.pin_current_position, "📌")
+                .on_hover_text("Pin above the scrolling settings");
+        });
+        ui.horizontal(|ui| {
+            ui.label(self.format_position(self.ui_state.current_position));
+            if ui.button("Copy").clicked() {
+                let coords_text = self.current_position_copy_text();
+                self.copy_to_clipboard(coords_text);
+            }
+        });
+        if self.ui_state.active_tool == Tool::Angle {
+            self.angle_readout(ui);
+        }
+
+        ui.checkbox(&mut self.ui_state.inset_readout, "Show insets");
+        if self.ui_state.inset_readout {
+            let canvas_pos = self.coordinate_system.from_system_coordinates(self.ui_state.current_position);
+            ui.horizontal(|ui| {
+                ui.label(self.inset_summary(canvas_pos, None));
+                if ui.button("Copy CSS").clicked() {
+                    self.copy_to_clipboard(self.inset_css(canvas_pos));
+                }
+            });
+        }
+
+        if self.ui_state.dual_readout {
+            ui.horizontal(|ui| {
+                ui.label("Copy as:");
+                for units in CopyUnits::ALL {
+                    ui.selectable_value(&mut self.ui_state.copy_units, units, units.label());
+                }
+            });
+        }
+
+        if self.is_snapping_active() {
+            ui.label("Snapping enabled");
+        } else {
+            let x = self.ui_state.current_position_raw.x as f32;
+            let y = self.ui_state.current_position_raw.y as f32;
+            ui.label(format!("Raw: ({:.1}, {:.1})", x, y));
+        }
+    }
+
+    // A distance in the units picked for copying
+    fn distance_in_copy_units(&self, device: f32) -> f32 {
+        if self.ui_state.dual_readout && self.ui_state.copy_units == CopyUnits::Css {
+            self.ui_state.pixel_ratio.to_css(egui::pos2(device, device)).x
+        } else {
+            device.round()
+        }
+    }
+
+    // Signed distances to the nearest column and row guide, skipping the
+    // marker being described
+    fn guide_offsets(&self, canvas_pos: egui::Pos2, exclude: Option<usize>) -> (Option<f32>, Option<f32>) {
+        let guides = |kind: MarkerKind| {
+            self.markers
+                .iter()
+                .enumerate()
+                .filter(move |(i, marker)| {
+                    marker.kind == kind && Some(*i) != exclude && self.groups.is_visible(marker.group)
+                })
+                .map(|(_, marker)| marker.position)
+        };
+        (
+            analysis::nearest_guide(canvas_pos.x, guides(MarkerKind::ColumnLine).map(|pos| pos.x)),
+            analysis::nearest_guide(canvas_pos.y, guides(MarkerKind::RowLine).map(|pos| pos.y)),
+        )
+    }
+
+    // e.g. "T 24 · R 16 · B 1056 · L 1904 | guides x +12, y -8"
+    fn inset_summary(&self, canvas_pos: egui::Pos2, exclude: Option<usize>) -> String {
+        let insets = analysis::Insets::of(canvas_pos, self.canvas.get_size());
+        let mut summary = format!(
+            "T {} · R {} · B {} · L {}",
+            coordinate::format_pixel(self.distance_in_copy_units(insets.top)),
+            coordinate::format_pixel(self.distance_in_copy_units(insets.right)),
+            coordinate::format_pixel(self.distance_in_copy_units(insets.bottom)),
+            coordinate::format_pixel(self.distance_in_copy_units(insets.left)),
+        );
+        let offsets = self.guide_offsets(canvas_pos, exclude);
+        let offset_labels: Vec<String> = [("x", offsets.0), ("y", offsets.1)]
+            .into_iter()
+            .filter_map(|(axis, offset)| {
+                let offset = self.distance_in_copy_units(offset?);
+                Some(format!("{} {:+}", axis, offset))
+            })
+            .collect();
+        if !offset_labels.is_empty() {
+            summary.push_str(&format!(" | guides {}", offset_labels.join(", ")));
+        }
+        summary
+    }
+
+    // CSS insets from the nearest horizontal and vertical edge, e.g. "top: 24px; right: 16px;"
+    fn inset_css(&self, canvas_pos: egui::Pos2) -> String {
+        let insets = analysis::Insets::of(canvas_pos, self.canvas.get_size());
+        let vertical = if insets.top <= insets.bottom {
+            ("top", insets.top)
+        } else {
+            ("bottom", insets.bottom)
+        };
+        let horizontal = if insets.left <= insets.right {
+            ("left", insets.left)
+        } else {
+            ("right", insets.right)
+        };
+        [vertical, horizontal]
+            .iter()
+            .map(|(side, distance)| {
+                format!("{}: {}px;", side, coordinate::format_pixel(self.distance_in_copy_units(*distance)))
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn template_values(&self, index: usize) -> format::TemplateValues {
+        let marker = &self.markers[index];
+        let [x, y] = self.format_copy_position(self.copy_position(marker.system_position));
+        let (width, height) = self.canvas.get_size();
+        format::TemplateValues {
+            index: index + 1,
+            x,
+            y,
+            label: marker.label_text().map(str::to_string),
+            group: self.groups.get(marker.group).map(|group| group.name.clone()).unwrap_or_default(),
+            color: marker.color,
+            normalized: (marker.position.x / width, marker.position.y / height),
+            canvas_size: self.ui_state.output_transform.output_size((width, height)),
+        }
+    }
+
+    // The cursor as if it were the next marker: its number, the active group and color
+    fn cursor_template_values(&self) -> format::TemplateValues {
+        let system_pos = self.ui_state.current_position;
+        let canvas_pos = self.coordinate_system.from_system_coordinates(system_pos);
+        let [x, y] = self.format_copy_position(self.copy_position(system_pos));
+        let (width, height) = self.canvas.get_size();
+        format::TemplateValues {
+            index: self.markers.len() + 1,
+            x,
+            y,
+            label: Some("Cursor".to_string()),
+            group: self.groups.get(self.groups.active()).map(|group| group.name.clone()).unwrap_or_default(),
+            color: self.ui_state.marker_color,
+            normalized: (canvas_pos.x / width, canvas_pos.y / height),
+            canvas_size: self.ui_state.output_transform.output_size((width, height)),
+        }
+    }
+
+    // Current position Copy button text, honouring the custom format
+    fn current_position_copy_text(&self) -> String {
+        if self.ui_state.copy_template.enabled {
+            format::render_template(&self.ui_state.copy_template.item, &self.cursor_template_values())
+        } else {
+            self.copy_button_text(self.ui_state.current_position, None)
+        }
+    }
+
+    // Markers included by Copy All: visible and not ghosts
+    fn copy_all_indices(&self) -> Vec<usize> {
+        (0..self.markers.len())
+            .filter(|&i| !self.markers[i].ghost && self.groups.is_visible(self.markers[i].group))
+            .collect()
+    }
+
+    // Marker positions in the copy units as numeric literals for code. Floats
+    // use the decimals of the current units.
+    fn code_exporter(&self, indices: &[usize]) -> export::CodeExporter {
+        let integers = self.ui_state.code_integers;
+        let decimals = match self.coordinate_system.units() {
+            CoordinateUnits::Pixels => [self.ui_state.code_decimals; 2],
+            CoordinateUnits::Normalized => [self.ui_state.normalized_decimals; 2],
+            CoordinateUnits::Percent => self.ui_state.percent_decimals,
+        };
+        let points = indices
+            .iter()
+            .map(|&i| {
+                let marker = &self.markers[i];
+                let position = self.copy_position(marker.system_position);
+                export::CodePoint {
+                    x: export::code_number(position.x, integers, decimals[0]),
+                    y: export::code_number(position.y, integers, decimals[1]),
+                    label: marker.label_text().map(str::to_string),
+                }
+            })
+            .collect();
+        export::CodeExporter::new(points, integers)
+    }
+
+    // Copies the Copy All markers as source code. No markers gives an empty literal.
+    fn copy_as_menu(&mut self, ui: &mut Ui) {
+        ui.menu_button("Copy as Code", |ui| {
+            ui.checkbox(&mut self.ui_state.code_integers, "Integers");
+            if !self.ui_state.code_integers && self.coordinate_system.units() == CoordinateUnits::Pixels {
+                ui.horizontal(|ui| {
+                    ui.label("Decimals:");
+                    ui.add(egui::DragValue::new(&mut self.ui_state.code_decimals).clamp_range(1..=6));
+                });
+            }
+            ui.checkbox(&mut self.ui_state.code_labels, "Labels in Python and JSON data")
+                .on_hover_text("Otherwise labels only appear as comments, which JSON has none of");
+            ui.checkbox(&mut self.ui_state.python_dicts, "Python as dicts");
+            ui.checkbox(&mut self.ui_state.json_arrays, "JSON as nested arrays");
+            ui.separator();
+            for language in CodeLanguage::ALL {
+                if ui.button(language.label()).on_hover_text(language.example()).clicked() {
+                    let code = self.code_exporter(&self.copy_all_indices());
+                    let labels = self.ui_state.code_labels;
+                    let text = match language {
+                        CodeLanguage::Rust => code.rust(),
+                        CodeLanguage::Python => code.python(self.ui_state.python_dicts, labels),
+                        CodeLanguage::JavaScript => code.javascript(),
+                        CodeLanguage::C => code.c(),
+                        CodeLanguage::Json => code.json(self.ui_state.json_arrays, labels),
+                    };
+                    self.copy_to_clipboard(text);
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    // One marker as JSON, with the "Copy as Code" number and label settings
+    fn marker_json(&self, index: usize) -> String {
+        self.code_exporter(&[index])
+            .json_single(self.ui_state.json_arrays, self.ui_state.code_labels)
+    }
+
+    // Text for the Copy buttons of the given markers, honouring the custom format
+    fn copy_text(&self, indices: &[usize]) -> String {
+        let template = &self.ui_state.copy_template;
+        if !template.enabled {
+            let lines: Vec<String> = indices
+                .iter()
+                .map(|&i| {
+                    let marker = &self.markers[i];
+                    match marker.label_text() {
+                        Some(label) => self.copy_button_text(marker.system_position, Some(label)),
+                        None => format!("{}. {}", i + 1, self.copy_button_text(marker.system_position, None)),
+                    }
+                })
+                .collect();
+            return lines.join("\n");
+        }
+
+        let items: Vec<String> = indices
+            .iter()
+            .map(|&i| format::render_template(&template.item, &self.template_values(i)))
+            .collect();
+        if indices.len() == 1 {
+            return items.into_iter().next().unwrap_or_default();
+        }
+        format!(
+            "{}{}{}",
+            format::unescape(&template.prefix),
+            items.join(&format::unescape(&template.separator)),
+            format::unescape(&template.suffix)
+        )
+    }
+
+    pub fn copy_to_clipboard(&mut self, text: String) -> bool {
+        if let Some(clipboard) = &mut self.clipboard {
+            clipboard.set_contents(text).is_ok()
+        } else {
+            false
+        }
+    }
+
+    // Ask for a destination directory and write one file per non-empty group
+    fn export_split_by_group(&mut self, ctx: &Context) {
+        let format = self.ui_state.export_format;
+        let directory = match rfd::FileDialog::new().pick_folder() {
+            Some(directory) => directory,
+            None => return,
+        };
+
+        let groups: Vec<(u32, &str)> = self
+            .groups
+            .groups()
+            .iter()
+            .filter(|group| group.visible || self.ui_state.export_hidden_groups)
+            .filter(|group| self.markers.iter().any(|marker| marker.group == group.id))
+            .map(|group| (group.id, group.name.as_str()))
+            .collect();
+        let names: Vec<&str> = groups.iter().map(|(_, name)| *name).collect();
+        let file_names = export::split_file_names(&self.ui_state.export_basename, &names, format.extension());
+
+        let mut marker_count = 0;
+        for ((id, _), file_name) in groups.iter().zip(&file_names) {
+            let markers: Vec<Marker> = self
+                .export_marker_list()
+                .into_iter()
+                .filter(|marker| marker.group == *id)
+                .collect();
+            let path = directory.join(file_name);
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            let result = export::export_markers(
+                format,
+                &name,
+                self.canvas.get_size(),
+                &markers,
+                &self.export_settings(),
+            )
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|err| err.to_string()));
+
+            if let Err(err) = result {
+                self.ui_state.export_status = Some(format!("Export of {} failed: {}", file_name, err));
+                return;
+            }
+            marker_count += markers.len();
+        }
+
+        let summary = format!("{} files, {} markers", file_names.len(), marker_count);
+        self.ui_state.export_status = Some(format!("Exported {}", summary));
+        self.show_toast(ctx, summary);
+    }
+
+    fn colormap_colors(&self) -> Option<(Vec<Color32>, (f32, f32))> {
+        colormap::marker_colors(&self.markers, self.ui_state.color_by, self.ui_state.colormap)
+    }
+
+    // Markers as drawn, so exports match the active "color by" mode
+    fn export_marker_list(&self) -> Vec<Marker> {
+        let mut markers = self.markers.clone();
+        if let Some((colors, _)) = self.colormap_colors() {
+            for (marker, color) in markers.iter_mut().zip(colors) {
+                marker.color = color;
+            }
+        }
+        markers
+    }
+
+    fn export_settings(&self) -> ExportSettings<'_> {
+        ExportSettings {
+            dpi: self.ui_state.export_dpi,
+            gamma: self.ui_state.gamma,
+            color_profile: self.ui_state.color_profile,
+            output_transform: self.ui_state.output_transform,
+            css_grid_fractional: self.ui_state.css_grid_fractional,
+            html_area_radius: self.ui_state.html_area_radius,
+            android_dp_scale: self.ui_state.android_dp_scale,
+            png_layout: self.ui_state.png_layout,
+            session: self.ui_state.session.clone(),
+            annotations: self.annotations.clone(),
+            layers: self.canvas_layers(),
+        }
+    }
+
+    // Ask for a destination file and write all markers in the selected export format
+    fn export_to_file(&mut self) {
+        let format = self.ui_state.export_format;
+        let dialog = rfd::FileDialog::new()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(&format!("coordinates.{}", format.extension()));
+
+        if let Some(path) = dialog.save_file() {
+            // The file name doubles as the frame/session name in the export
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Coordinates".to_string());
+            let contents = export::export_markers(
+                format,
+                &name,
+                self.canvas.get_size(),
+                &self.export_marker_list(),
+                &self.export_settings(),
+            );
+
+            let result = contents.and_then(|bytes| std::fs::write(&path, bytes).map_err(|err| err.to_string()));
+            self.ui_state.export_status = Some(match result {
+                Ok(()) => format!("Exported {} markers", self.markers.len()),
+                Err(err) => format!("Export failed: {}", err),
+            });
+        }
+    }
+
+    fn show_csv_dialog(&mut self, ctx: &Context) {
+        if !self.ui_state.show_csv_dialog {
+            return;
+        }
+        let mut save = false;
+        let mut save_measurements = false;
+        let mut cancel = false;
+        let has_measurements = !self.measurements.is_empty();
+        let options = &mut self.ui_state.csv_options;
+        egui::Window::new("Export CSV")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label("Delimiter")
+                    .selected_text(options.delimiter.label())
+                    .show_ui(ui, |ui| {
+                        for delimiter in CsvDelimiter::ALL {
+                            ui.selectable_value(&mut options.delimiter, delimiter, delimiter.label());
+                        }
+                    });
+                ui.checkbox(&mut options.include_header, "Header row");
+                ui.checkbox(&mut options.integers, "Round coordinates to integers");
+                ui.label("Columns:");
+                ui.horizontal_wrapped(|ui| {
+                    for (column, enabled) in CsvColumn::ALL.iter().zip(options.columns.iter_mut()) {
+                        ui.checkbox(enabled, column.header());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let any_column = options.columns.contains(&true);
+                    save = ui.add_enabled(any_column, egui::Button::new("Save…")).clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+                ui.separator();
+                ui.label("Measurements:");
+                save_measurements = ui
+                    .add_enabled(has_measurements, egui::Button::new("Save Measurements…"))
+                    .on_hover_text("All measurements in their own file, with the delimiter and header row above")
+                    .on_disabled_hover_text("No measurements kept")
+                    .clicked();
+            });
+        if save {
+            self.export_csv(ctx);
+        }
+        if save_measurements {
+            self.save_measurements_csv(ctx);
+        }
+        if save || save_measurements || cancel {
+            self.ui_state.show_csv_dialog = false;
+        }
+    }
+
+    fn export_csv(&mut self, ctx: &Context) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("markers.csv");
+        if let Some(path) = dialog.save_file() {
+            let markers = self.export_marker_list();
+            let positions: Vec<egui::Pos2> = markers
+                .iter()
+                .map(|marker| self.coordinate_system.apply_scale(self.output_position(marker.system_position)))
+                .collect();
+            let csv = export::to_csv(&markers, &positions, &self.ui_state.csv_options);
+            let message = match export::write_atomic(&path, csv.as_bytes()) {
+                Ok(()) => format!("Exported {} markers", markers.len()),
+                Err(err) => format!("CSV export failed: {}", err),
+            };
+            self.show_toast(ctx, message);
+        }
+    }
+
+    // Writes the config file whenever a persisted setting differs from the last save
+    fn save_settings_if_changed(&mut self) {
+        let settings = Settings::from_ui_state(&self.ui_state);
+        if settings != self.saved_settings {
+            self.ui_state.settings_error = settings
+                .save()
+                .err()
+                .map(|err| format!("Couldn't save settings: {}", err));
+            self.saved_settings = settings;
+        }
+    }
+
+    fn reset_settings(&mut self) {
+        self.ui_state.settings_error = Settings::delete()
+            .err()
+            .map(|err| format!("Couldn't delete settings: {}", err));
+        let defaults = Settings::default();
+        defaults.apply(&mut self.ui_state);
+        self.saved_settings = defaults;
+
+        self.apply_grid_settings();
+        self.update_canvas_resolution();
+    }
+
+    // Returns true when markers were rescaled to follow the new size
+    fn update_canvas_resolution(&mut self) -> bool {
+        let old_size = self.canvas.get_size();
+        if let Some((width, height)) = self.resolution_presets.get(&self.ui_state.selected_resolution) {
+            if self.ui_state.selected_resolution == "Custom" {
+                self.canvas.set_size(self.ui_state.custom_width, self.ui_state.custom_height);
+                self.coordinate_system
+                    .update_canvas_size(self.ui_state.custom_width, self.ui_state.custom_height);
+            } else {
+                self.canvas.set_size(*width, *height);
+                self.ui_state.custom_width = *width;
+                self.ui_state.custom_height = *height;
+                self.coordinate_system.update_canvas_size(*width, *height);
+            }
+        }
+        let new_size = self.canvas.get_size();
+        if new_size == old_size {
+            return false;
+        }
+
+        let rescale = self.ui_state.rescale_on_resize && !self.markers.is_empty();
+        if rescale {
+            let before = self.markers.clone();
+            let scale = egui::vec2(new_size.0 / old_size.0, new_size.1 / old_size.1);
+            for marker in &mut self.markers {
+                marker.position = (marker.position.to_vec2() * scale).to_pos2();
+                marker.system_position = self.coordinate_system.to_system_coordinates(marker.position);
+            }
+            self.record_replace(before);
+        }
+        self.check_canvas_bounds();
+        rescale
+    }
+
+    fn markers_outside_canvas(&self) -> Vec<usize> {
+        self.markers
+            .iter()
+            .enumerate()
+            .filter(|(_, marker)| !self.is_inside_canvas(marker.position))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Run after operations that can push markers off the canvas
+    fn check_canvas_bounds(&mut self) {
+        self.ui_state.outside_canvas_count = self.markers_outside_canvas().len();
+    }
+
+    fn clamp_markers_to_canvas(&mut self) {
+        let (canvas_width, canvas_height) = self.canvas.get_size();
+        let before = self.markers.clone();
+        for index in self.markers_outside_canvas() {
+            let marker = &mut self.markers[index];
+            marker.position = egui::pos2(
+                marker.position.x.clamp(0.0, canvas_width),
+                marker.position.y.clamp(0.0, canvas_height),
+            );
+            marker.system_position = self.coordinate_system.to_system_coordinates(marker.position);
+        }
+        self.record_replace(before);
+        self.check_canvas_bounds();
+    }
+
+    fn delete_markers_outside_canvas(&mut self) {
+        let before = self.markers.clone();
+        let outside = self.markers_outside_canvas();
+        let mut index = 0;
+        self.markers.retain(|_| {
+            index += 1;
+            !outside.contains(&(index - 1))
+        });
+        self.ui_state.selected_markers.clear();
+        self.ui_state.expanded_notes.clear();
+        self.record_replace(before);
+        self.check_canvas_bounds();
+    }
+
+    // Non-modal banner listing what to do with markers outside the canvas
+    fn show_outside_canvas_banner(&mut self, ctx: &Context) {
+        let count = self.ui_state.outside_canvas_count;
+        if count == 0 {
+            return;
+        }
+
+        egui::TopBottomPanel::top("outside_canvas_banner").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let message = if count == 1 {
+                    "1 marker is outside the canvas".to_string()
+                } else {
+                    format!("{} markers are outside the canvas", count)
+                };
+                ui.colored_label(Color32::from_rgb(255, 170, 0), message);
+
+                if ui.button("Select").clicked() {
+                    self.ui_state.selected_markers = self.markers_outside_canvas().into_iter().collect();
+                }
+                if ui.button("Clamp to Border").clicked() {
+                    self.clamp_markers_to_canvas();
+                }
+                if ui.button("Delete").clicked() {
+                    self.delete_markers_outside_canvas();
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.ui_state.outside_canvas_count = 0;
+                }
+            });
+        });
+    }
+
+    fn is_snapping_active(&self) -> bool {
+        self.grid.is_snapping_enabled()
+            || self.ui_state.pixel_grid.enabled
+            || self.ui_state.force_integer_positions
+            || self.ui_state.snap_to_markers
+    }
+
+    // Visible markers in snapping range of `pos` with their snap scores
+    fn snap_candidates(&self, pos: egui::Pos2) -> Vec<(egui::Pos2, f32)> {
+        const SCREEN_SNAP_RADIUS: f32 = 20.0;
+        let radius = SCREEN_SNAP_RADIUS / self.canvas.get_zoom();
+
+        self.markers
+            .iter()
+            .filter(|marker| self.groups.is_visible(marker.group) && !marker.ghost)
+            .filter(|marker| (marker.position - pos).length() <= radius)
+            .map(|marker| (marker.position, grid::snap_score(marker.position, pos, &self.grid, radius)))
+            .collect()
+    }
+
+    // Snap cursor position to nearest grid point if enabled. Forced integer
+    // positions are rounded afterwards, so they apply with or without a grid.
+    fn apply_grid_snapping(&self, pos: egui::Pos2) -> egui::Pos2 {
+        let snapped = self.snap_to_grid(pos);
+        if self.ui_state.force_integer_positions {
+            snapped.round()
+        } else {
+            snapped
+        }
+    }
+
+    // Grid snapping followed by the marker axis pass, which wins on the axes it
+    // finds a guide for. `exclude` is a marker being dragged, so it can't
+    // snap to itself.
+    fn apply_all_snapping(&self, pos: egui::Pos2, exclude: Option<usize>) -> egui::Pos2 {
+        let snapped = self.snap_to_grid(pos);
+        let (guide_x, guide_y) = self.marker_axis_guides(pos, exclude);
+        let snapped = egui::pos2(guide_x.unwrap_or(snapped.x), guide_y.unwrap_or(snapped.y));
+        if self.ui_state.force_integer_positions {
+            snapped.round()
+        } else {
+            snapped
+        }
+    }
+
+    // The closest marker X and Y (canvas coordinates) within the snap threshold of `pos`
+    fn marker_axis_guides(&self, pos: egui::Pos2, exclude: Option<usize>) -> (Option<f32>, Option<f32>) {
+        if !self.ui_state.snap_to_markers {
+            return (None, None);
+        }
+        let threshold = self.ui_state.marker_snap_threshold / self.canvas.get_zoom();
+        let positions: Vec<egui::Pos2> = self
+            .markers
+            .iter()
+            .enumerate()
+            .filter(|&(i, marker)| Some(i) != exclude && self.groups.is_visible(marker.group) && !marker.ghost)
+            .map(|(_, marker)| marker.position)
+            .collect();
+        let nearest = |value: f32, axis: fn(egui::Pos2) -> f32| {
+            positions
+                .iter()
+                .map(|&position| axis(position))
+                .filter(|candidate| (candidate - value).abs() <= threshold)
+                .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+        };
+        (nearest(pos.x, |p| p.x), nearest(pos.y, |p| p.y))
+    }
+
+    fn snap_to_grid(&self, pos: egui::Pos2) -> egui::Pos2 {
+        if self.grid.is_snapping_enabled() && self.grid.snap_mode() == SnapMode::NearestMarker {
+            let best = self
+                .snap_candidates(pos)
+                .into_iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((marker_pos, _)) = best {
+                return marker_pos;
+            }
+        }
+
+        if self.ui_state.pixel_grid.enabled {
+            // Pixel grid always works in whole pixels
+            pos.round()
+        } else if self.grid.is_snapping_enabled() {
+            let grid_size = self.grid.get_size();
+            let (canvas_width, canvas_height) = self.canvas.get_size();
+
+            let snapped = self.grid.snap(pos);
+            let (x, y) = (snapped.x, snapped.y);
+
+            if pos.x < grid_size.x / 2.0 {
+                egui::pos2(0.0, y)
+            } else if pos.x > canvas_width - grid_size.x / 2.0 {
+                egui::pos2(canvas_width, y)
+            } else if pos.y < grid_size.y / 2.0 {
+                egui::pos2(x, 0.0)
+            } else if pos.y > canvas_height - grid_size.y / 2.0 {
+                egui::pos2(x, canvas_height)
+            } else {
+                snapped
+            }
+        } else {
+            pos
+        }
+    }
+
+    fn change_origin(&mut self, origin_mode: OriginMode, migration: OriginMigration) {
+        let before = self.markers.clone();
+        self.ui_state.origin_mode = origin_mode;
+        migrate_markers(&mut self.markers, &mut self.coordinate_system, origin_mode, migration);
+        self.record_replace(before);
+        self.check_canvas_bounds();
+    }
+
+    // Asks how existing markers should follow a pending origin switch
+    fn show_origin_migration_dialog(&mut self, ctx: &Context) {
+        let marker_count = self.markers.len();
+        let pending = match &mut self.ui_state.pending_origin_change {
+            Some(pending) => pending,
+            None => return,
+        };
+        let mut choice = None;
+        let mut cancelled = false;
+
+        egui::Window::new("Origin Changed")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("How should the {} existing markers follow the new origin?", marker_count));
+                ui.label("Outlines on the canvas show where they'd move if they keep their values.");
+                ui.horizontal(|ui| {
+                    for migration in OriginMigration::ALL {
+                        if ui.button(migration.label()).clicked() {
+                            choice = Some(migration);
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+                ui.checkbox(&mut pending.remember, "Remember my choice");
+            });
+
+        if let Some(migration) = choice {
+            let origin_mode = pending.origin_mode;
+            if pending.remember {
+                self.ui_state.origin_migration = Some(migration);
+            }
+            self.ui_state.pending_origin_change = None;
+            self.change_origin(origin_mode, migration);
+        } else if cancelled {
+            self.ui_state.pending_origin_change = None;
+        }
+    }
+
+    fn set_custom_origin(&mut self, origin: Option<egui::Pos2>) {
+        let before = self.coordinate_system.custom_origin();
+        if before != origin {
+            self.apply_custom_origin(origin);
+            self.undo_stack.push(MarkerCommand::Origin { before, after: origin });
+        }
+    }
+
+    // Moves the origin while it's being dragged. finish_origin_drag records the
+    // whole drag as one undo step.
+    fn drag_custom_origin(&mut self, origin: Option<egui::Pos2>) {
+        if self.ui_state.origin_before_drag.is_none() {
+            self.ui_state.origin_before_drag = Some(self.coordinate_system.custom_origin());
+        }
+        self.apply_custom_origin(origin);
+    }
+
+    fn finish_origin_drag(&mut self) {
+        if let Some(before) = self.ui_state.origin_before_drag.take() {
+            let after = self.coordinate_system.custom_origin();
+            if before != after {
+                self.undo_stack.push(MarkerCommand::Origin { before, after });
+            }
+        }
+    }
+
+    // Markers keep their canvas positions, so only their system values change
+    fn apply_custom_origin(&mut self, origin: Option<egui::Pos2>) {
+        self.coordinate_system.set_custom_origin(origin);
+        for marker in &mut self.markers {
+            marker.system_position = self.coordinate_system.to_system_coordinates(marker.position);
+        }
+    }
+
+    // Whether a screen position is over the origin indicator drawn with the grid
+    fn is_over_origin(&self, screen_pos: egui::Pos2, canvas_rect: egui::Rect) -> bool {
+        const ORIGIN_HIT_RADIUS: f32 = 8.0;
+
+        let origin = self
+            .canvas
+            .canvas_to_screen_pos(self.coordinate_system.origin_canvas_pos(), canvas_rect);
+        self.grid.is_visible() && (screen_pos - origin).length() < ORIGIN_HIT_RADIUS
+    }
+
+    // Drag the origin indicator to define a custom origin, double-click it to reset
+    fn handle_origin_drag(&mut self, ui: &mut Ui, response: &egui::Response, canvas_rect: egui::Rect) {
+        if response.drag_started_by(egui::PointerButton::Primary) && !ui.input(|i| i.modifiers.alt) {
+            if let Some(press_pos) = ui.input(|i| i.pointer.press_origin()) {
+                self.ui_state.dragging_origin = self.is_over_origin(press_pos, canvas_rect);
+            }
+        }
+
+        if self.ui_state.dragging_origin {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.drag_custom_origin(Some(self.apply_grid_snapping(canvas_pos)));
+            }
+            if response.drag_released() {
+                self.ui_state.dragging_origin = false;
+                self.finish_origin_drag();
+            }
+        }
+
+        if response.double_clicked() {
+            if let Some(pos) = response.hover_pos() {
+                if self.is_over_origin(pos, canvas_rect) {
+                    self.set_custom_origin(None);
+                }
+            }
+        }
+    }
+
+    // Drag an existing marker with the Place tool to move it, snapping like placement
+    fn handle_marker_drag(&mut self, ui: &Ui, response: &egui::Response, canvas_rect: egui::Rect) {
+        if response.drag_started_by(egui::PointerButton::Primary)
+            && self.ui_state.active_tool == Tool::Place
+            && !self.ui_state.dragging_origin
+            && !self.ui_state.move_selection
+            && ui.input(|i| i.modifiers.is_none())
+        {
+            if let Some(press_pos) = ui.input(|i| i.pointer.press_origin()) {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(press_pos, canvas_rect);
+                self.ui_state.dragging_marker = self
+                    .marker_at(canvas_pos)
+                    .map(|index| (index, self.markers[index].position));
+            }
+        }
+
+        if let Some((index, start)) = self.ui_state.dragging_marker {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                let snapped_pos = self.apply_all_snapping(canvas_pos, Some(index));
+                if self.is_inside_canvas(snapped_pos) {
+                    if let Some(marker) = self.markers.get_mut(index) {
+                        marker.position = snapped_pos;
+                        marker.system_position = self.coordinate_system.to_system_coordinates(snapped_pos);
+                    }
+                }
+            }
+            if response.drag_released() {
+                self.ui_state.dragging_marker = None;
+                self.record_move(index, start);
+            }
+        }
+    }
+
+    // Records a finished move of marker `index` from `from` to where it is now
+    fn record_move(&mut self, index: usize, from: egui::Pos2) {
+        if let Some(marker) = self.markers.get(index) {
+            if marker.position != from {
+                let to = marker.position;
+                self.undo_stack.push(MarkerCommand::Move { index, from, to });
+            }
+        }
+    }
+
+    // Records everything that changed since `before` was taken as one undo step
+    fn record_replace(&mut self, before: Vec<Marker>) {
+        if before != *self.markers {
+            self.undo_stack.push(MarkerCommand::Replace {
+                before,
+                after: self.markers.clone(),
+            });
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            markers: self.markers.clone(),
+            groups: self.groups.groups().to_vec(),
+            measurements: self.measurements.clone(),
+        }
+    }
+
+    // Like record_replace, for changes that also added groups or measurements
+    fn record_snapshot(&mut self, before: Snapshot) {
+        let after = self.snapshot();
+        self.undo_stack.push(MarkerCommand::Snapshot { before, after });
+    }
+
+    // Turns a whole label or note editing session into one undo step instead of one per keystroke
+    fn track_text_edit(&mut self, response: &egui::Response) {
+        if response.gained_focus() {
+            self.ui_state.text_edit_before = Some(self.markers.clone());
+        }
+        if response.lost_focus() {
+            if let Some(before) = self.ui_state.text_edit_before.take() {
+                self.record_replace(before);
+            }
+        }
+    }
+
+    // Replaces the annotations as one undo step
+    fn set_annotations(&mut self, annotations: Vec<Annotation>) {
+        let before = std::mem::replace(&mut self.annotations, annotations);
+        self.undo_stack.push(MarkerCommand::Annotations {
+            before,
+            after: self.annotations.clone(),
+        });
+    }
+
+    // Replaces the measurements as one undo step
+    fn set_measurements(&mut self, measurements: Vec<Measurement>) {
+        let before = std::mem::replace(&mut self.measurements, measurements);
+        self.undo_stack.push(MarkerCommand::Measurements {
+            before,
+            after: self.measurements.clone(),
+        });
+    }
+
+    fn undo(&mut self) {
+        if let Some(command) = self.undo_stack.undo() {
+            self.apply_marker_command(command);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(command) = self.undo_stack.redo() {
+            self.apply_marker_command(command);
+        }
+    }
+
+    // Replays a command from the undo stack without recording it again
+    fn apply_marker_command(&mut self, command: MarkerCommand) {
+        match command {
+            MarkerCommand::Add { index, marker } => {
+                let index = index.min(self.markers.len());
+                self.markers.insert(index, marker);
+                shift_indices_after_insertion(&mut self.ui_state.selected_markers, index);
+                shift_indices_after_insertion(&mut self.ui_state.expanded_notes, index);
+            }
+            MarkerCommand::Remove { index, .. } => {
+                if index < self.markers.len() {
+                    self.markers.remove(index);
+                    shift_indices_after_removal(&mut self.ui_state.selected_markers, index);
+                    shift_indices_after_removal(&mut self.ui_state.expanded_notes, index);
+                }
+            }
+            MarkerCommand::Move { index, to, .. } => {
+                if let Some(marker) = self.markers.get_mut(index) {
+                    marker.position = to;
+                    marker.system_position = self.coordinate_system.to_system_coordinates(to);
+                }
+            }
+            MarkerCommand::Replace { after, .. } => self.replace_markers(after),
+            MarkerCommand::Origin { after, .. } => self.apply_custom_origin(after),
+            MarkerCommand::Annotations { after, .. } => self.annotations = after,
+            MarkerCommand::Measurements { after, .. } => self.measurements = after,
+            MarkerCommand::Snapshot { after, .. } => {
+                let active = self.groups.active();
+                self.groups.restore(after.groups);
+                if self.groups.active() != active {
+                    self.set_active_group(self.groups.active());
+                }
+                self.replace_markers(after.markers);
+                self.measurements = after.measurements;
+            }
+        }
+        self.check_canvas_bounds();
+    }
+
+    fn replace_markers(&mut self, after: Vec<Marker>) {
+        // Indices only stay meaningful when no marker was added or removed
+        if after.len() != self.markers.len() {
+            self.ui_state.selected_markers.clear();
+            self.ui_state.expanded_notes.clear();
+        }
+        *self.markers = after;
+        // The origin or scale may have changed since the snapshot was taken
+        for marker in &mut self.markers {
+            marker.system_position = self.coordinate_system.to_system_coordinates(marker.position);
+        }
+    }
+
+    fn ruler_span(&self) -> Option<(f32, f32)> {
+        self.ui_state.ruler_measure_start.zip(self.ui_state.ruler_measure_end)
+    }
+
+    // e.g. "X span: 400 px"
+    fn ruler_span_label(&self) -> Option<String> {
+        let (start, end) = self.ruler_span()?;
+        let axis = match self.ui_state.ruler_measure_axis {
+            RulerAxis::X => "X",
+            RulerAxis::Y => "Y",
+        };
+        let distance = coordinate::format_pixel(self.distance_in_copy_units((end - start).abs()));
+        Some(format!("{} span: {} px", axis, distance))
+    }
+
+    // A first click picks a marker, a second one on another marker measures
+    // between the two. Clicking empty canvas starts over.
+    fn pick_measure_marker(&mut self, index: Option<usize>) {
+        match (self.ui_state.measure_first, index) {
+            (Some(first), Some(second)) if first != second => {
+                self.ui_state.measurement = Some((first, second));
+                self.ui_state.measure_first = None;
+            }
+            _ => {
+                self.ui_state.measure_first = index;
+                self.ui_state.measurement = None;
+            }
+        }
+    }
+
+    // Line between the measured markers, labelled at its midpoint with the
+    // distance, the angle from the +X axis and the X/Y components
+    fn draw_measurement(&self, painter: &egui::Painter, canvas_rect: egui::Rect) {
+        let highlight = Stroke::new(2.0, Color32::from_rgb(255, 200, 0));
+        if let Some(marker) = self.ui_state.measure_first.and_then(|i| self.markers.get(i)) {
+            let center = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
+            painter.circle_stroke(center, 9.0, highlight);
+        }
+        let (a, b) = match self.ui_state.measurement {
+            Some((first, second)) => match (self.markers.get(first), self.markers.get(second)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return,
+            },
+            None => return,
+        };
+
+        // System coordinates, so the angle follows the origin's Y direction
+        let delta = b.system_position - a.system_position;
+        let angle = delta.y.atan2(delta.x).to_degrees();
+        let text = format!(
+            "{:.1} px  \u{2220} {:.1}\u{b0}\n\u{394}x {}  \u{394}y {}",
+            delta.length(),
+            angle,
+            coordinate::format_pixel(delta.x),
+            coordinate::format_pixel(delta.y)
+        );
+
+        let start = self.canvas.canvas_to_screen_pos(a.position, canvas_rect);
+        let end = self.canvas.canvas_to_screen_pos(b.position, canvas_rect);
+        painter.line_segment([start, end], highlight);
+        for point in [start, end] {
+            painter.circle_stroke(point, 9.0, highlight);
+        }
+        let galley = painter.layout_no_wrap(text, egui::FontId::proportional(12.0), Color32::BLACK);
+        let label_rect = egui::Align2::CENTER_CENTER
+            .anchor_rect(egui::Rect::from_min_size(start + (end - start) / 2.0, galley.size()))
+            .expand(3.0);
+        painter.rect_filled(label_rect, 3.0, highlight.color);
+        painter.galley(label_rect.shrink(3.0).min, galley);
+    }
+
+    // (interior, signed) angle at the vertex of the Angle tool's three points,
+    // signed in system coordinates so it follows the origin's Y direction
+    fn angle_measurement(&self) -> Option<(f32, f32)> {
+        let [a, vertex, b] = match self.ui_state.angle_points[..] {
+            [a, vertex, b] => [a, vertex, b].map(|pos| self.coordinate_system.to_system_coordinates(pos)),
+            _ => return None,
+        };
+        let signed = analysis::signed_angle(vertex, a, b)?;
+        Some((signed.abs(), signed))
+    }
+
+    fn angle_text(&self) -> Option<String> {
+        let (interior, signed) = self.angle_measurement()?;
+        Some(format!("Interior {:.1}\u{b0}, signed {:+.1}\u{b0}", interior, signed))
+    }
+
+    // Angle tool readout under the current position
+    fn angle_readout(&mut self, ui: &mut Ui) {
+        ui.label("Angle: click an arm point, the vertex, then the other arm point");
+        let mut selected: Vec<usize> = self.ui_state.selected_markers.iter().copied().collect();
+        selected.sort_unstable();
+        if selected.len() == 3
+            && ui
+                .button("Use Selected Markers")
+                .on_hover_text("The middle marker in list order is the vertex")
+                .clicked()
+        {
+            self.ui_state.angle_points = selected.iter().map(|&i| self.markers[i].position).collect();
+        }
+        if let Some(text) = self.angle_text() {
+            ui.horizontal(|ui| {
+                ui.label(&text);
+                if ui.button("Copy").clicked() {
+                    self.copy_to_clipboard(text.clone());
+                }
+            });
+        }
+    }
+
+    // Arms of the Angle tool with an arc at the vertex labelled with the interior angle
+    fn draw_angle(&self, painter: &egui::Painter, canvas_rect: egui::Rect) {
+        const ARC_RADIUS: f32 = 24.0;
+        let stroke = Stroke::new(2.0, Color32::from_rgb(255, 140, 0));
+        let points: Vec<egui::Pos2> = self
+            .ui_state
+            .angle_points
+            .iter()
+            .map(|&pos| self.canvas.canvas_to_screen_pos(pos, canvas_rect))
+            .collect();
+        for &point in &points {
+            painter.circle_stroke(point, 5.0, stroke);
+        }
+        if let [a, vertex, ..] = points[..] {
+            painter.line_segment([a, vertex], stroke);
+        }
+        let (a, vertex, b) = match points[..] {
+            [a, vertex, b] => (a, vertex, b),
+            _ => return,
+        };
+        painter.line_segment([vertex, b], stroke);
+
+        // The arc is drawn in screen space, which may be flipped relative to the system
+        let (interior, _) = match self.angle_measurement() {
+            Some(angles) => angles,
+            None => return,
+        };
+        let (arm_a, arm_b) = ((a - vertex).normalized(), (b - vertex).normalized());
+        let start = arm_a.y.atan2(arm_a.x);
+        let sweep = (arm_a.x * arm_b.y - arm_a.y * arm_b.x).atan2(arm_a.dot(arm_b));
+        let arc: Vec<egui::Pos2> = (0..=24)
+            .map(|i| {
+                let angle = start + sweep * i as f32 / 24.0;
+                vertex + egui::vec2(angle.cos(), angle.sin()) * ARC_RADIUS
+            })
+            .collect();
+        painter.add(egui::Shape::line(arc, stroke));
+
+        let middle = start + sweep / 2.0;
+        painter.text(
+            vertex + egui::vec2(middle.cos(), middle.sin()) * (ARC_RADIUS + 14.0),
+            egui::Align2::CENTER_CENTER,
+            format!("{:.1}\u{b0}", interior),
+            egui::FontId::proportional(12.0),
+            stroke.color,
+        );
+    }
+
+    // Kept measurements with values in the active coordinate system and units
+    fn measurement_records(&self) -> Vec<MeasurementRecord> {
+        let to_units = |pos: egui::Pos2| {
+            let system_pos = self.coordinate_system.to_system_coordinates(pos);
+            self.coordinate_system.apply_scale(self.output_position(system_pos))
+        };
+        self.measurements.iter().map(|measurement| measurement.record(&to_units)).collect()
+    }
+
+    fn measurements_section(&mut self, ui: &mut Ui, ctx: &Context) {
+        let distance = self.ui_state.measurement.and_then(|(first, second)| {
+            Some(Measurement::Distance {
+                from: self.markers.get(first)?.position,
+                to: self.markers.get(second)?.position,
+            })
+        });
+        let angle = match self.ui_state.angle_points[..] {
+            [arm_a, vertex, arm_b] => Some(Measurement::Angle { arm_a, vertex, arm_b }),
+            _ => None,
+        };
+        let mut selected: Vec<usize> = self.ui_state.selected_markers.iter().copied().collect();
+        selected.sort_unstable();
+        let positions: Vec<egui::Pos2> = selected
+            .iter()
+            .filter_map(|&i| self.markers.get(i))
+            .map(|marker| marker.position)
+            .collect();
+        let rect = (positions.len() >= 2).then(|| Measurement::Rect {
+            rect: egui::Rect::from_points(&positions),
+        });
+        let circle = match positions[..] {
+            [center, edge] => Some(Measurement::Circle {
+                center,
+                radius: center.distance(edge),
+            }),
+            _ => None,
+        };
+
+        // Kept measurements are copies and don't follow markers that move later
+        let mut kept = None;
+        ui.horizontal_wrapped(|ui| {
+            for (measurement, label, hover, disabled) in [
+                (distance, "Keep Distance", "The Measure tool's distance", "Measure two markers first"),
+                (angle, "Keep Angle", "The Angle tool's angle", "Place three points with the Angle tool first"),
+                (rect, "Rect from Selection", "Bounding box of the selected markers", "Select two or more markers"),
+                (
+                    circle,
+                    "Circle from Selection",
+                    "Centered on the first selected marker in list order, through the second",
+                    "Select two markers",
+                ),
+            ] {
+                if ui
+                    .add_enabled(measurement.is_some(), egui::Button::new(label))
+                    .on_hover_text(hover)
+                    .on_disabled_hover_text(disabled)
+                    .clicked()
+                {
+                    kept = measurement;
+                }
+            }
+        });
+        if let Some(measurement) = kept {
+            let mut measurements = self.measurements.clone();
+            measurements.push(measurement);
+            self.set_measurements(measurements);
+        }
+
+        let records = self.measurement_records();
+        let units = self.coordinate_system.unit_label().to_string();
+        let mut removed = None;
+        for (i, record) in records.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let mut text = format!("{}. {} {}", i + 1, record.kind().label(), record.value_text());
+                if record.kind() != MeasurementKind::Angle && !units.is_empty() {
+                    text = format!("{} {}", text, units);
+                }
+                ui.label(text);
+                if ui.small_button("Delete").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            let mut measurements = self.measurements.clone();
+            measurements.remove(i);
+            self.set_measurements(measurements);
+        }
+
+        ui.checkbox(&mut self.ui_state.show_measurements, "Show on canvas");
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!records.is_empty(), |ui| {
+                if ui.button("Copy CSV").clicked() {
+                    self.copy_to_clipboard(self.measurements_csv());
+                }
+                if ui.button("Save CSV…").clicked() {
+                    self.save_measurements_csv(ctx);
+                }
+                if ui.button("Clear").clicked() {
+                    self.set_measurements(Vec::new());
+                }
+            });
+            if ui.button("Import CSV…").clicked() {
+                self.import_measurements_csv();
+            }
+        });
+    }
+
+    // Uses the delimiter and header row of the CSV export options
+    fn measurements_csv(&self) -> String {
+        let options = &self.ui_state.csv_options;
+        measurement::to_csv(&self.measurement_records(), options.delimiter.as_str(), options.include_header)
+    }
+
+    fn save_measurements_csv(&mut self, ctx: &Context) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("measurements.csv");
+        if let Some(path) = dialog.save_file() {
+            let message = match export::write_atomic(&path, self.measurements_csv().as_bytes()) {
+                Ok(()) => format!("Exported {} measurements", self.measurements.len()),
+                Err(err) => format!("CSV export failed: {}", err),
+            };
+            self.show_toast(ctx, message);
+        }
+    }
+
+    // Adds the measurements of a CSV from Save CSV… as one undo step. Nothing is
+    // imported if any row is invalid.
+    fn import_measurements_csv(&mut self) {
+        let path = match rfd::FileDialog::new().add_filter("CSV", &["csv"]).pick_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| vec![err.to_string()])
+            .and_then(|csv| measurement::from_csv(&csv));
+        match result {
+            Ok(imported) => {
+                self.ui_state.export_status = Some(format!("Imported {} measurements", imported.len()));
+                let mut measurements = self.measurements.clone();
+                measurements.extend(imported);
+                self.set_measurements(measurements);
+            }
+            Err(errors) => self.ui_state.import_errors = errors,
+        }
+    }
+
+    // Kept measurements, each labelled with its value
+    fn draw_kept_measurements(&self, painter: &egui::Painter, canvas_rect: egui::Rect) {
+        let stroke = Stroke::new(2.0, MEASUREMENT_COLOR);
+        let to_screen = |pos: egui::Pos2| self.canvas.canvas_to_screen_pos(pos, canvas_rect);
+        for (measurement, record) in self.measurements.iter().zip(self.measurement_records()) {
+            let label_pos = match *measurement {
+                Measurement::Distance { from, to } => {
+                    let (from, to) = (to_screen(from), to_screen(to));
+                    painter.line_segment([from, to], stroke);
+                    from + (to - from) / 2.0
+                }
+                Measurement::Angle { arm_a, vertex, arm_b } => {
+                    let vertex = to_screen(vertex);
+                    painter.line_segment([to_screen(arm_a), vertex], stroke);
+                    painter.line_segment([vertex, to_screen(arm_b)], stroke);
+                    vertex
+                }
+                Measurement::Rect { rect } => {
+                    let rect = egui::Rect::from_two_pos(to_screen(rect.min), to_screen(rect.max));
+                    painter.rect_stroke(rect, 0.0, stroke);
+                    rect.center_top()
+                }
+                Measurement::Circle { center, radius } => {
+                    let screen_center = to_screen(center);
+                    let screen_radius = screen_center.distance(to_screen(center + egui::vec2(radius, 0.0)));
+                    painter.circle_stroke(screen_center, screen_radius, stroke);
+                    screen_center
+                }
+            };
+            painter.text(
+                label_pos,
+                egui::Align2::CENTER_BOTTOM,
+                record.value_text(),
+                egui::FontId::proportional(12.0),
+                stroke.color,
+            );
+        }
+    }
+
+    // Dragging along a ruler measures a span; clicks on a ruler clear it.
+    // Returns true when the ruler took the pointer, so the canvas ignores it.
+    fn handle_ruler_measure(&mut self, ui: &Ui, response: &egui::Response, canvas_rect: egui::Rect) -> bool {
+        if !self.ui_state.show_rulers {
+            return false;
+        }
+        let rulers = Ruler::for_view(canvas_rect, self.ui_state.ruler_thickness);
+        let ruler_at = |pos: egui::Pos2| rulers.iter().find(|ruler| ruler.rect.contains(pos));
+
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            let press_origin = ui.input(|i| i.pointer.press_origin());
+            if let Some((ruler, pos)) = press_origin.and_then(|pos| ruler_at(pos).map(|ruler| (ruler, pos))) {
+                let value = ruler.value_at_pos(pos, &self.canvas, canvas_rect, &self.coordinate_system).round();
+                self.ui_state.ruler_measure_axis = ruler.axis;
+                self.ui_state.ruler_measure_start = Some(value);
+                self.ui_state.ruler_measure_end = Some(value);
+                self.ui_state.ruler_measuring = true;
+            }
+        }
+
+        if self.ui_state.ruler_measuring {
+            let ruler = rulers.iter().find(|ruler| ruler.axis == self.ui_state.ruler_measure_axis);
+            if let Some((ruler, pos)) = ruler.zip(response.interact_pointer_pos()) {
+                let value = ruler.value_at_pos(pos, &self.canvas, canvas_rect, &self.coordinate_system).round();
+                self.ui_state.ruler_measure_end = Some(value);
+            }
+            if response.drag_released() {
+                self.ui_state.ruler_measuring = false;
+            }
+            return true;
+        }
+
+        if response.clicked() && response.hover_pos().and_then(ruler_at).is_some() {
+            self.ui_state.ruler_measure_start = None;
+            self.ui_state.ruler_measure_end = None;
+            return true;
+        }
+        false
+    }
+
+    // Handle mouse interactions with the canvas
+    fn handle_canvas_interactions(&mut self, ui: &mut Ui, response: egui::Response) {
+        let canvas_rect = response.rect;
+
+        if self.handle_ruler_measure(ui, &response, canvas_rect) {
+            return;
+        }
+        if self.handle_minimap(ui, &response, canvas_rect) {
+            return;
+        }
+
+        self.handle_origin_drag(ui, &response, canvas_rect);
+        self.handle_marker_drag(ui, &response, canvas_rect);
+
+        // Clicking an off-screen arrow brings its marker into view instead of placing one
+        if self.ui_state.show_offscreen_indicators && response.clicked() {
+            let target = response
+                .hover_pos()
+                .and_then(|pos| self.offscreen_indicator_at(pos, canvas_rect));
+            if let Some(index) = target {
+                self.pan_to(self.markers[index].position);
+                return;
+            }
+        }
+
+        let bindings = self.ui_state.mouse_bindings;
+        let modifiers = ui.input(|i| i.modifiers);
+
+        if bindings.pan.dragged(&response, modifiers)
+            || (bindings.alt_drag_pans() && response.dragged_by(egui::PointerButton::Primary) && modifiers.alt)
+        {
+            self.canvas_target_offset = None;
+            self.canvas.pan(response.drag_delta());
+        }
+
+        self.handle_marquee_zoom(ui, &response, canvas_rect);
+        if self.ui_state.marquee.is_none() {
+            self.handle_selection_rect(ui, &response, canvas_rect);
+        }
+        self.handle_selection_move(&response, canvas_rect);
+
+        if self.ui_state.active_tool == Tool::Lasso
+            && self.ui_state.marquee.is_none()
+            && self.ui_state.selection_rect.is_none()
+            && !self.ui_state.move_selection
+            && !modifiers.shift
+            && !self.ui_state.dragging_origin
+            && !ui.input(|i| i.modifiers.alt)
+        {
+            self.handle_lasso(&response, canvas_rect);
+        }
+
+        if response.hovered() {
+            let scroll_delta = ui.input(|i| i.scroll_delta.y);
+            if scroll_delta != 0.0 {
+                self.canvas_target_offset = None;
+                let zoom_factor = if scroll_delta > 0.0 { 1.1 } else { 1.0 / 1.1 };
+                let mouse_pos = ui.input(|i| i.pointer.hover_pos());
+                if let Some(pos) = mouse_pos {
+                    if self.ui_state.smooth_zoom {
+                        self.canvas.zoom_towards(zoom_factor, pos, canvas_rect);
+                    } else {
+                        self.canvas.zoom_at(zoom_factor, pos, canvas_rect);
+                    }
+                }
+            }
+        }
+
+        if let Some(mouse_pos) = response.hover_pos() {
+            let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
+            let dragged = self.ui_state.dragging_marker.map(|(index, _)| index);
+            let snapped_pos = self.apply_all_snapping(canvas_pos, dragged);
+
+            self.ui_state.current_position = self.coordinate_system.to_system_coordinates(snapped_pos);
+            self.ui_state.current_position_raw = self.coordinate_system.to_system_coordinates(canvas_pos);
+
+            #[cfg(feature = "websocket")]
+            {
+                let output = self.output_position(self.ui_state.current_position);
+                let now = ui.input(|i| i.time);
+                if let Some(stream) = &mut self.position_stream {
+                    stream.send_hover(output, now);
+                }
+            }
+        }
+
+        // Clicks on empty canvas drop the selection; Shift+clicks keep it
+        if response.clicked() && !modifiers.shift {
+            let on_marker = match response.hover_pos() {
+                Some(pos) => self.marker_at(self.canvas.screen_to_canvas_pos(pos, canvas_rect)).is_some(),
+                None => false,
+            };
+            if self.ui_state.active_tool == Tool::Lasso || !on_marker {
+                self.ui_state.selected_markers.clear();
+            }
+        }
+
+        if response.double_clicked() && self.ui_state.editing_marker.is_none() {
+            if let Some(pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                if let Some(index) = self.marker_at(canvas_pos) {
+                    self.ui_state.editing_marker = Some(MarkerEdit {
+                        index,
+                        system_position: self.markers[index].system_position,
+                        just_opened: true,
+                    });
+                }
+            }
+        }
+
+        // Clicks while the marker editor is open only dismiss it
+        if bindings.place.clicked(&response, modifiers)
+            && self.ui_state.active_tool == Tool::Place
+            && self.ui_state.editing_marker.is_none()
+            && self.markers.len() < self.ui_state.max_markers
+        {
+            if let Some(pos) = response.hover_pos() {
+                let border_rect = self.canvas.get_screen_rect(canvas_rect);
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                // Clicks on an existing marker don't stack another one on top (double-click edits it)
+                if border_rect.contains(pos)
+                    && !self.is_over_origin(pos, canvas_rect)
+                    && self.marker_at(canvas_pos).is_none()
+                {
+                    let snapped_pos = self.apply_all_snapping(canvas_pos, None);
+
+                    if self.is_inside_canvas(snapped_pos) {
+                        self.add_marker(snapped_pos);
+                    }
+                }
+            }
+        }
+
+        if self.ui_state.active_tool != Tool::Measure {
+            self.ui_state.measure_first = None;
+            self.ui_state.measurement = None;
+        } else if bindings.place.clicked(&response, modifiers) {
+            if let Some(pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.pick_measure_marker(self.marker_at(canvas_pos));
+            }
+        }
+
+        if self.ui_state.active_tool != Tool::Angle {
+            self.ui_state.angle_points.clear();
+        } else if bindings.place.clicked(&response, modifiers) {
+            if let Some(pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                // Clicks on a marker use its exact position
+                let point = match self.marker_at(canvas_pos) {
+                    Some(index) => self.markers[index].position,
+                    None => self.apply_all_snapping(canvas_pos, None),
+                };
+                if self.ui_state.angle_points.len() == 3 {
+                    self.ui_state.angle_points.clear();
+                }
+                self.ui_state.angle_points.push(point);
+            }
+        }
+
+        // One-shot: back to placing markers once the origin is set
+        if bindings.place.clicked(&response, modifiers) && self.ui_state.active_tool == Tool::SetOrigin {
+            if let Some(pos) = response.hover_pos() {
+                // Snapped in canvas space, so the origin can land on a grid line
+                let canvas_pos = self.apply_grid_snapping(self.canvas.screen_to_canvas_pos(pos, canvas_rect));
+                if self.is_inside_canvas(canvas_pos) {
+                    self.set_custom_origin(Some(canvas_pos));
+                    self.ui_state.active_tool = Tool::Place;
+                }
+            }
+        }
+
+        if bindings.place.clicked(&response, modifiers) && self.ui_state.active_tool == Tool::Bezier {
+            if let Some(pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.ui_state.bezier_points.push(self.apply_grid_snapping(canvas_pos));
+                if let [p0, p1, p2, p3] = self.ui_state.bezier_points[..] {
+                    let mut annotations = self.annotations.clone();
+                    annotations.push(Annotation::Bezier { p0, p1, p2, p3 });
+                    self.set_annotations(annotations);
+                    self.ui_state.bezier_points.clear();
+                }
+            }
+        }
+
+        if bindings.place.clicked(&response, modifiers) && self.ui_state.active_tool == Tool::Stamp {
+            if let Some(pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.place_stamp(&response.ctx, self.apply_grid_snapping(canvas_pos));
+            }
+        }
+
+        if bindings.delete.clicked(&response, modifiers) {
+            if let Some(pos) = response.hover_pos() {
+                let border_rect = self.canvas.get_screen_rect(canvas_rect);
+                if border_rect.contains(pos) {
+                    let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                    self.remove_nearby_marker(canvas_pos, self.canvas.get_zoom());
+                }
+            }
+        }
+    }
+
+    fn is_inside_canvas(&self, canvas_pos: egui::Pos2) -> bool {
+        let (canvas_width, canvas_height) = self.canvas.get_size();
+        canvas_pos.x >= 0.0
+            && canvas_pos.x <= canvas_width
+            && canvas_pos.y >= 0.0
+            && canvas_pos.y <= canvas_height
+    }
+
+    fn add_marker(&mut self, canvas_pos: egui::Pos2) {
+        // Checked here too so nothing is streamed for a refused marker
+        if self.markers.len() >= self.ui_state.max_markers {
+            return;
+        }
+        let system_pos = self.coordinate_system.to_system_coordinates(canvas_pos);
+        let marker = Marker::new(
+            canvas_pos,
+            system_pos,
+            self.ui_state.marker_color,
+            self.groups.active(),
+        );
+        self.push_marker(Marker {
+            kind: self.ui_state.place_kind,
+            ..marker
+        });
+
+        #[cfg(feature = "websocket")]
+        if let Some(stream) = &self.position_stream {
+            let label = format!("Marker {}", self.markers.len());
+            stream.send_marker(self.output_position(system_pos), &label);
+        }
+    }
+
+    // Appends a marker as an undoable step, unless the marker limit is reached
+    fn push_marker(&mut self, marker: Marker) {
+        if self.markers.len() >= self.ui_state.max_markers {
+            return;
+        }
+        self.undo_stack.push(MarkerCommand::Add {
+            index: self.markers.len(),
+            marker: marker.clone(),
+        });
+        self.markers.push(marker);
+    }
+
+    // Why `count` more markers can't be added, if they'd go past the marker limit.
+    // Bulk adds are rejected as a whole rather than cut short.
+    fn marker_limit_error(&self, count: usize) -> Option<String> {
+        let room = self.ui_state.max_markers.saturating_sub(self.markers.len());
+        if count <= room {
+            return None;
+        }
+        Some(format!(
+            "{} markers would go past the marker limit ({}); {} more fit. Delete some or increase the limit.",
+            count, self.ui_state.max_markers, room
+        ))
+    }
+
+    // New markers take the active group's color
+    fn set_active_group(&mut self, id: u32) {
+        self.groups.set_active(id);
+        if let Some(group) = self.groups.get(id) {
+            self.ui_state.marker_color = group.color;
+        }
+    }
+
+    fn active_stamp(&self) -> Option<&Stamp> {
+        self.ui_state.active_stamp.and_then(|index| self.ui_state.stamps.get(index))
+    }
+
+    // Place the active stamp with its top-left at `anchor`, as a new group
+    fn place_stamp(&mut self, ctx: &Context, anchor: egui::Pos2) {
+        let (name, positions) = match self.active_stamp() {
+            Some(stamp) => (stamp.name.clone(), stamp.positions_at(anchor, self.ui_state.stamp_scale)),
+            None => return,
+        };
+        if let Some(error) = self.marker_limit_error(positions.len()) {
+            self.show_toast(ctx, error);
+            return;
+        }
+        let before = self.snapshot();
+        let group = self.groups.add(name);
+        let color = self.groups.get(group).map_or(self.ui_state.marker_color, |group| group.color);
+        // One undo step for the whole stamp and its group rather than one per marker
+        for position in positions {
+            let system_pos = self.coordinate_system.to_system_coordinates(position);
+            self.markers.push(Marker::new(position, system_pos, color, group));
+        }
+        self.record_snapshot(before);
+        self.check_canvas_bounds();
+    }
+
+    fn save_stamp_from_selection(&mut self) {
+        let mut selected: Vec<usize> = self.ui_state.selected_markers.iter().copied().collect();
+        selected.sort_unstable();
+        let positions: Vec<egui::Pos2> = selected
+            .iter()
+            .filter_map(|&index| self.markers.get(index))
+            .map(|marker| marker.position)
+            .collect();
+        let name = match self.ui_state.new_stamp_name.trim() {
+            "" => format!("Stamp {}", self.ui_state.stamps.len() + 1),
+            name => name.to_string(),
+        };
+        if let Some(stamp) = Stamp::from_positions(name, &positions) {
+            self.ui_state.stamps.push(stamp);
+            self.ui_state.active_stamp = Some(self.ui_state.stamps.len() - 1);
+            self.ui_state.new_stamp_name.clear();
+        }
+    }
+
+    fn open_background_image(&mut self, ctx: &Context) {
+        let path = match rfd::FileDialog::new()
+            .add_filter("Images", &BACKGROUND_IMAGE_EXTENSIONS)
+            .pick_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        self.load_background_image(ctx, &path);
+    }
+
+    fn load_background_image(&mut self, ctx: &Context, path: &Path) {
+        match image::open(path) {
+            Ok(image) => {
+                let pixels = image.to_rgba8();
+                let key = self.background_key();
+                let color_image = export::composite_image(&pixels, key.base, key.mode, key.gamma);
+                // Nearest filtering keeps individual pixels crisp when zoomed in
+                let texture = ctx.load_texture("background_image", color_image, egui::TextureOptions::NEAREST);
+                let average_luminance = export::average_luminance(&pixels);
+                self.background_image = Some(BackgroundImage {
+                    pixels,
+                    texture,
+                    key,
+                    average_luminance,
+                });
+                self.ui_state.background_error = None;
+                recent::push_recent_file(&mut self.ui_state.recent_images, path);
+            }
+            Err(err) => self.ui_state.background_error = Some(format!("Could not open image: {}", err)),
+        }
+    }
+
+    fn background_key(&self) -> BackgroundKey {
+        BackgroundKey {
+            mode: self.ui_state.composite_mode,
+            base: self.background_color(),
+            gamma: self.ui_state.gamma,
+        }
+    }
+
+    // Blending happens in software, so the texture is rebuilt whenever the
+    // blend mode, gamma or the canvas color behind the image changes
+    fn refresh_background_texture(&mut self) {
+        let key = self.background_key();
+        if let Some(background) = &mut self.background_image {
+            if background.key != key {
+                let color_image = export::composite_image(&background.pixels, key.base, key.mode, key.gamma);
+                background.texture.set(color_image, egui::TextureOptions::NEAREST);
+                background.key = key;
+            }
+        }
+    }
+
+    // Files dropped on the window; the first image replaces the background
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let dropped = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .find(|path| is_background_image_path(path))
+        });
+        if let Some(path) = dropped {
+            self.load_background_image(ctx, &path);
+        }
+    }
+
+    fn background_image_section(&mut self, ui: &mut Ui, ctx: &Context) {
+        ui.horizontal(|ui| {
+            if ui.button("Open Image…").clicked() {
+                self.open_background_image(ctx);
+            }
+            if self.background_image.is_some() && ui.button("Remove").clicked() {
+                self.background_image = None;
+            }
+        });
+        match &self.background_image {
+            Some(background) => {
+                let [width, height] = background.texture.size();
+                ui.label(format!("{} x {} px", width, height));
+            }
+            None => {
+                ui.label("No image. You can also drop one onto the window.");
+            }
+        }
+        ui.add(egui::Slider::new(&mut self.ui_state.background_opacity, 0.0..=100.0).text("Opacity").suffix("%"));
+        egui::ComboBox::from_label("Blend Mode")
+            .selected_text(self.ui_state.composite_mode.label())
+            .show_ui(ui, |ui| {
+                for mode in CompositeMode::ALL {
+                    ui.selectable_value(&mut self.ui_state.composite_mode, mode, mode.label());
+                }
+            })
+            .response
+            .on_hover_text("How the image is blended with the canvas color behind it");
+        egui::ComboBox::from_label("Grid Layer")
+            .selected_text(self.ui_state.grid_layer.label())
+            .show_ui(ui, |ui| {
+                for layer in GridLayer::ALL {
+                    ui.selectable_value(&mut self.ui_state.grid_layer, layer, layer.label());
+                }
+            });
+        ui.checkbox(&mut self.ui_state.grid_auto_contrast, "Auto-contrast grid")
+            .on_hover_text("Light grid lines on dark images and dark lines on light ones");
+        if let Some(error) = &self.ui_state.background_error {
+            ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+        }
+    }
+
+    // Average and dominant colors of the background image under the last
+    // selection. Large regions are analyzed on a worker thread.
+    fn analyze_region(&mut self) {
+        let pixels = match (&self.background_image, self.ui_state.analysis_region) {
+            (Some(background), Some(region)) => region::crop(&background.pixels, region).unwrap_or_default(),
+            _ => return,
+        };
+        self.ui_state.region_colors = None;
+        self.region_analysis = None;
+        if pixels.width() as usize * pixels.height() as usize > region::BACKGROUND_PIXELS {
+            self.region_analysis = Some(RegionAnalysis::start(pixels));
+        } else {
+            self.ui_state.region_colors = Some(region::analyze(&pixels, &AtomicUsize::new(0)));
+        }
+    }
+
+    fn poll_region_analysis(&mut self, ctx: &Context) {
+        if let Some(analysis) = &self.region_analysis {
+            match analysis.poll() {
+                Some(colors) => {
+                    self.ui_state.region_colors = Some(colors);
+                    self.region_analysis = None;
+                }
+                None => ctx.request_repaint(), // Keep the progress bar moving
+            }
+        }
+    }
+
+    fn region_colors_section(&mut self, ui: &mut Ui) {
+        ui.label("Shift+drag over the background image to pick a region.");
+        let can_analyze = self.background_image.is_some() && self.ui_state.analysis_region.is_some();
+        if ui
+            .add_enabled(can_analyze && self.region_analysis.is_none(), egui::Button::new("Analyze Region"))
+            .clicked()
+        {
+            self.analyze_region();
+        }
+        if let Some(analysis) = &self.region_analysis {
+            ui.add(egui::ProgressBar::new(analysis.progress()).show_percentage());
+            return;
+        }
+
+        let colors = match &self.ui_state.region_colors {
+            Some(colors) => colors,
+            None => return,
+        };
+        if colors.pixel_count == 0 {
+            ui.label("The selection doesn't cover any image pixels");
+            return;
+        }
+        ui.label(format!("{} pixels", colors.pixel_count));
+        let mut copied = None;
+        egui::Grid::new("region_colors").num_columns(4).show(ui, |ui| {
+            let rows = std::iter::once(("Average".to_string(), colors.average)).chain(
+                colors
+                    .dominant
+                    .iter()
+                    .map(|&(color, share)| (format!("{:.1}%", share * 100.0), color)),
+            );
+            for (name, color) in rows {
+                egui::color_picker::show_color(ui, color, egui::vec2(24.0, 16.0));
+                ui.label(name);
+                let hex = export::color_hex(color);
+                ui.monospace(&hex);
+                if ui.small_button("Copy").clicked() {
+                    copied = Some(hex);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(hex) = copied {
+            self.copy_to_clipboard(hex);
+        }
+    }
+
+    // Recent Sessions and Recent Images submenus of the File menu
+    fn recent_files_menu(&mut self, ui: &mut Ui, ctx: &Context) {
+        let mut open_session = None;
+        let mut open_image = None;
+        ui.menu_button("Recent Sessions", |ui| {
+            open_session = recent_file_entries(ui, &self.ui_state.recent_sessions);
+        });
+        ui.menu_button("Recent Images", |ui| {
+            open_image = recent_file_entries(ui, &self.ui_state.recent_images);
+        });
+        let has_recent = !self.ui_state.recent_sessions.is_empty() || !self.ui_state.recent_images.is_empty();
+        if ui.add_enabled(has_recent, egui::Button::new("Clear Recent Files")).clicked() {
+            // Saved with the other settings at the end of the frame
+            self.ui_state.recent_sessions.clear();
+            self.ui_state.recent_images.clear();
+            ui.close_menu();
+        }
+        if let Some(path) = open_session {
+            ui.close_menu();
+            self.import_project_file(&path);
+        }
+        if let Some(path) = open_image {
+            ui.close_menu();
+            self.load_background_image(ctx, &path);
+        }
+    }
+
+    fn import_stamps(&mut self) {
+        let path = match rfd::FileDialog::new().add_filter("Stamps", &["json"]).pick_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| stamp::stamps_from_json(&json));
+        match result {
+            Ok(stamps) => self.ui_state.stamps.extend(stamps),
+            Err(err) => self.ui_state.export_status = Some(format!("Stamp import failed: {}", err)),
+        }
+    }
+
+    fn export_stamps(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("Stamps", &["json"])
+            .set_file_name("stamps.json");
+        if let Some(path) = dialog.save_file() {
+            if let Err(err) = std::fs::write(&path, stamp::stamps_to_json(&self.ui_state.stamps)) {
+                self.ui_state.export_status = Some(format!("Stamp export failed: {}", err));
+            }
+        }
+    }
+
+    // Copy markers shifted by the duplicate offset; the copies become the selection
+    fn duplicate_markers(&mut self, ctx: &Context, indices: &[usize]) {
+        if let Some(error) = self.marker_limit_error(indices.len()) {
+            self.show_toast(ctx, error);
+            return;
+        }
+        let offset = egui::vec2(self.ui_state.duplicate_offset_x, self.ui_state.duplicate_offset_y);
+        let first_new = self.markers.len();
+        let before = self.markers.clone();
+        for &index in indices {
+            if let Some(marker) = self.markers.get(index) {
+                let mut copy = marker.clone();
+                copy.position += offset;
+                copy.system_position = self.coordinate_system.to_system_coordinates(copy.position);
+                copy.created_at = SystemTime::now();
+                self.markers.push(copy);
+            }
+        }
+        self.record_replace(before);
+        self.ui_state.selected_markers = (first_new..self.markers.len()).collect();
+        self.check_canvas_bounds();
+    }
+
+    // Place a marker from a typed "col,row" or "@x,y" command
+    fn run_grid_command(&mut self, input: &str) -> Result<(), String> {
+        let command = GridCommand::parse(input)?;
+        let canvas_pos = self.canvas_pos_inside(command.system_position(self.grid.get_size()))?;
+        if let Some(error) = self.marker_limit_error(1) {
+            return Err(error);
+        }
+        self.add_marker(canvas_pos);
+        Ok(())
+    }
+
+    // Canvas position of a typed system position, rejected rather than clamped when it's off the canvas
+    fn canvas_pos_inside(&self, system_pos: egui::Pos2) -> Result<egui::Pos2, String> {
+        let canvas_pos = self.coordinate_system.from_system_coordinates(system_pos);
+        if self.is_inside_canvas(canvas_pos) {
+            Ok(canvas_pos)
+        } else {
+            let (canvas_width, canvas_height) = self.canvas.get_size();
+            Err(format!(
+                "({}, {}) is outside the {}x{} canvas",
+                system_pos.x, system_pos.y, canvas_width, canvas_height
+            ))
+        }
+    }
+
+    // X/Y fields with buttons to place a marker there or center the view on it
+    fn place_at_section(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("X:");
+            ui.add(egui::DragValue::new(&mut self.ui_state.place_at.x).speed(1.0));
+            ui.label("Y:");
+            ui.add(egui::DragValue::new(&mut self.ui_state.place_at.y).speed(1.0));
+        });
+        ui.horizontal(|ui| {
+            let below_limit = self.markers.len() < self.ui_state.max_markers;
+            let place = ui.add_enabled(below_limit, egui::Button::new("Place")).clicked();
+            let go_to = ui.button("Go to").on_hover_text("Center the view here without placing a marker").clicked();
+            if place || go_to {
+                match self.canvas_pos_inside(self.ui_state.place_at) {
+                    Ok(canvas_pos) => {
+                        self.ui_state.place_at_error = None;
+                        if place {
+                            self.add_marker(canvas_pos);
+                        } else {
+                            self.pan_to(canvas_pos);
+                        }
+                    }
+                    Err(err) => self.ui_state.place_at_error = Some(err),
+                }
+            }
+        });
+        if let Some(error) = &self.ui_state.place_at_error {
+            ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+        }
+    }
+
+    // Canvas size, origin and every non-ghost marker, for non-technical readers
+    fn share_summary_text(&self) -> String {
+        let (width, height) = self.ui_state.output_transform.output_size(self.canvas.get_size());
+        let origin = match self.coordinate_system.custom_origin() {
+            Some(origin) => format!("origin at ({})", coordinate::format_pixels(origin)),
+            None => match self.coordinate_system.origin_mode() {
+                OriginMode::TopLeft => "origin top-left".to_string(),
+                OriginMode::BottomLeft => "origin bottom-left".to_string(),
+                OriginMode::Center => "origin centered".to_string(),
+            },
+        };
+        let mut canvas_line = format!("Canvas {}x{}, {}", width, height, origin);
+        if self.ui_state.dual_readout && self.ui_state.copy_units == CopyUnits::Css {
+            canvas_line.push_str(", CSS px");
+        }
+
+        let session = &self.ui_state.session;
+        let heading = if session.title.is_empty() {
+            None
+        } else {
+            Some(format!("{} \u{2014} {}", session.title, export::format_date(SystemTime::now())))
+        };
+        let entries: Vec<format::SummaryEntry> = self
+            .markers
+            .iter()
+            .enumerate()
+            .filter(|(_, marker)| !marker.ghost)
+            .map(|(i, marker)| format::SummaryEntry {
+                index: i + 1,
+                label: marker.label_text().map(str::to_string),
+                coordinates: self.copy_coordinates(marker.system_position),
+            })
+            .collect();
+        format::share_summary(heading.as_deref(), &canvas_line, &entries)
+    }
+
+    fn show_share_summary(&mut self, ctx: &Context) {
+        let summary = match &mut self.ui_state.share_summary {
+            Some(summary) => summary,
+            None => return,
+        };
+        let mut copy = false;
+        let mut close = false;
+        egui::Window::new("Share Summary").collapsible(false).show(ctx, |ui| {
+            ui.label("Copied to the clipboard. Edit below and copy again if needed.");
+            ui.add(
+                egui::TextEdit::multiline(summary)
+                    .desired_rows(10)
+                    .desired_width(360.0)
+                    .font(egui::TextStyle::Monospace),
+            );
+            ui.horizontal(|ui| {
+                copy = ui.button("Copy").clicked();
+                close = ui.button("Close").clicked();
+            });
+        });
+
+        if copy {
+            let text = summary.clone();
+            self.copy_to_clipboard(text);
+        }
+        if close {
+            self.ui_state.share_summary = None;
+        }
+    }
+
+    // Small popup for typing grid cell or pixel positions
+    fn show_command_entry(&mut self, ctx: &Context) {
+        if !self.ui_state.command_entry.open {
+            return;
+        }
+
+        let mut submitted = None;
+        egui::Window::new("Place at grid cell")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                let entry = &mut self.ui_state.command_entry;
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut entry.input).hint_text("col,row or @x,y"),
+                );
+                response.request_focus();
+
+                if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+                    entry.history_previous();
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                    entry.history_next();
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submitted = Some(entry.input.clone());
+                }
+
+                if let Some(error) = &entry.error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                ui.label("Enter to place, Esc to close");
+            });
+
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.command_entry.close();
+        } else if let Some(input) = submitted {
+            match self.run_grid_command(&input) {
+                Ok(()) => {
+                    self.ui_state.command_entry.push_history(input.trim().to_string());
+                    self.ui_state.command_entry.close();
+                    // Follow the new marker if it landed out of view
+                    if let Some(marker) = self.markers.last() {
+                        let screen_pos = self.canvas.canvas_to_screen_pos(marker.position, self.ui_state.view_rect);
+                        if !self.ui_state.view_rect.contains(screen_pos) {
+                            self.pan_to(marker.position);
+                        }
+                    }
+                }
+                Err(err) => self.ui_state.command_entry.error = Some(err),
+            }
+        }
+    }
+
+    // Marker shown at full strength by the playback preview
+    fn playback_marker(&self) -> Option<usize> {
+        let playback = &self.ui_state.playback;
+        if playback.previewing && !self.markers.is_empty() {
+            Some(playback.frame_idx % self.markers.len())
+        } else {
+            None
+        }
+    }
+
+    fn advance_playback(&mut self, now: f64) {
+        let playback = &mut self.ui_state.playback;
+        if !playback.playing || self.markers.is_empty() {
+            playback.last_advance = now;
+            return;
+        }
+        let frame_time = 1.0 / playback.fps as f64;
+        if now - playback.last_advance >= frame_time {
+            playback.frame_idx = (playback.frame_idx + 1) % self.markers.len();
+            playback.last_advance = now;
+        }
+    }
+
+    fn show_playback_controls(&mut self, ui: &mut Ui) {
+        let last_frame = self.markers.len() - 1;
+        let playback = &mut self.ui_state.playback;
+        playback.frame_idx = playback.frame_idx.min(last_frame);
+        ui.horizontal(|ui| {
+            let label = if playback.playing { "⏸" } else { "▶" };
+            if ui.button(label).on_hover_text("Play the markers in order").clicked() {
+                playback.playing = !playback.playing;
+                playback.previewing = true;
+            }
+            if ui.add_enabled(playback.previewing, egui::Button::new("⏹")).clicked() {
+                playback.playing = false;
+                playback.previewing = false;
+            }
+            let scrubbed = ui
+                .add(egui::Slider::new(&mut playback.frame_idx, 0..=last_frame).custom_formatter(|n, _| format!("{}", n as usize + 1)))
+                .changed();
+            if scrubbed {
+                playback.previewing = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("FPS:");
+            ui.add(egui::DragValue::new(&mut playback.fps).speed(0.1).clamp_range(0.5..=60.0));
+        });
+    }
+
+    // Glide to center a canvas position at the current zoom
+    fn pan_to(&mut self, canvas_pos: egui::Pos2) {
+        self.canvas_target_offset = Some(self.canvas.centered_offset(canvas_pos, self.canvas.get_zoom()));
+    }
+
+    // Moves the canvas offset part of the way to its target each frame. The
+    // exponential factor covers the same distance per second at any frame rate.
+    fn tick_pan(&mut self, ctx: &Context) {
+        let target = match self.canvas_target_offset {
+            Some(target) => target,
+            None => return,
+        };
+        let dt = ctx.input(|i| i.stable_dt);
+        let factor = 1.0 - (-self.canvas_pan_speed * dt).exp();
+        let offset = self.canvas.get_offset();
+        let offset = offset + (target - offset) * factor;
+        if (target - offset).length() < 0.5 {
+            self.canvas.set_offset(target);
+            self.canvas_target_offset = None;
+        } else {
+            self.canvas.set_offset(offset);
+            ctx.request_repaint();
+        }
+    }
+
+    // Animate the view so the selected markers fill it, with some room around them
+    fn fit_to_selection(&mut self) {
+        const MARGIN: f32 = 40.0; // Screen px kept free around the selection
+        let positions: Vec<egui::Pos2> = self
+            .ui_state
+            .selected_markers
+            .iter()
+            .filter_map(|&i| self.markers.get(i).map(|marker| marker.position))
+            .collect();
+        if positions.is_empty() {
+            return;
+        }
+        let bounds = egui::Rect::from_points(&positions);
+        // A single point has no extent to fit, so only pan
+        if bounds.width() < 1.0 && bounds.height() < 1.0 {
+            self.pan_to(bounds.center());
+            return;
+        }
+        let view_rect = self.ui_state.view_rect.shrink(MARGIN);
+        let zoom = Canvas::zoom_to_fit(bounds, view_rect);
+        // Smooth zoom keeps the view center fixed, so the pan lands on the
+        // selection once both settle
+        self.canvas.smooth_zoom_to(zoom);
+        self.canvas_target_offset = Some(self.canvas.centered_offset(bounds.center(), zoom));
+    }
+
+    // Record the lasso path while dragging and select the enclosed markers on release
+    fn handle_lasso(&mut self, response: &egui::Response, canvas_rect: egui::Rect) {
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.ui_state.lasso_path.clear();
+        }
+
+        if response.dragged_by(egui::PointerButton::Primary) {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                // Skip points closer than a couple of screen pixels to keep the path small
+                let far_enough = match self.ui_state.lasso_path.last() {
+                    Some(last) => (*last - canvas_pos).length() * self.canvas.get_zoom() > 2.0,
+                    None => true,
+                };
+                if far_enough {
+                    self.ui_state.lasso_path.push(canvas_pos);
+                }
+            }
+        }
+
+        if response.drag_released_by(egui::PointerButton::Primary) {
+            let polygon = std::mem::take(&mut self.ui_state.lasso_path);
+            self.ui_state.selected_markers = self
+                .markers
+                .iter()
+                .enumerate()
+                .filter(|(_, marker)| self.groups.is_visible(marker.group))
+                .filter(|(_, marker)| analysis::point_in_polygon(marker.position, &polygon))
+                .map(|(i, _)| i)
+                .collect();
+        }
+    }
+
+    // Ctrl+Shift+drag draws a rectangle that the view zooms to fill on release
+    fn handle_marquee_zoom(&mut self, ui: &Ui, response: &egui::Response, canvas_rect: egui::Rect) {
+        // Smaller drags (in screen px) are treated as accidental
+        const MIN_DRAG: f32 = 8.0;
+
+        let modifiers = ui.input(|i| i.modifiers);
+        if response.drag_started_by(egui::PointerButton::Primary) && modifiers.command && modifiers.shift {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.ui_state.marquee = Some((canvas_pos, canvas_pos));
+            }
+        }
+
+        let (start, _) = match self.ui_state.marquee {
+            Some(marquee) => marquee,
+            None => return,
+        };
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.marquee = None;
+            return;
+        }
+        if let Some(pos) = response.interact_pointer_pos() {
+            let end = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+            self.ui_state.marquee = Some((start, end));
+        }
+
+        if response.drag_released_by(egui::PointerButton::Primary) {
+            if let Some((start, end)) = self.ui_state.marquee.take() {
+                let region = egui::Rect::from_two_pos(start, end);
+                if region.size().max_elem() * self.canvas.get_zoom() >= MIN_DRAG {
+                    let zoom = Canvas::zoom_to_fit(region, canvas_rect);
+                    self.canvas_target_offset = None;
+                    self.canvas.animate_to(region.center(), zoom, ui.input(|i| i.time));
+                }
+            }
+        }
+    }
+
+    // Shift+drag draws a rectangle that selects the markers inside it on release
+    fn handle_selection_rect(&mut self, ui: &Ui, response: &egui::Response, canvas_rect: egui::Rect) {
+        let modifiers = ui.input(|i| i.modifiers);
+        if response.drag_started_by(egui::PointerButton::Primary) && modifiers.shift && !modifiers.command {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.ui_state.selection_rect = Some((canvas_pos, canvas_pos));
+            }
+        }
+
+        let (start, _) = match self.ui_state.selection_rect {
+            Some(selection_rect) => selection_rect,
+            None => return,
+        };
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.selection_rect = None;
+            return;
+        }
+        if let Some(pos) = response.interact_pointer_pos() {
+            let end = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+            self.ui_state.selection_rect = Some((start, end));
+        }
+
+        if response.drag_released_by(egui::PointerButton::Primary) {
+            if let Some((start, end)) = self.ui_state.selection_rect.take() {
+                let region = egui::Rect::from_two_pos(start, end);
+                self.ui_state.analysis_region = Some(region);
+                self.ui_state.selected_markers = self
+                    .markers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, marker)| self.groups.is_visible(marker.group))
+                    .filter(|(_, marker)| region.contains(marker.position))
+                    .map(|(i, _)| i)
+                    .collect();
+            }
+        }
+    }
+
+    // With Move Selected on, dragging moves every selected marker by the same
+    // amount. The whole move is one undo step.
+    fn handle_selection_move(&mut self, response: &egui::Response, canvas_rect: egui::Rect) {
+        if !self.ui_state.move_selection || self.ui_state.selected_markers.is_empty() {
+            self.ui_state.moving_selection = None;
+            return;
+        }
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(pos, canvas_rect);
+                self.ui_state.moving_selection = Some((canvas_pos, self.markers.clone()));
+            }
+        }
+
+        let (start, before) = match self.ui_state.moving_selection.take() {
+            Some(moving) => moving,
+            None => return,
+        };
+        if let Some(pos) = response.interact_pointer_pos() {
+            let delta = self.canvas.screen_to_canvas_pos(pos, canvas_rect) - start;
+            let moved: Vec<(usize, egui::Pos2)> = self
+                .ui_state
+                .selected_markers
+                .iter()
+                .filter_map(|&i| before.get(i).map(|marker| (i, marker.position + delta)))
+                .collect();
+            // The group stops at the canvas edge rather than leaving some markers behind
+            if moved.iter().all(|&(_, position)| self.is_inside_canvas(position)) {
+                for (i, position) in moved {
+                    if let Some(marker) = self.markers.get_mut(i) {
+                        marker.position = position;
+                        marker.system_position = self.coordinate_system.to_system_coordinates(position);
+                    }
+                }
+            }
+        }
+
+        if response.drag_released_by(egui::PointerButton::Primary) {
+            let changed = before
+                .iter()
+                .zip(&self.markers)
+                .any(|(old, new)| old.position != new.position);
+            if changed {
+                let selected = std::mem::take(&mut self.ui_state.selected_markers);
+                self.undo_stack.push(MarkerCommand::Replace {
+                    before,
+                    after: self.markers.clone(),
+                });
+                self.ui_state.selected_markers = selected;
+            }
+        } else {
+            self.ui_state.moving_selection = Some((start, before));
+        }
+    }
+
+    fn copy_selected_markers(&mut self) {
+        let mut selected: Vec<usize> = self.ui_state.selected_markers.iter().copied().collect();
+        selected.sort_unstable();
+        let text = self.copy_text(&selected);
+        self.copy_to_clipboard(text);
+    }
+
+    fn delete_selected_markers(&mut self) {
+        if self.ui_state.selected_markers.is_empty() {
+            return;
+        }
+        let before = self.markers.clone();
+        let selected = std::mem::take(&mut self.ui_state.selected_markers);
+        let mut index = 0;
+        self.markers.retain(|_| {
+            index += 1;
+            !selected.contains(&(index - 1))
+        });
+        self.ui_state.expanded_notes.clear();
+        self.undo_stack.push(MarkerCommand::Replace {
+            before,
+            after: self.markers.clone(),
+        });
+    }
+
+    // Index of the visible marker under a canvas position, if any
+    fn marker_at(&self, position: egui::Pos2) -> Option<usize> {
+        self.marker_near(position, self.canvas.get_zoom())
+    }
+
+    // The threshold is converted from screen px to canvas units, so clicking
+    // a marker feels the same at every zoom level
+    fn marker_near(&self, position: egui::Pos2, zoom: f32) -> Option<usize> {
+        let canvas_threshold = MARKER_SCREEN_THRESHOLD / zoom;
+
+        self.markers.iter().position(|marker| {
+            let delta = marker.position - position;
+            delta.length() < canvas_threshold && self.groups.is_visible(marker.group)
+        })
+    }
+
+    // Ghost markers are reference points and survive right-clicks
+    fn remove_nearby_marker(&mut self, position: egui::Pos2, zoom: f32) {
+        if let Some(index) = self.marker_near(position, zoom) {
+            if !self.markers[index].ghost {
+                self.remove_marker(index);
+            }
+        }
+    }
+
+    fn confirm_marker_edit(&mut self) {
+        if let Some(edit) = self.ui_state.editing_marker.take() {
+            let (canvas_width, canvas_height) = self.canvas.get_size();
+            let canvas_pos = self.coordinate_system.from_system_coordinates(edit.system_position);
+            let clamped = egui::pos2(
+                canvas_pos.x.clamp(0.0, canvas_width),
+                canvas_pos.y.clamp(0.0, canvas_height),
+            );
+
+            if let Some(marker) = self.markers.get_mut(edit.index) {
+                let from = marker.position;
+                marker.position = clamped;
+                marker.system_position = self.coordinate_system.to_system_coordinates(clamped);
+                self.record_move(edit.index, from);
+            }
+        }
+    }
+
+    // In-place popup for typing a marker's position in system coordinates
+    fn show_marker_editor(&mut self, ctx: &Context) {
+        let (index, just_opened) = match &self.ui_state.editing_marker {
+            Some(edit) => (edit.index, edit.just_opened),
+            None => return,
+        };
+        let marker_position = match self.markers.get(index) {
+            Some(marker) => marker.position,
+            None => {
+                self.ui_state.editing_marker = None;
+                return;
+            }
+        };
+
+        let screen_pos = self
+            .canvas
+            .canvas_to_screen_pos(marker_position, self.ui_state.view_rect);
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        let window = egui::Window::new(format!("Marker {}", index + 1))
+            .id(egui::Id::new("marker_editor"))
+            .collapsible(false)
+            .resizable(false)
+            .fixed_pos(screen_pos + egui::vec2(12.0, 12.0))
+            .show(ctx, |ui| {
+                if let Some(edit) = &mut self.ui_state.editing_marker {
+                    ui.horizontal(|ui| {
+                        ui.label("X:");
+                        ui.add(egui::DragValue::new(&mut edit.system_position.x).speed(1.0));
+                        ui.label("Y:");
+                        ui.add(egui::DragValue::new(&mut edit.system_position.y).speed(1.0));
+                    });
+                }
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("OK").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        // Clicking anywhere outside the popup confirms, like pressing Enter
+        let clicked_outside = !just_opened
+            && ctx.input(|i| {
+                i.pointer.any_click()
+                    && match (i.pointer.interact_pos(), &window) {
+                        (Some(pos), Some(window)) => !window.response.rect.contains(pos),
+                        _ => false,
+                    }
+            });
+
+        if cancelled || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.editing_marker = None;
+        } else if confirmed || clicked_outside || ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+            self.confirm_marker_edit();
+        } else if let Some(edit) = &mut self.ui_state.editing_marker {
+            edit.just_opened = false;
+        }
+    }
+
+    // New canvas positions for the markers the quantize settings apply to
+    fn quantized_positions(&self, settings: &QuantizeSettings) -> Vec<(usize, egui::Pos2)> {
+        let quantize = |pos: egui::Pos2| {
+            egui::pos2(
+                grid::quantize(pos.x, settings.step_x, settings.rounding),
+                grid::quantize(pos.y, settings.step_y, settings.rounding),
+            )
+        };
+        let mut indices: Vec<usize> = if settings.selection_only {
+            self.ui_state.selected_markers.iter().copied().collect()
+        } else {
+            (0..self.markers.len()).collect()
+        };
+        indices.sort_unstable();
+
+        indices
+            .into_iter()
+            .filter_map(|index| {
+                let marker = self.markers.get(index)?;
+                let position = if settings.align_to_grid_lines {
+                    quantize(marker.position)
+                } else {
+                    // Round the numbers the user sees, whatever the origin
+                    let system_position = quantize(marker.system_position);
+                    if system_position == marker.system_position {
+                        marker.position
+                    } else {
+                        self.coordinate_system.from_system_coordinates(system_position)
+                    }
+                };
+                Some((index, position))
+            })
+            .collect()
+    }
+
+    fn show_quantize_dialog(&mut self, ctx: &Context) {
+        let preview = match &self.ui_state.quantize {
+            Some(settings) => self.quantized_positions(settings),
+            None => return,
+        };
+        let displacements: Vec<f32> = preview
+            .iter()
+            .map(|&(index, position)| (position - self.markers[index].position).length())
+            .collect();
+        let moved = displacements.iter().filter(|&&distance| distance > 0.0).count();
+        let max_displacement = displacements.iter().copied().fold(0.0, f32::max);
+        let average_displacement = if displacements.is_empty() {
+            0.0
+        } else {
+            displacements.iter().sum::<f32>() / displacements.len() as f32
+        };
+        let has_selection = !self.ui_state.selected_markers.is_empty();
+        let mut apply = false;
+        let mut cancel = false;
+
+        let settings = match &mut self.ui_state.quantize {
+            Some(settings) => settings,
+            None => return,
+        };
+        egui::Window::new("Quantize Markers")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("quantize_settings").num_columns(2).show(ui, |ui| {
+                    ui.label("Step X:");
+                    ui.add(egui::DragValue::new(&mut settings.step_x).speed(1.0).clamp_range(1.0..=1000.0));
+                    ui.end_row();
+                    ui.label("Step Y:");
+                    ui.add(egui::DragValue::new(&mut settings.step_y).speed(1.0).clamp_range(1.0..=1000.0));
+                    ui.end_row();
+                    ui.label("Ties:");
+                    egui::ComboBox::from_id_source("quantize_rounding")
+                        .selected_text(settings.rounding.label())
+                        .show_ui(ui, |ui| {
+                            for rounding in RoundingMode::ALL {
+                                ui.selectable_value(&mut settings.rounding, rounding, rounding.label());
+                            }
+                        });
+                    ui.end_row();
+                });
+                ui.add_enabled(
+                    has_selection,
+                    egui::Checkbox::new(&mut settings.selection_only, "Selected markers only"),
+                );
+                ui.checkbox(&mut settings.align_to_grid_lines, "Align to drawn grid lines")
+                    .on_hover_text("Quantize canvas positions rather than displayed coordinates; they differ when the origin isn't top-left");
+
+                ui.separator();
+                ui.label(format!("{} of {} markers will move", moved, preview.len()));
+                ui.label(format!(
+                    "Max displacement: {:.1} px, average: {:.1} px",
+                    max_displacement, average_displacement
+                ));
+                ui.horizontal(|ui| {
+                    apply = ui.add_enabled(moved > 0, egui::Button::new("Apply")).clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if apply {
+            let before = self.markers.clone();
+            for (index, position) in preview {
+                let marker = &mut self.markers[index];
+                marker.position = position;
+                marker.system_position = self.coordinate_system.to_system_coordinates(position);
+            }
+            self.record_replace(before);
+            self.check_canvas_bounds();
+        }
+        if apply || cancel {
+            self.ui_state.quantize = None;
+        }
+    }
+
+    fn load_expected_points(&mut self) {
+        let path = match rfd::FileDialog::new()
+            .add_filter("Expected coordinates", &["json", "csv"])
+            .pick_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        let is_csv = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                if is_csv {
+                    validation::expected_from_csv(&contents)
+                } else {
+                    validation::expected_from_json(&contents)
+                }
+            });
+        match result {
+            Ok(points) => self.ui_state.expected_points = Some(points),
+            Err(err) => self.ui_state.export_status = Some(format!("Validation failed: {}", err)),
+        }
+    }
+
+    // Recomputed every frame so results follow markers as they are edited
+    fn validation_results(&self) -> Option<Vec<ValidationResult>> {
+        let expected = self.ui_state.expected_points.as_ref()?;
+        let canvas_size = self.canvas.get_size();
+        let transform = self.ui_state.output_transform;
+        let actual: Vec<egui::Pos2> = self
+            .markers
+            .iter()
+            .map(|marker| transform.apply(marker.position, canvas_size))
+            .collect();
+        Some(validation::validate(expected, &actual, self.ui_state.validation_tolerance))
+    }
+
+    fn export_validation_results(&mut self, results: &[ValidationResult]) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("validation.csv");
+        if let Some(path) = dialog.save_file() {
+            if let Err(err) = std::fs::write(&path, validation::results_to_csv(results)) {
+                self.ui_state.export_status = Some(format!("Validation export failed: {}", err));
+            }
+        }
+    }
+
+    fn show_validation_window(&mut self, ctx: &Context) {
+        let results = match self.validation_results() {
+            Some(results) => results,
+            None => return,
+        };
+        let passed = results.iter().filter(|result| result.passed()).count();
+        let mut export = false;
+        let mut close = false;
+
+        egui::Window::new("Validation").collapsible(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Tolerance:");
+                ui.add(
+                    egui::DragValue::new(&mut self.ui_state.validation_tolerance)
+                        .speed(0.1)
+                        .clamp_range(0.0..=1000.0)
+                        .suffix(" px"),
+                );
+            });
+            ui.label(format!(
+                "{} passed, {} failed of {}",
+                passed,
+                results.len() - passed,
+                results.len()
+            ));
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("validation_results").num_columns(5).striped(true).show(ui, |ui| {
+                    for heading in ["Point", "Marker", "Δx, Δy", "Distance", "Result"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for result in &results {
+                        let color = validation_color(result.passed());
+                        ui.label(&result.name);
+                        match result.marker {
+                            Some(index) => ui.label(format!("#{}", index + 1)),
+                            None => ui.label("missing"),
+                        };
+                        match (result.delta, result.distance()) {
+                            (Some((dx, dy)), Some(distance)) => {
+                                ui.label(format!("{:+.1}, {:+.1}", dx, dy));
+                                ui.label(format!("{:.2} / {}", distance, result.tolerance));
+                            }
+                            _ => {
+                                ui.label("–");
+                                ui.label("–");
+                            }
+                        }
+                        let verdict = if result.passed() { "pass" } else { "fail" };
+                        ui.label(egui::RichText::new(verdict).color(Color32::WHITE).background_color(color));
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                export = ui.button("Export CSV…").clicked();
+                close = ui.button("Close").clicked();
+            });
+        });
+
+        if export {
+            self.export_validation_results(&results);
+        }
+        if close {
+            self.ui_state.expected_points = None;
+        }
+    }
+
+    // Remove a marker and shift selected indices above it down by one
+    fn remove_marker(&mut self, index: usize) {
+        let marker = self.markers.remove(index);
+        self.undo_stack.push(MarkerCommand::Remove { index, marker });
+        shift_indices_after_removal(&mut self.ui_state.selected_markers, index);
+        shift_indices_after_removal(&mut self.ui_state.expanded_notes, index);
+    }
+
+    fn clear_markers(&mut self) {
+        if !self.markers.is_empty() {
+            let before = std::mem::take(&mut *self.markers);
+            self.undo_stack.push(MarkerCommand::Replace { before, after: Vec::new() });
+        }
+        self.ui_state.selected_markers.clear();
+        self.ui_state.expanded_notes.clear();
+    }
+
+    // Detail area under a marker row: note editor, age and coordinates
+    fn show_marker_details(&mut self, ui: &mut Ui, index: usize) {
+        let (width, height) = self.canvas.get_size();
+        ui.indent(("marker_details", index), |ui| {
+            // Edited through a copy so an open note doesn't touch the marker list every frame
+            let mut note = self.markers[index].note.clone();
+            let note_response = ui.add(egui::TextEdit::multiline(&mut note).hint_text("Note").desired_rows(3));
+            if note_response.changed() {
+                self.markers[index].note = note;
+            }
+            self.track_text_edit(&note_response);
+            let marker = &self.markers[index];
+            ui.label(format!("Created {}", marker.age_label()));
+            ui.label(format!("Raw: ({:.1}, {:.1})", marker.position.x, marker.position.y));
+            ui.label(format!(
+                "System: ({:.1}, {:.1})",
+                marker.system_position.x, marker.system_position.y
+            ));
+            ui.label(format!(
+                "Normalized: ({:.3}, {:.3})",
+                marker.position.x / width,
+                marker.position.y / height
+            ));
+        });
+    }
+
+    fn background_color(&self) -> Color32 {
+        if self.ui_state.dark_mode {
+            Color32::from_rgb(20, 20, 20)
+        } else {
+            Color32::from_rgb(240, 240, 240)
+        }
+    }
+
+    // Sizes drawn as nested outlines, with the active one chosen by radio button
+    fn nested_sizes_section(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.ui_state.show_nested_sizes, "Show nested outlines");
+
+        let mut remove = None;
+        let mut activate = None;
+        for (i, (name, width, height)) in self.secondary_sizes.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let is_active = self.ui_state.selected_resolution == *name && self.canvas.get_size() == (*width, *height);
+                if ui.radio(is_active, "").on_hover_text("Use for coordinate output").clicked() {
+                    activate = Some(i);
+                }
+                ui.colored_label(NESTED_SIZE_COLORS[i], format!("{} ({}x{})", name, width, height));
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = activate {
+            let (name, width, height) = self.secondary_sizes[i].clone();
+            self.ui_state.selected_resolution = name;
+            self.ui_state.custom_width = width;
+            self.ui_state.custom_height = height;
+        }
+        if let Some(i) = remove {
+            self.secondary_sizes.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("nested_size_preset")
+                .selected_text(&self.ui_state.new_nested_size)
+                .show_ui(ui, |ui| {
+                    for preset in self.resolution_presets.keys() {
+                        ui.selectable_value(&mut self.ui_state.new_nested_size, preset.clone(), preset);
+                    }
+                });
+            let full = self.secondary_sizes.len() >= NESTED_SIZE_COLORS.len();
+            if ui.add_enabled(!full, egui::Button::new("Add")).clicked() {
+                let name = self.ui_state.new_nested_size.clone();
+                // Custom takes the size currently entered in the Width/Height fields
+                let size = match name.as_str() {
+                    "Custom" => Some((self.ui_state.custom_width, self.ui_state.custom_height)),
+                    _ => self.resolution_presets.get(&name).copied(),
+                };
+                if let Some((width, height)) = size {
+                    self.secondary_sizes.push((name, width, height));
+                }
+            }
+        });
+    }
+
+    // Outlines share the corner at the coordinate origin, so the same position
+    // can be read against every size
+    fn draw_nested_sizes(&self, painter: &egui::Painter, canvas_rect: egui::Rect) {
+        let (canvas_width, canvas_height) = self.canvas.get_size();
+        let mut sizes: Vec<(usize, &(String, f32, f32))> = self.secondary_sizes.iter().enumerate().collect();
+        // Largest first so the smaller labels are drawn on top
+        sizes.sort_by(|(_, a), (_, b)| (b.1 * b.2).total_cmp(&(a.1 * a.2)));
+        for (i, (name, width, height)) in sizes {
+            // Anchored where the origin is, so the sizes share its (0, 0)
+            let min_canvas = match self.ui_state.origin_mode {
+                OriginMode::TopLeft => egui::pos2(0.0, 0.0),
+                OriginMode::BottomLeft => egui::pos2(0.0, canvas_height - height),
+                OriginMode::Center => egui::pos2((canvas_width - width) / 2.0, (canvas_height - height) / 2.0),
+            };
+            let min = self.canvas.canvas_to_screen_pos(min_canvas, canvas_rect);
+            let max = self.canvas.canvas_to_screen_pos(min_canvas + egui::vec2(*width, *height), canvas_rect);
+            let rect = egui::Rect::from_min_max(min, max);
+            let stroke = Stroke::new(1.5, NESTED_SIZE_COLORS[i]);
+            for side in [
+                [rect.left_top(), rect.right_top()],
+                [rect.right_top(), rect.right_bottom()],
+                [rect.right_bottom(), rect.left_bottom()],
+                [rect.left_bottom(), rect.left_top()],
+            ] {
+                painter.extend(egui::Shape::dashed_line(&side, stroke, 6.0, 4.0));
+            }
+            painter.text(
+                rect.right_top() + egui::vec2(-4.0, 4.0),
+                egui::Align2::RIGHT_TOP,
+                name,
+                egui::FontId::proportional(11.0),
+                NESTED_SIZE_COLORS[i],
+            );
+        }
+    }
+
+    fn border_color(&self) -> Color32 {
+        if self.ui_state.dark_mode {
+            Color32::from_rgb(150, 150, 150)
+        } else {
+            Color32::from_rgb(100, 100, 100)
+        }
+    }
+
+    fn grid_color(&self) -> Color32 {
+        if let (true, Some(background)) = (self.ui_state.grid_auto_contrast, &self.background_image) {
+            return grid::contrasting_grid_color(background.average_luminance);
+        }
+        if self.ui_state.dark_mode {
+            Color32::from_rgba_premultiplied(180, 180, 180, 60)
+        } else {
+            Color32::from_rgba_premultiplied(80, 80, 80, 80)
+        }
+    }
+
+    // Grid and background image as drawn on the canvas, for rendered views and exports
+    fn canvas_layers(&self) -> CanvasLayers<'_> {
+        CanvasLayers {
+            grid: if self.grid.is_visible() {
+                Some((&self.grid, self.grid_color()))
+            } else {
+                None
+            },
+            image: self.background_image.as_ref().map(|background| ImageLayer {
+                pixels: &background.pixels,
+                mode: self.ui_state.composite_mode,
+                opacity: self.ui_state.background_opacity / 100.0,
+                gamma: self.ui_state.gamma,
+            }),
+            grid_above_image: self.ui_state.grid_layer == GridLayer::AboveImage,
+        }
+    }
+
+    // Render the visible canvas area and put it on the clipboard as an image
+    fn copy_view_as_image(&mut self, ctx: &Context) {
+        let view_rect = self.ui_state.view_rect;
+        if !view_rect.is_positive() {
+            return;
+        }
+
+        let pixels_per_point = ctx.pixels_per_point();
+        let screen_rect = self.canvas.get_screen_rect(view_rect);
+        let markers = self.export_marker_list();
+        // The same text draw_canvas puts next to each marker
+        let labels = if self.ui_state.show_marker_labels {
+            markers
+                .iter()
+                .map(|marker| {
+                    let coordinates = self.position_text(marker.system_position);
+                    match marker.label_text() {
+                        Some(label) => format!("{} {}", label, coordinates),
+                        None => coordinates,
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let view = RenderView {
+            width: (view_rect.width() * pixels_per_point).round() as u32,
+            height: (view_rect.height() * pixels_per_point).round() as u32,
+            canvas_origin: ((screen_rect.min - view_rect.min) * pixels_per_point).to_pos2(),
+            zoom: self.canvas.get_zoom() * pixels_per_point,
+            marker_radius: 5.0 * pixels_per_point,
+            background: self.background_color(),
+            border: Some(self.border_color()),
+            layers: self.canvas_layers(),
+            labels,
+            label_color: if self.ui_state.dark_mode {
+                Color32::WHITE
+            } else {
+                Color32::BLACK
+            },
+            label_size: egui::FontId::default().size * pixels_per_point,
+        };
+        let image = export::render_image(&view, self.canvas.get_size(), &markers);
+
+        let message = match copy_image_to_clipboard(&image) {
+            Ok(()) => format!("Copied {}x{} view to clipboard", image.width(), image.height()),
+            Err(_) => {
+                // Not every platform supports clipboard images, so fall back to a file
+                let path = std::env::temp_dir().join("coordinate_picker_view.png");
+                match image.save(&path) {
+                    Ok(()) => format!(
+                        "Clipboard images unsupported, saved {}x{} view to {}",
+                        image.width(),
+                        image.height(),
+                        path.display()
+                    ),
+                    Err(err) => format!("Failed to copy view: {}", err),
+                }
+            }
+        };
+        self.show_toast(ctx, message);
+    }
+
+    fn show_toast(&mut self, ctx: &Context, message: String) {
+        let expires_at = ctx.input(|i| i.time) + 3.0;
+        self.ui_state.toast = Some(Toast { message, expires_at });
+    }
+
+    fn draw_toast(&mut self, ctx: &Context) {
+        if let Some(toast) = &self.ui_state.toast {
+            if ctx.input(|i| i.time) > toast.expires_at {
+                self.ui_state.toast = None;
+                return;
+            }
+
+            egui::Area::new("toast")
+                .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -20.0))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(&toast.message);
+                    });
+                });
+        }
+    }
+
+    // e.g. "Coordinate Picker | Grid: ON | Snap: OFF | 1920×1080"
+    fn update_window_title(&mut self, frame: &mut eframe::Frame) {
+        let on_off = |on: bool| if on { "ON" } else { "OFF" };
+        let (width, height) = self.canvas.get_size();
+        let app_name = match self.ui_state.session.title.trim() {
+            "" => "Coordinate Picker".to_string(),
+            title => format!("Coordinate Picker — {}", title),
+        };
+        let title = format!(
+            "{} | Grid: {} | Snap: {} | {}×{}",
+            app_name,
+            on_off(self.grid.is_visible()),
+            on_off(self.grid.is_snapping_enabled()),
+            width as u32,
+            height as u32
+        );
+        if title != self.window_title {
+            frame.set_window_title(&title);
+            self.window_title = title;
+        }
+    }
+
+    // Start or stop the WebSocket server to match the settings toggle
+    #[cfg(feature = "websocket")]
+    fn update_position_stream(&mut self) {
+        // Dropping the old stream shuts its server thread down
+        self.position_stream = None;
+        self.ui_state.stream_error = None;
+        if self.ui_state.stream_enabled {
+            match PositionStream::start(self.ui_state.stream_port) {
+                Ok(stream) => self.position_stream = Some(stream),
+                Err(err) => {
+                    self.ui_state.stream_enabled = false;
+                    self.ui_state.stream_error = Some(format!("Couldn't start stream: {}", err));
+                }
+            }
+        }
+    }
+
+    // Save the current view into a 1-based slot, or append it when `slot` is None
+    fn save_view_bookmark(&mut self, slot: Option<usize>, name: String) {
+        let bookmark = ViewBookmark {
+            name,
+            center: self.canvas.view_center(),
+            zoom: self.canvas.get_zoom(),
+        };
+        let bookmarks = &mut self.ui_state.view_bookmarks;
+        match slot {
+            Some(slot) if slot <= bookmarks.len() => bookmarks[slot - 1] = bookmark,
+            _ => bookmarks.push(bookmark),
+        }
+    }
+
+    fn recall_view_bookmark(&mut self, ctx: &Context, slot: usize) {
+        if let Some(bookmark) = self.ui_state.view_bookmarks.get(slot - 1) {
+            let now = ctx.input(|i| i.time);
+            self.canvas_target_offset = None;
+            self.canvas.animate_to(bookmark.center, bookmark.zoom, now);
+        }
+    }
+
+    // Global keyboard shortcuts, ignored while a text field has focus
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        let keys = self.ui_state.key_bindings;
+        if ctx.wants_keyboard_input() {
+            // Typing Tab into a field shouldn't leave the canvas blank
+            if !self.ui_state.latch_hide_overlays {
+                self.ui_state.overlays_suppressed = false;
+            }
+            return;
+        }
+
+        if self.ui_state.latch_hide_overlays {
+            if ctx.input_mut(|i| i.consume_shortcut(&keys.hide_overlays)) {
+                self.ui_state.overlays_suppressed = !self.ui_state.overlays_suppressed;
+            }
+        } else {
+            self.ui_state.overlays_suppressed = ctx.input(|i| {
+                i.key_down(keys.hide_overlays.key) && i.modifiers.matches(keys.hide_overlays.modifiers)
+            });
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.shortcuts_overlay)) {
+            self.ui_state.show_shortcuts_overlay = true;
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.command_entry)) {
+            self.ui_state.command_entry.open();
+        }
+
+        const NUMBER_KEYS: [egui::Key; 9] = [
+            egui::Key::Num1,
+            egui::Key::Num2,
+            egui::Key::Num3,
+            egui::Key::Num4,
+            egui::Key::Num5,
+            egui::Key::Num6,
+            egui::Key::Num7,
+            egui::Key::Num8,
+            egui::Key::Num9,
+        ];
+        for (i, key) in NUMBER_KEYS.into_iter().enumerate() {
+            let slot = i + 1;
+            if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, key)) {
+                self.save_view_bookmark(Some(slot), format!("View {}", slot));
+            } else if ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, key)) {
+                self.recall_view_bookmark(ctx, slot);
+            }
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.reset_zoom)) {
+            self.canvas_target_offset = None;
+            self.canvas.set_zoom(canvas::DEFAULT_ZOOM);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.reset_pan)) {
+            self.canvas_target_offset = None;
+            self.canvas.set_offset(egui::Vec2::ZERO);
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            match self.ui_state.active_tool {
+                Tool::Measure => {
+                    self.ui_state.measure_first = None;
+                    self.ui_state.measurement = None;
+                }
+                Tool::Angle => self.ui_state.angle_points.clear(),
+                _ => {}
+            }
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.set_origin)) {
+            self.ui_state.active_tool = if self.ui_state.active_tool == Tool::SetOrigin {
+                Tool::Place
+            } else {
+                Tool::SetOrigin
+            };
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.duplicate)) {
+            let mut selected: Vec<usize> = self.ui_state.selected_markers.iter().copied().collect();
+            selected.sort_unstable();
+            self.duplicate_markers(ctx, &selected);
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.copy_view)) {
+            self.copy_view_as_image(ctx);
+        }
+
+        let redo_alternate = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.redo) || i.consume_shortcut(&redo_alternate)) {
+            self.redo();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&keys.undo)) {
+            self.undo();
+        }
+    }
+
+    // Full-screen cheat sheet, dismissed by any key press or click
+    fn show_shortcuts_overlay(&mut self, ctx: &Context) {
+        let dismissed = ctx.input(|i| {
+            i.pointer.any_click()
+                || i.events
+                    .iter()
+                    .any(|event| matches!(event, egui::Event::Key { pressed: true, .. }))
+        });
+        if dismissed {
+            self.ui_state.show_shortcuts_overlay = false;
+            return;
+        }
+
+        let screen_rect = ctx.screen_rect();
+        egui::Area::new("shortcuts_overlay")
+            .order(egui::Order::Foreground)
+            .fixed_pos(screen_rect.min)
+            .show(ctx, |ui| {
+                ui.painter().rect_filled(screen_rect, 0.0, Color32::from_black_alpha(180));
+                // Keeps clicks from reaching the canvas underneath
+                ui.allocate_rect(screen_rect, egui::Sense::click());
+
+                let card_rect = egui::Rect::from_center_size(screen_rect.center(), egui::vec2(420.0, 340.0));
+                ui.allocate_ui_at_rect(card_rect, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.vertical_centered(|ui| {
+                            ui.heading("Keyboard Shortcuts");
+                            ui.label("Press any key or click to dismiss.");
+                        });
+                        ui.separator();
+                        egui::Grid::new("shortcuts_table")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Action");
+                                ui.strong("Key");
+                                ui.end_row();
+                                for (action, key) in input::list_shortcuts(&self.ui_state.key_bindings) {
+                                    ui.label(action);
+                                    ui.monospace(key);
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+            });
+    }
+
+    // All visible marker dots as plain squares in a single mesh. Rebuilt only when
+    // the canvas moves or the markers, their color settings or group visibility
+    // change. Shape::Mesh owns its mesh in egui 0.22, so the cached one is still
+    // copied once per frame.
+    fn marker_mesh(&mut self, border_rect: egui::Rect, colors: Option<&[Color32]>) -> egui::Shape {
+        let zoom = self.canvas.get_zoom();
+        // Colors only change with the markers or with these settings, so nothing per marker is compared
+        let key = MarkerMeshKey {
+            generation: self.markers.generation(),
+            origin: border_rect.min,
+            zoom,
+            color_by: self.ui_state.color_by,
+            colormap: self.ui_state.colormap,
+            playback_marker: self.playback_marker(),
+            hidden_groups: self
+                .groups
+                .groups()
+                .iter()
+                .filter(|group| !group.visible)
+                .map(|group| group.id)
+                .collect(),
+        };
+
+        if !matches!(&self.marker_mesh, Some((cached_key, _)) if *cached_key == key) {
+            let mut mesh = egui::Mesh::default();
+            for (i, marker) in self.markers.iter().enumerate() {
+                if !self.groups.is_visible(marker.group) {
+                    continue;
+                }
+                let color = match colors {
+                    Some(colors) => marker.ghosted(colors[i]),
+                    None => marker.display_color(),
+                };
+                let screen_pos = border_rect.min + marker.position.to_vec2() * zoom;
+                mesh.add_colored_rect(egui::Rect::from_center_size(screen_pos, egui::vec2(10.0, 10.0)), color);
+            }
+            self.marker_mesh = Some((key, mesh));
+        }
+
+        match &self.marker_mesh {
+            Some((_, mesh)) => egui::Shape::mesh(mesh.clone()),
+            None => egui::Shape::Noop,
+        }
+    }
+
+    // Draw the main canvas and all its elements
+    fn draw_canvas(&mut self, ui: &mut Ui) -> egui::Response {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
+        let canvas_rect = response.rect;
+        painter.rect_filled(canvas_rect, 0.0, self.background_color());
+
+        let border_rect = self.canvas.get_screen_rect(canvas_rect);
+
+        let show_grid = self.grid_visibility_alpha > 0.0 && !self.ui_state.overlays_suppressed;
+        let grid_above_image = self.ui_state.grid_layer == GridLayer::AboveImage;
+        if show_grid && !grid_above_image {
+            self.draw_grid(&painter, canvas_rect, border_rect);
+        }
+
+        if let Some(BackgroundImage { texture, .. }) = &self.background_image {
+            let opacity = self.ui_state.background_opacity / 100.0;
+            let image_rect = egui::Rect::from_min_max(
+                self.canvas.canvas_to_screen_pos(egui::Pos2::ZERO, canvas_rect),
+                self.canvas.canvas_to_screen_pos(texture.size_vec2().to_pos2(), canvas_rect),
+            );
+            let uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+            painter
+                .with_clip_rect(border_rect.intersect(canvas_rect))
+                .image(texture.id(), image_rect, uv, Color32::WHITE.gamma_multiply(opacity));
+        }
+
+        if ui.input(|i| !i.raw.hovered_files.is_empty()) {
+            painter.rect_filled(canvas_rect, 0.0, Color32::from_black_alpha(120));
+            painter.text(
+                canvas_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop image to use as background",
+                egui::FontId::proportional(20.0),
+                Color32::WHITE,
+            );
+        }
+
+        if self.ui_state.overlays_suppressed {
+            painter.rect_stroke(border_rect, 0.0, Stroke::new(2.0, self.border_color()));
+            painter.text(
+                canvas_rect.center_top() + egui::vec2(0.0, 8.0),
+                egui::Align2::CENTER_TOP,
+                "overlays hidden",
+                egui::FontId::proportional(12.0),
+                Color32::GRAY,
+            );
+            return response;
+        }
+
+        if show_grid && grid_above_image {
+            self.draw_grid(&painter, canvas_rect, border_rect);
+        }
+
+        if self.ui_state.pixel_grid.enabled {
+            self.draw_pixel_grid(&painter, canvas_rect, border_rect, response.hover_pos());
+        }
+
+        painter.rect_stroke(border_rect, 0.0, Stroke::new(2.0, self.border_color()));
+
+        if self.ui_state.show_nested_sizes {
+            self.draw_nested_sizes(&painter, canvas_rect);
+        }
+
+        // Large sessions draw dots as one mesh and only label hovered/selected markers
+        let use_mesh = self.markers.len() > self.ui_state.marker_mesh_threshold;
+        let colormap = self.colormap_colors();
+        let mut draw_colors = colormap.as_ref().map(|(colors, _)| colors.clone());
+        let playback_marker = self.playback_marker();
+        if let Some(current) = playback_marker {
+            let colors = draw_colors.get_or_insert_with(|| self.markers.iter().map(|marker| marker.color).collect());
+            for (i, color) in colors.iter_mut().enumerate() {
+                if i != current {
+                    *color = color.gamma_multiply(0.3);
+                }
+            }
+        }
+        if use_mesh && self.ui_state.show_marker_dots {
+            painter.add(self.marker_mesh(border_rect, draw_colors.as_deref()));
+        }
+        let marker_color = |i: usize, marker: &Marker| match &draw_colors {
+            Some(colors) => marker.ghosted(colors[i]),
+            None => marker.display_color(),
+        };
+        let hovered_marker = response
+            .hover_pos()
+            .and_then(|pos| self.marker_at(self.canvas.screen_to_canvas_pos(pos, canvas_rect)));
+        let mut validation_status = vec![None; self.markers.len()];
+        for result in self.validation_results().unwrap_or_default() {
+            if let Some(index) = result.marker {
+                validation_status[index] = Some(result.passed());
+            }
+        }
+
+        for (i, marker) in self.markers.iter().enumerate() {
+            if !self.groups.is_visible(marker.group) {
+                continue;
+            }
+
+            let line_pos = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
+            let line_stroke = Stroke::new(1.0, marker_color(i, marker));
+            match marker.kind {
+                MarkerKind::ColumnLine => painter.vline(line_pos.x, border_rect.y_range(), line_stroke),
+                MarkerKind::RowLine => painter.hline(border_rect.x_range(), line_pos.y, line_stroke),
+                MarkerKind::Point => {}
+            }
+
+            let screen_pos = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
+            if let Some(passed) = validation_status[i] {
+                painter.circle_stroke(screen_pos, 7.0, Stroke::new(2.0, validation_color(passed)));
+            }
+
+            if use_mesh && hovered_marker != Some(i) && !self.ui_state.selected_markers.contains(&i) {
+                continue;
+            }
+
+            if self.ui_state.show_marker_dots && !use_mesh {
+                painter.circle_filled(screen_pos, 5.0, marker_color(i, marker));
+            }
+
+            if self.ui_state.selected_markers.contains(&i) {
+                painter.circle_stroke(screen_pos, 9.0, Stroke::new(2.0, Color32::from_rgb(255, 200, 0)));
+            }
+
+            if self.ui_state.show_marker_labels {
+                let label_pos = screen_pos + egui::vec2(10.0, 0.0);
+                let mut text_color = if self.ui_state.dark_mode {
+                    Color32::WHITE
+                } else {
+                    Color32::BLACK
+                };
+                if marker.ghost {
+                    text_color = text_color.gamma_multiply(0.3);
+                }
+                painter.text(
+                    label_pos,
+                    egui::Align2::LEFT_CENTER,
+                    {
+                        let coordinates = self.position_text(marker.system_position);
+                        match marker.label_text() {
+                            Some(label) => format!("{} {}", label, coordinates),
+                            None => coordinates,
+                        }
+                    },
+                    egui::FontId::default(),
+                    text_color,
+                );
+            }
+        }
+
+        // Preview of "keep numeric values" while the migration dialog is open
+        if let Some(pending) = &self.ui_state.pending_origin_change {
+            let preview_stroke = Stroke::new(1.0, Color32::from_rgb(255, 200, 0));
+            let moved_positions =
+                numeric_migration_positions(&self.markers, &self.coordinate_system, pending.origin_mode);
+            for (marker, position) in self.markers.iter().zip(moved_positions) {
+                let from = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
+                let to = self.canvas.canvas_to_screen_pos(position, canvas_rect);
+                painter.circle_stroke(to, 5.0, preview_stroke);
+                painter.extend(egui::Shape::dashed_line(&[from, to], preview_stroke, 4.0, 4.0));
+            }
+        }
+
+        if self.ui_state.lasso_path.len() >= 2 {
+            let mut lasso_screen: Vec<egui::Pos2> = self
+                .ui_state
+                .lasso_path
+                .iter()
+                .map(|pos| self.canvas.canvas_to_screen_pos(*pos, canvas_rect))
+                .collect();
+            lasso_screen.push(lasso_screen[0]);
+            let lasso_color = if self.ui_state.dark_mode {
+                Color32::WHITE
+            } else {
+                Color32::BLACK
+            };
+            painter.extend(egui::Shape::dashed_line(
+                &lasso_screen,
+                Stroke::new(1.0, lasso_color),
+                6.0,
+                4.0,
+            ));
+        }
+
+        if let Some((start, end)) = self.ui_state.selection_rect {
+            let selection_rect = egui::Rect::from_two_pos(
+                self.canvas.canvas_to_screen_pos(start, canvas_rect),
+                self.canvas.canvas_to_screen_pos(end, canvas_rect),
+            );
+            let selection_color = Color32::from_rgb(255, 200, 0);
+            painter.rect_filled(selection_rect, 0.0, selection_color.gamma_multiply(0.1));
+            let corners = [
+                selection_rect.left_top(),
+                selection_rect.right_top(),
+                selection_rect.right_bottom(),
+                selection_rect.left_bottom(),
+                selection_rect.left_top(),
+            ];
+            painter.extend(egui::Shape::dashed_line(&corners, Stroke::new(1.0, selection_color), 6.0, 4.0));
+        }
+
+        if let Some((start, end)) = self.ui_state.marquee {
+            let marquee_rect = egui::Rect::from_two_pos(
+                self.canvas.canvas_to_screen_pos(start, canvas_rect),
+                self.canvas.canvas_to_screen_pos(end, canvas_rect),
+            );
+            let marquee_color = Color32::from_rgb(0, 120, 255);
+            painter.rect(
+                marquee_rect,
+                0.0,
+                marquee_color.gamma_multiply(0.1),
+                Stroke::new(1.0, marquee_color),
+            );
+        }
+
+        let annotation_stroke = Stroke::new(2.0, export::ANNOTATION_COLOR);
+        for annotation in &self.annotations {
+            let points = annotation
+                .flatten(annotation::CURVE_SEGMENTS)
+                .into_iter()
+                .map(|pos| self.canvas.canvas_to_screen_pos(pos, canvas_rect))
+                .collect();
+            painter.add(egui::Shape::line(points, annotation_stroke));
+        }
+
+        // Bezier points placed so far, as hollow squares joined by dashed lines
+        if self.ui_state.active_tool == Tool::Bezier && !self.ui_state.bezier_points.is_empty() {
+            let mut path: Vec<egui::Pos2> = self
+                .ui_state
+                .bezier_points
+                .iter()
+                .map(|&pos| self.canvas.canvas_to_screen_pos(pos, canvas_rect))
+                .collect();
+            for &point in &path {
+                let square = egui::Rect::from_center_size(point, egui::vec2(8.0, 8.0));
+                painter.rect_stroke(square, 0.0, annotation_stroke);
+            }
+            if let Some(mouse_pos) = response.hover_pos() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
+                path.push(self.canvas.canvas_to_screen_pos(self.apply_grid_snapping(canvas_pos), canvas_rect));
+            }
+            painter.extend(egui::Shape::dashed_line(&path, Stroke::new(1.0, export::ANNOTATION_COLOR), 4.0, 4.0));
+        }
+
+        if let Some(mouse_pos) = response.hover_pos() {
+            draw_crosshair(&painter, mouse_pos, &self.ui_state.crosshair, border_rect);
+
+            if self.ui_state.active_tool == Tool::Stamp {
+                if let Some(stamp) = self.active_stamp() {
+                    let anchor = self.apply_grid_snapping(self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect));
+                    for position in stamp.positions_at(anchor, self.ui_state.stamp_scale) {
+                        let screen_pos = self.canvas.canvas_to_screen_pos(position, canvas_rect);
+                        painter.circle_stroke(screen_pos, 5.0, Stroke::new(1.0, self.ui_state.marker_color));
+                    }
+                }
+            }
+
+            if self.is_snapping_active() {
+                let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
+                let dragged = self.ui_state.dragging_marker.map(|(index, _)| index);
+                let snapped_pos = self.apply_all_snapping(canvas_pos, dragged);
+                let snapped_screen_pos = self.canvas.canvas_to_screen_pos(snapped_pos, canvas_rect);
+
+                // Guides along the marker axes being snapped to
+                let border_rect = self.canvas.get_screen_rect(canvas_rect);
+                let guide_stroke = Stroke::new(1.0, Color32::from_rgb(60, 140, 255));
+                let (guide_x, guide_y) = self.marker_axis_guides(canvas_pos, dragged);
+                if guide_x.is_some() {
+                    painter.line_segment(
+                        [
+                            egui::pos2(snapped_screen_pos.x, border_rect.min.y),
+                            egui::pos2(snapped_screen_pos.x, border_rect.max.y),
+                        ],
+                        guide_stroke,
+                    );
+                }
+                if guide_y.is_some() {
+                    painter.line_segment(
+                        [
+                            egui::pos2(border_rect.min.x, snapped_screen_pos.y),
+                            egui::pos2(border_rect.max.x, snapped_screen_pos.y),
+                        ],
+                        guide_stroke,
+                    );
+                }
+
+                painter.circle_stroke(
+                    snapped_screen_pos,
+                    8.0,
+                    Stroke::new(1.5, Color32::from_rgb(0, 200, 0)),
+                );
+
+                if (snapped_screen_pos - mouse_pos).length() > 2.0 {
+                    painter.line_segment(
+                        [mouse_pos, snapped_screen_pos],
+                        Stroke::new(1.0, Color32::from_rgba_premultiplied(0, 200, 0, 150)),
+                    );
+                }
+
+                if self.ui_state.show_snap_scores && self.grid.snap_mode() == SnapMode::NearestMarker {
+                    for (marker_pos, score) in self.snap_candidates(canvas_pos) {
+                        let screen_pos = self.canvas.canvas_to_screen_pos(marker_pos, canvas_rect);
+                        painter.text(
+                            screen_pos - egui::vec2(0.0, 12.0),
+                            egui::Align2::CENTER_BOTTOM,
+                            format!("{:.2}", score),
+                            egui::FontId::monospace(11.0),
+                            Color32::from_rgb(0, 200, 0),
+                        );
+                    }
+                }
+            }
+        }
+
+        if self.ui_state.show_offscreen_indicators {
+            let indicators = self.offscreen_indicators(canvas_rect);
+            for &(i, tip, direction) in &indicators {
+                let side = direction.rot90() * 5.0;
+                let base = tip - direction * 10.0;
+                painter.add(egui::Shape::convex_polygon(
+                    vec![tip, base + side, base - side],
+                    marker_color(i, &self.markers[i]),
+                    Stroke::new(1.0, self.border_color()),
+                ));
+            }
+            let hovered = response.hover_pos().and_then(|pos| self.offscreen_indicator_at(pos, canvas_rect));
+            if let Some(i) = hovered {
+                let text = format!("Marker {}: {}", i + 1, self.format_position(self.markers[i].system_position));
+                egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("offscreen_indicator"), |ui| {
+                    ui.label(text);
+                    ui.weak("Click to center the view on it");
+                });
+            }
+        }
+
+        if let Some(current) = playback_marker {
+            // Pulsing ring on the marker of the current frame
+            let pulse = (ui.input(|i| i.time) * 6.0).sin() as f32;
+            let screen_pos = self.canvas.canvas_to_screen_pos(self.markers[current].position, canvas_rect);
+            painter.circle_stroke(screen_pos, 10.0 + 3.0 * pulse, Stroke::new(2.0, Color32::from_rgb(255, 200, 0)));
+        }
+
+        if let Some((_, range)) = colormap {
+            self.draw_colormap_legend(&painter, canvas_rect, range);
+        }
+
+        if self.ui_state.show_measurements {
+            self.draw_kept_measurements(&painter, canvas_rect);
+        }
+        if self.ui_state.active_tool == Tool::Measure {
+            self.draw_measurement(&painter, canvas_rect);
+        }
+        if self.ui_state.active_tool == Tool::Angle {
+            self.draw_angle(&painter, canvas_rect);
+        }
+
+        if self.ui_state.show_rulers {
+            let thickness = self.ui_state.ruler_thickness;
+            for ruler in Ruler::for_view(canvas_rect, thickness) {
+                ruler.draw(&painter, &self.canvas, canvas_rect, &self.coordinate_system);
+            }
+            if let Some(((start, end), label)) = self.ruler_span().zip(self.ruler_span_label()) {
+                let rulers = Ruler::for_view(canvas_rect, thickness);
+                if let Some(ruler) = rulers.iter().find(|ruler| ruler.axis == self.ui_state.ruler_measure_axis) {
+                    ruler.draw_span(&painter, &self.canvas, canvas_rect, &self.coordinate_system, (start, end), &label);
+                }
+            }
+            // Corner square where the two rulers meet
+            let corner = egui::Rect::from_min_size(canvas_rect.min, egui::vec2(thickness, thickness));
+            painter.rect_filled(corner, 0.0, Color32::from_black_alpha(170));
+        }
+
+        if self.ui_state.show_minimap {
+            self.draw_minimap(&painter, canvas_rect);
+        }
+
+        response
+    }
+
+    // Screen rect of the minimap: the whole canvas shrunk to fit 180x120,
+    // kept clear of the rulers
+    fn minimap_rect(&self, canvas_rect: egui::Rect) -> egui::Rect {
+        const MAX_SIZE: egui::Vec2 = egui::vec2(180.0, 120.0);
+        const MARGIN: f32 = 10.0;
+        let (width, height) = self.canvas.get_size();
+        let scale = (MAX_SIZE.x / width.max(1.0)).min(MAX_SIZE.y / height.max(1.0));
+        let mut area = canvas_rect.shrink(MARGIN);
+        if self.ui_state.show_rulers {
+            area.min += egui::vec2(self.ui_state.ruler_thickness, self.ui_state.ruler_thickness);
+        }
+        self.ui_state
+            .minimap_corner
+            .align()
+            .align_size_within_rect(egui::vec2(width, height) * scale, area)
+    }
+
+    // Canvas position under a point of the minimap
+    fn minimap_to_canvas(&self, minimap: egui::Rect, screen_pos: egui::Pos2) -> egui::Pos2 {
+        let (width, height) = self.canvas.get_size();
+        let t = (screen_pos - minimap.min) / minimap.size();
+        egui::pos2(t.x.clamp(0.0, 1.0) * width, t.y.clamp(0.0, 1.0) * height)
+    }
+
+    // Clicking the minimap pans the view there and dragging on it follows the
+    // pointer. Returns true when the minimap took the pointer.
+    fn handle_minimap(&mut self, ui: &Ui, response: &egui::Response, canvas_rect: egui::Rect) -> bool {
+        if !self.ui_state.show_minimap {
+            return false;
+        }
+        let minimap = self.minimap_rect(canvas_rect);
+        let pressed_on_minimap = match ui.input(|i| i.pointer.press_origin()) {
+            Some(pos) => minimap.contains(pos),
+            None => false,
+        };
+        let pointer = match response.interact_pointer_pos().or(response.hover_pos()) {
+            Some(pos) => pos,
+            None => return false,
+        };
+
+        if response.clicked() && minimap.contains(pointer) {
+            self.pan_to(self.minimap_to_canvas(minimap, pointer));
+            return true;
+        }
+        if response.dragged_by(egui::PointerButton::Primary) && pressed_on_minimap {
+            self.canvas_target_offset = None;
+            self.canvas
+                .set_view(self.minimap_to_canvas(minimap, pointer), self.canvas.get_zoom());
+            return true;
+        }
+        // Keep hover readouts and tools from acting through the minimap
+        minimap.contains(pointer) && !response.dragged()
+    }
+
+    // Overview of the whole canvas with marker dots and the visible area outlined
+    fn draw_minimap(&self, painter: &egui::Painter, canvas_rect: egui::Rect) {
+        let minimap = self.minimap_rect(canvas_rect);
+        let (width, height) = self.canvas.get_size();
+        let to_minimap = |canvas_pos: egui::Pos2| {
+            minimap.min + egui::vec2(canvas_pos.x / width.max(1.0), canvas_pos.y / height.max(1.0)) * minimap.size()
+        };
+
+        painter.rect_filled(minimap.expand(3.0), 3.0, Color32::from_black_alpha(170));
+        painter.rect_filled(minimap, 0.0, self.background_color());
+        painter.rect_stroke(minimap, 0.0, Stroke::new(1.0, self.border_color()));
+
+        for marker in &self.markers {
+            if self.groups.is_visible(marker.group) {
+                painter.circle_filled(to_minimap(marker.position), 1.5, marker.display_color());
+            }
+        }
+
+        let view = egui::Rect::from_min_max(
+            to_minimap(self.canvas.screen_to_canvas_pos(canvas_rect.min, canvas_rect)),
+            to_minimap(self.canvas.screen_to_canvas_pos(canvas_rect.max, canvas_rect)),
+        );
+        painter
+            .with_clip_rect(minimap.expand(1.0))
+            .rect_stroke(view, 0.0, Stroke::new(1.5, Color32::from_rgb(255, 200, 0)));
+    }
+
+    // Arrow tip and direction on the view edge for each visible marker that is
+    // panned out of view, pointing from the view center towards the marker
+    fn offscreen_indicators(&self, canvas_rect: egui::Rect) -> Vec<(usize, egui::Pos2, egui::Vec2)> {
+        let mut view = canvas_rect;
+        if self.ui_state.show_rulers {
+            view.min += egui::vec2(self.ui_state.ruler_thickness, self.ui_state.ruler_thickness);
+        }
+        let edge = view.shrink(6.0);
+        let center = view.center();
+        self.markers
+            .iter()
+            .enumerate()
+            .filter(|(_, marker)| self.groups.is_visible(marker.group))
+            .filter_map(|(i, marker)| {
+                let screen_pos = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
+                if view.contains(screen_pos) {
+                    return None;
+                }
+                let offset = screen_pos - center;
+                // Scale the offset down until it touches the nearer edge
+                let scale = (edge.width() / 2.0 / offset.x.abs()).min(edge.height() / 2.0 / offset.y.abs());
+                Some((i, center + offset * scale, offset.normalized()))
+            })
+            .collect()
+    }
+
+    fn offscreen_indicator_at(&self, screen_pos: egui::Pos2, canvas_rect: egui::Rect) -> Option<usize> {
+        const HIT_RADIUS: f32 = 10.0;
+        self.offscreen_indicators(canvas_rect)
+            .into_iter()
+            .find(|&(_, tip, direction)| (tip - direction * 5.0 - screen_pos).length() < HIT_RADIUS)
+            .map(|(i, _, _)| i)
+    }
+
+    // Gradient bar in the bottom-left corner with the mapped value range
+    fn draw_colormap_legend(&self, painter: &egui::Painter, canvas_rect: egui::Rect, range: (f32, f32)) {
+        const STEPS: usize = 32;
+        let inset = if self.ui_state.show_rulers {
+            self.ui_state.ruler_thickness + 10.0
+        } else {
+            10.0
+        };
+        let bar = egui::Rect::from_min_size(
+            egui::pos2(canvas_rect.min.x + inset, canvas_rect.max.y - 34.0),
+            egui::vec2(160.0, 10.0),
+        );
+        let step_width = bar.width() / STEPS as f32;
+        for step in 0..STEPS {
+            let t = step as f32 / (STEPS - 1) as f32;
+            let rect = egui::Rect::from_min_size(
+                bar.min + egui::vec2(step as f32 * step_width, 0.0),
+                egui::vec2(step_width + 0.5, bar.height()),
+            );
+            painter.rect_filled(rect, 0.0, self.ui_state.colormap.sample(t));
+        }
+        painter.rect_stroke(bar, 0.0, Stroke::new(1.0, self.border_color()));
+
+        let text_color = if self.ui_state.dark_mode {
+            Color32::WHITE
+        } else {
+            Color32::BLACK
+        };
+        let font = egui::FontId::proportional(11.0);
+        let labels = [
+            (bar.left_bottom(), egui::Align2::LEFT_TOP, format!("{:.0}", range.0)),
+            (bar.center_bottom(), egui::Align2::CENTER_TOP, self.ui_state.color_by.label().to_string()),
+            (bar.right_bottom(), egui::Align2::RIGHT_TOP, format!("{:.0}", range.1)),
+        ];
+        for (anchor, align, text) in labels {
+            painter.text(anchor + egui::vec2(0.0, 2.0), align, text, font.clone(), text_color);
+        }
+    }
 
-        for marker in &self.markers {
-            let screen_pos = self.canvas.canvas_to_screen_pos(marker.position, canvas_rect);
-            painter.circle_filled(screen_pos, 5.0, marker.color);
+    // Draw 1px grid lines and highlight the hovered pixel when zoomed in far enough
+    fn draw_pixel_grid(
+        &self,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        border_rect: egui::Rect,
+        hover_pos: Option<egui::Pos2>,
+    ) {
+        const MIN_PIXEL_GRID_ZOOM: f32 = 8.0;
 
-            let label_pos = screen_pos + egui::vec2(10.0, 0.0);
-            let text_color = if self.ui_state.dark_mode {
-                Color32::WHITE
-            } else {
-                Color32::BLACK
-            };
-            painter.text(
-                label_pos,
-                egui::Align2::LEFT_CENTER,
-                format!(
-                    "({}, {})",
-                    marker.system_position.x as i32,
-                    marker.system_position.y as i32
-                ),
-                egui::FontId::default(),
-                text_color,
-            );
+        if self.canvas.get_zoom() < MIN_PIXEL_GRID_ZOOM {
+            return;
         }
 
-        if let Some(mouse_pos) = response.hover_pos() {
-            let crosshair_color = Color32::from_rgb(255, 0, 0);
-            let crosshair_size = 10.0;
+        let (canvas_width, canvas_height) = self.canvas.get_size();
+        let visible_rect = border_rect.intersect(canvas_rect);
+        let top_left = self.canvas.screen_to_canvas_pos(visible_rect.min, canvas_rect);
+        let bottom_right = self.canvas.screen_to_canvas_pos(visible_rect.max, canvas_rect);
 
+        let line_color = if self.ui_state.dark_mode {
+            Color32::from_rgba_premultiplied(120, 120, 120, 25)
+        } else {
+            Color32::from_rgba_premultiplied(60, 60, 60, 25)
+        };
+        let stroke = Stroke::new(1.0, line_color);
+
+        let first_x = top_left.x.ceil().max(0.0) as i32;
+        let last_x = bottom_right.x.floor().min(canvas_width) as i32;
+        for x in first_x..=last_x {
+            let screen_x = self.canvas.canvas_to_screen_pos(egui::pos2(x as f32, 0.0), canvas_rect).x;
             painter.line_segment(
                 [
-                    egui::pos2(mouse_pos.x - crosshair_size, mouse_pos.y),
-                    egui::pos2(mouse_pos.x + crosshair_size, mouse_pos.y),
+                    egui::pos2(screen_x, visible_rect.min.y),
+                    egui::pos2(screen_x, visible_rect.max.y),
                 ],
-                Stroke::new(1.0, crosshair_color),
+                stroke,
             );
+        }
 
+        let first_y = top_left.y.ceil().max(0.0) as i32;
+        let last_y = bottom_right.y.floor().min(canvas_height) as i32;
+        for y in first_y..=last_y {
+            let screen_y = self.canvas.canvas_to_screen_pos(egui::pos2(0.0, y as f32), canvas_rect).y;
             painter.line_segment(
                 [
-                    egui::pos2(mouse_pos.x, mouse_pos.y - crosshair_size),
-                    egui::pos2(mouse_pos.x, mouse_pos.y + crosshair_size),
+                    egui::pos2(visible_rect.min.x, screen_y),
+                    egui::pos2(visible_rect.max.x, screen_y),
                 ],
-                Stroke::new(1.0, crosshair_color),
+                stroke,
             );
+        }
 
-            if self.grid.is_snapping_enabled() {
-                let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
-                let snapped_pos = self.apply_grid_snapping(canvas_pos);
-                let snapped_screen_pos = self.canvas.canvas_to_screen_pos(snapped_pos, canvas_rect);
+        if let Some(mouse_pos) = hover_pos {
+            let canvas_pos = self.canvas.screen_to_canvas_pos(mouse_pos, canvas_rect);
+            let pixel = canvas_pos.floor();
+            if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= canvas_width || pixel.y >= canvas_height {
+                return;
+            }
 
-                painter.circle_stroke(
-                    snapped_screen_pos,
-                    8.0,
-                    Stroke::new(1.5, Color32::from_rgb(0, 200, 0)),
-                );
+            let cell = egui::Rect::from_min_max(
+                self.canvas.canvas_to_screen_pos(pixel, canvas_rect),
+                self.canvas.canvas_to_screen_pos(pixel + egui::vec2(1.0, 1.0), canvas_rect),
+            );
+            painter.rect_filled(cell, 0.0, Color32::from_rgba_unmultiplied(255, 200, 0, 70));
 
-                if (snapped_screen_pos - mouse_pos).length() > 2.0 {
-                    painter.line_segment(
-                        [mouse_pos, snapped_screen_pos],
-                        Stroke::new(1.0, Color32::from_rgba_premultiplied(0, 200, 0, 150)),
-                    );
-                }
+            if self.ui_state.pixel_grid.show_pixel_index {
+                // Pixel rows count upwards when Y grows upwards
+                let index_y = if self.coordinate_system.origin_mode().y_up() {
+                    canvas_height - 1.0 - pixel.y
+                } else {
+                    pixel.y
+                };
+                let text_color = if self.ui_state.dark_mode {
+                    Color32::WHITE
+                } else {
+                    Color32::BLACK
+                };
+                painter.text(
+                    cell.right_bottom() + egui::vec2(4.0, 4.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("[{}, {}]", pixel.x as i32, index_y as i32),
+                    egui::FontId::monospace(11.0),
+                    text_color,
+                );
             }
         }
-
-        response
     }
 
     // Draw the grid on the canvas
     fn draw_grid(&self, painter: &egui::Painter, canvas_rect: egui::Rect, border_rect: egui::Rect) {
-        let grid_size = self.grid.get_size() * self.canvas.get_zoom();
-        if grid_size < 5.0 {
-            return;
-        }
-
-        let grid_color = if self.ui_state.dark_mode {
-            Color32::from_rgba_premultiplied(180, 180, 180, 60)
+        let zoom = self.canvas.get_zoom();
+        let grid_color = self.grid_color().gamma_multiply(self.grid_visibility_alpha);
+        let minor_color = grid_color.gamma_multiply(0.5);
+        let label_color = if self.ui_state.dark_mode {
+            Color32::from_gray(200)
         } else {
-            Color32::from_rgba_premultiplied(80, 80, 80, 80)
-        };
+            Color32::from_gray(60)
+        }
+        .gamma_multiply(self.grid_visibility_alpha);
+        let label_font = egui::FontId::proportional(10.0);
 
         let (canvas_width, canvas_height) = self.canvas.get_size();
         let origin_screen_pos = self.canvas.canvas_to_screen_pos(egui::pos2(0.0, 0.0), canvas_rect);
+        let to_screen = |pos: egui::Pos2| self.canvas.canvas_to_screen_pos(pos, canvas_rect);
 
-        let cells_left = (origin_screen_pos.x - border_rect.min.x) / grid_size;
-        let cells_right = (border_rect.max.x - origin_screen_pos.x) / grid_size;
-        let cells_up = (origin_screen_pos.y - border_rect.min.y) / grid_size;
-        let cells_down = (border_rect.max.y - origin_screen_pos.y) / grid_size;
-
-        let left_count = cells_left.ceil() as i32 + 2;
-        let right_count = cells_right.ceil() as i32 + 2;
-        let up_count = cells_up.ceil() as i32 + 2;
-        let down_count = cells_down.ceil() as i32 + 2;
-
-        // Draw vertical grid lines
-        for i in -left_count..=right_count {
-            let canvas_x = (i as f32) * self.grid.get_size();
-            let screen_x = self.canvas.canvas_to_screen_pos(egui::pos2(canvas_x, 0.0), canvas_rect).x;
-
-            if screen_x >= border_rect.min.x && screen_x <= border_rect.max.x {
-                painter.line_segment(
-                    [
-                        egui::pos2(screen_x, border_rect.min.y),
-                        egui::pos2(screen_x, border_rect.max.y),
-                    ],
-                    Stroke::new(1.0, grid_color),
-                );
+        // Lines of a rotated grid run past the view, so clip them to it
+        let clipped = painter.with_clip_rect(border_rect.intersect(painter.clip_rect()));
+        let area = egui::Rect::from_two_pos(
+            self.canvas.screen_to_canvas_pos(border_rect.min, canvas_rect),
+            self.canvas.screen_to_canvas_pos(border_rect.max, canvas_rect),
+        );
+        let (min_spacing, max_spacing) = (self.ui_state.grid_min_spacing, self.ui_state.grid_max_spacing);
+        let lines = self.grid.lines(area, zoom, min_spacing, max_spacing);
+        match self.grid.style() {
+            GridStyle::Lines | GridStyle::Dashed => {
+                for line in &lines {
+                    let stroke = Stroke::new(1.0, if line.major { grid_color } else { minor_color });
+                    let points = [to_screen(line.from), to_screen(line.to)];
+                    if self.grid.style() == GridStyle::Dashed {
+                        clipped.extend(egui::Shape::dashed_line(&points, stroke, 4.0, 4.0));
+                    } else {
+                        clipped.line_segment(points, stroke);
+                    }
+                }
+            }
+            GridStyle::Dots => {
+                for (pos, major) in self.grid.dots(area, zoom, min_spacing, max_spacing) {
+                    let (radius, color) = if major { (1.5, grid_color) } else { (1.0, minor_color) };
+                    clipped.circle_filled(to_screen(pos), radius, color);
+                }
             }
         }
 
-        // Draw horizontal grid lines
-        for i in -up_count..=down_count {
-            let canvas_y = (i as f32) * self.grid.get_size();
-            let screen_y = self.canvas.canvas_to_screen_pos(egui::pos2(0.0, canvas_y), canvas_rect).y;
-
-            if screen_y >= border_rect.min.y && screen_y <= border_rect.max.y {
-                painter.line_segment(
-                    [
-                        egui::pos2(border_rect.min.x, screen_y),
-                        egui::pos2(border_rect.max.x, screen_y),
-                    ],
-                    Stroke::new(1.0, grid_color),
-                );
+        // Labels sit just inside the top and left canvas edges, or the view's when those are scrolled away.
+        // They only make sense while the grid lines up with the canvas axes.
+        let label_top = origin_screen_pos.y.max(border_rect.min.y) + 2.0;
+        let label_left = origin_screen_pos.x.max(border_rect.min.x) + 2.0;
+        if self.grid.rotation() == 0.0 {
+            for line in lines.iter().filter(|line| line.major && line.offset > 0.0) {
+                if line.vertical && line.offset <= canvas_width {
+                    let screen_x = to_screen(line.from).x;
+                    if screen_x < border_rect.min.x || screen_x > border_rect.max.x {
+                        continue;
+                    }
+                    let system_x = self.coordinate_system.to_system_coordinates(egui::pos2(line.offset, 0.0)).x;
+                    painter.text(
+                        egui::pos2(screen_x + 2.0, label_top),
+                        egui::Align2::LEFT_TOP,
+                        coordinate::format_pixel(system_x),
+                        label_font.clone(),
+                        label_color,
+                    );
+                } else if !line.vertical && line.offset <= canvas_height {
+                    let screen_y = to_screen(line.from).y;
+                    if screen_y < border_rect.min.y || screen_y > border_rect.max.y {
+                        continue;
+                    }
+                    let system_y = self.coordinate_system.to_system_coordinates(egui::pos2(0.0, line.offset)).y;
+                    painter.text(
+                        egui::pos2(label_left, screen_y - 1.0),
+                        egui::Align2::LEFT_BOTTOM,
+                        coordinate::format_pixel(system_y),
+                        label_font.clone(),
+                        label_color,
+                    );
+                }
             }
         }
 
@@ -334,7 +5901,8 @@ impl CoordinatePickerApp {
             Color32::from_rgba_premultiplied(200, 200, 200, 100)
         } else {
             Color32::from_rgba_premultiplied(100, 100, 100, 100)
-        };
+        }
+        .gamma_multiply(self.grid_visibility_alpha);
 
         // Draw canvas edges
         let left_edge_x = self.canvas.canvas_to_screen_pos(egui::pos2(0.0, 0.0), canvas_rect).x;
@@ -382,11 +5950,7 @@ impl CoordinatePickerApp {
         }
 
         // Draw origin point
-        let origin_canvas_pos = if self.coordinate_system.is_origin_top_left() {
-            egui::pos2(0.0, 0.0)
-        } else {
-            egui::pos2(0.0, self.canvas.get_height())
-        };
+        let origin_canvas_pos = self.coordinate_system.origin_canvas_pos();
         let origin = self.canvas.canvas_to_screen_pos(origin_canvas_pos, canvas_rect);
         if canvas_rect.contains(origin) {
             painter.circle_filled(origin, 5.0, Color32::RED);
@@ -395,10 +5959,10 @@ impl CoordinatePickerApp {
             } else {
                 Color32::BLACK
             };
-            let text_offset = if self.coordinate_system.is_origin_top_left() {
-                egui::vec2(10.0, -10.0)
-            } else {
+            let text_offset = if self.coordinate_system.origin_mode().y_up() {
                 egui::vec2(10.0, 10.0)
+            } else {
+                egui::vec2(10.0, -10.0)
             };
             painter.text(
                 origin + text_offset,
@@ -407,13 +5971,40 @@ impl CoordinatePickerApp {
                 egui::FontId::default(),
                 text_color,
             );
+
+            if self.ui_state.dragging_origin {
+                // Absolute canvas position so the origin can be parked precisely
+                painter.text(
+                    origin - text_offset,
+                    egui::Align2::LEFT_TOP,
+                    format!("canvas ({}, {})", origin_canvas_pos.x, origin_canvas_pos.y),
+                    egui::FontId::monospace(12.0),
+                    text_color,
+                );
+            }
         }
     }
 }
 
 // Implement the main update loop for the app
 impl eframe::App for CoordinatePickerApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        self.update_window_title(frame);
+
+        self.canvas.update_transition(ctx.input(|i| i.time));
+        self.canvas.tick_zoom(ctx.input(|i| i.stable_dt));
+        self.tick_pan(ctx);
+        self.advance_playback(ctx.input(|i| i.time));
+
+        // Fade the grid over ~300ms when it's toggled
+
+        let dt = ctx.input(|i| i.stable_dt);
+        self.grid_visibility_alpha = if self.grid.is_visible() {
+            (self.grid_visibility_alpha + dt * 3.33).min(1.0)
+        } else {
+            (self.grid_visibility_alpha - dt * 3.33).max(0.0)
+        };
+
         let mut style = (*ctx.style()).clone();
         if self.ui_state.dark_mode {
             style.visuals = egui::Visuals::dark();
@@ -421,256 +6012,379 @@ impl eframe::App for CoordinatePickerApp {
             style.visuals = egui::Visuals::light();
         }
         ctx.set_style(style);
+        self.refresh_background_texture();
+        self.poll_region_analysis(ctx);
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Export Markers…").clicked() {
+                        ui.close_menu();
+                        self.save_project_json();
+                    }
+                    if ui.button("Import Markers…").clicked() {
+                        ui.close_menu();
+                        self.import_project_json();
+                    }
+                    ui.separator();
+                    self.recent_files_menu(ui, ctx);
+                });
                 ui.heading("Coordinate Picker");
                 ui.separator();
                 if ui.button("Reset View").clicked() {
+                    self.canvas_target_offset = None;
                     self.canvas.reset_view();
                 }
+                if ui.button("Reset Zoom").clicked() {
+                    self.canvas_target_offset = None;
+                    self.canvas.set_zoom(canvas::DEFAULT_ZOOM);
+                }
+                if ui.button("Reset Pan").clicked() {
+                    self.canvas_target_offset = None;
+                    self.canvas.set_offset(egui::Vec2::ZERO);
+                }
+                if ui
+                    .add_enabled(self.undo_stack.can_undo(), egui::Button::new("⟲ Undo"))
+                    .on_hover_text("Undo the last marker change (Ctrl+Z)")
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(self.undo_stack.can_redo(), egui::Button::new("⟳ Redo"))
+                    .on_hover_text("Redo (Ctrl+Y)")
+                    .clicked()
+                {
+                    self.redo();
+                }
                 if ui.button("Clear Markers").clicked() {
-                    self.markers.clear();
+                    self.clear_markers();
+                }
+                if !self.annotations.is_empty() && ui.button("Clear Annotations").clicked() {
+                    self.set_annotations(Vec::new());
+                }
+                if ui.button("Open Image…").on_hover_text("Show a PNG, JPEG or BMP under the canvas").clicked() {
+                    self.open_background_image(ctx);
+                }
+                if self.background_image.is_some() && ui.button("Remove Image").clicked() {
+                    self.background_image = None;
+                }
+                if let Some(error) = &self.ui_state.background_error {
+                    ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+                }
+                if ui
+                    .button("Copy View")
+                    .on_hover_text("Copy the visible canvas as an image (Ctrl+Shift+C)")
+                    .clicked()
+                {
+                    self.copy_view_as_image(ctx);
+                }
+                if ui
+                    .button("Share Summary")
+                    .on_hover_text("Copy the canvas size and all coordinates as readable text")
+                    .clicked()
+                {
+                    let summary = self.share_summary_text();
+                    self.copy_to_clipboard(summary.clone());
+                    self.ui_state.share_summary = Some(summary);
+                }
+                ui.separator();
+                ui.label("Tool:");
+                for tool in Tool::ALL {
+                    ui.selectable_value(&mut self.ui_state.active_tool, tool, tool.label());
+                }
+                ui.separator();
+                let has_selection = !self.ui_state.selected_markers.is_empty();
+                ui.add_enabled_ui(has_selection, |ui| {
+                    if ui.button("Copy Selected").clicked() {
+                        self.copy_selected_markers();
+                    }
+                    if ui.button("Delete Selected").clicked() {
+                        self.delete_selected_markers();
+                    }
+                    ui.toggle_value(&mut self.ui_state.move_selection, "Move Selected")
+                        .on_hover_text("Drag on the canvas to move the selected markers together");
+                })
+                .response
+                .on_disabled_hover_text("Shift+drag on the canvas to select markers");
+                if self.ui_state.active_tool == Tool::Place {
+                    egui::ComboBox::from_id_source("place_kind")
+                        .selected_text(self.ui_state.place_kind.label())
+                        .show_ui(ui, |ui| {
+                            for kind in MarkerKind::ALL {
+                                ui.selectable_value(&mut self.ui_state.place_kind, kind, kind.label());
+                            }
+                        });
                 }
                 ui.separator();
                 ui.label("Zoom:");
                 let zoom_percentage = (self.canvas.get_zoom() * 100.0) as i32;
                 ui.label(format!("{}%", zoom_percentage));
+                self.output_transform_badge(ui);
+                if let Some(span) = self.ruler_span_label() {
+                    ui.separator();
+                    ui.label(span);
+                }
             });
         });
 
-        egui::SidePanel::right("settings_panel")
-            .resizable(true)
-            .default_width(250.0)
-            .show(ctx, |ui| {
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    ui.heading("Settings");
-                    ui.separator();
-
-                    ui.collapsing("Canvas Size", |ui| {
-                        egui::ComboBox::from_label("Resolution")
-                            .selected_text(&self.ui_state.selected_resolution)
-                            .show_ui(ui, |ui| {
-                                for preset in self.resolution_presets.keys() {
-                                    ui.selectable_value(
-                                        &mut self.ui_state.selected_resolution,
-                                        preset.clone(),
-                                        preset,
-                                    );
-                                }
-                            });
+        self.show_outside_canvas_banner(ctx);
 
-                        if self.ui_state.selected_resolution == "Custom" {
-                            ui.horizontal(|ui| {
-                                ui.label("Width:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.ui_state.custom_width)
-                                        .speed(1.0)
-                                        .clamp_range(100.0..=10000.0),
-                                );
-                            });
-                            ui.horizontal(|ui| {
-                                ui.label("Height:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.ui_state.custom_height)
-                                        .speed(1.0)
-                                        .clamp_range(100.0..=10000.0),
-                                );
-                            });
-                        }
+        if self.ui_state.compact_mode {
+            self.show_compact_panel(ctx, frame);
+        } else {
+            self.show_settings_panel(ctx, frame);
+        }
 
-                        self.update_canvas_resolution();
-                    });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let response = self.draw_canvas(ui);
+            self.ui_state.view_rect = response.rect;
+            self.handle_canvas_interactions(ui, response);
+        });
 
-                    ui.collapsing("Grid", |ui| {
-                        let grid_visible_changed = ui
-                            .checkbox(&mut self.ui_state.show_grid, "Show Grid")
-                            .changed();
+        self.show_command_entry(ctx);
+        self.show_marker_editor(ctx);
+        self.show_origin_migration_dialog(ctx);
+        self.show_quantize_dialog(ctx);
+        self.show_validation_window(ctx);
+        self.show_share_summary(ctx);
+        self.show_import_errors(ctx);
+        self.show_csv_dialog(ctx);
+        self.draw_toast(ctx);
+        self.save_settings_if_changed();
+        self.handle_dropped_files(ctx);
 
-                        let mut grid_size_changed = false;
-                        ui.horizontal(|ui| {
-                            ui.label("Grid Size:");
-                            grid_size_changed = ui
-                                .add(
-                                    egui::DragValue::new(&mut self.ui_state.grid_size)
-                                        .speed(1.0)
-                                        .clamp_range(5.0..=100.0),
-                                )
-                                .changed();
-                        });
+        // Handled after drawing so a key that opens a popup isn't typed into it.
+        // While the cheat sheet is up, keys only dismiss it.
+        if self.ui_state.show_shortcuts_overlay {
+            self.show_shortcuts_overlay(ctx);
+        } else {
+            self.handle_shortcuts(ctx);
+        }
 
-                        let grid_snap_changed = ui
-                            .checkbox(&mut self.ui_state.enable_snapping, "Snap to Grid")
-                            .changed();
+        ctx.request_repaint();
+    }
+}
 
-                        if grid_visible_changed || grid_size_changed || grid_snap_changed {
-                            self.grid.set_size(self.ui_state.grid_size);
-                            self.grid.set_visible(self.ui_state.show_grid);
-                            self.grid.set_snapping(self.ui_state.enable_snapping);
-                        }
-                    });
+// Clipped to the canvas border, so the lines stop at the canvas edge
+fn draw_crosshair(painter: &egui::Painter, pos: egui::Pos2, settings: &CrosshairSettings, border_rect: egui::Rect) {
+    let painter = painter.with_clip_rect(border_rect.intersect(painter.clip_rect()));
+    let stroke = Stroke::new(1.0, settings.color);
+    let size = settings.size;
+    let left = egui::pos2(pos.x - size, pos.y);
+    let right = egui::pos2(pos.x + size, pos.y);
+    let top = egui::pos2(pos.x, pos.y - size);
+    let bottom = egui::pos2(pos.x, pos.y + size);
 
-                    ui.collapsing("Coordinate System", |ui| {
-                        let changed1 = ui
-                            .radio_value(
-                                &mut self.ui_state.origin_top_left,
-                                true,
-                                "Origin at Top-Left (0,0)",
-                            )
-                            .changed();
-                        let changed2 = ui
-                            .radio_value(
-                                &mut self.ui_state.origin_top_left,
-                                false,
-                                "Origin at Bottom-Left (0,0)",
-                            )
-                            .changed();
-                            
-                        ui.separator();
-                        ui.checkbox(
-                            &mut self.ui_state.recalculate_markers,
-                            "Recalculate markers on origin change",
-                        );
+    let segments: &[[egui::Pos2; 2]] = match settings.shape {
+        CrosshairShape::Cross => &[[left, right], [top, bottom]],
+        CrosshairShape::Horizontal => &[[left, right]],
+        CrosshairShape::Vertical => &[[top, bottom]],
+        CrosshairShape::TShape => &[[left, right], [pos, bottom]],
+        CrosshairShape::LShape => &[[pos, right], [pos, bottom]],
+        CrosshairShape::DotOnly => {
+            painter.circle_filled(pos, 2.5, settings.color);
+            &[]
+        }
+    };
+    for segment in segments {
+        painter.line_segment(*segment, stroke);
+    }
+}
 
-                        if changed1 || changed2 {
-                            let old_origin_top_left = self.coordinate_system.is_origin_top_left();
-                            self.coordinate_system
-                                .set_origin_top_left(self.ui_state.origin_top_left);
-                            
-                            if self.ui_state.recalculate_markers && old_origin_top_left != self.ui_state.origin_top_left {
-                                // Recalculate all marker positions
-                                for marker in &mut self.markers {
-                                    // Convert back to canvas coordinates using old system
-                                    let canvas_pos = if old_origin_top_left {
-                                        marker.system_position
-                                    } else {
-                                        egui::pos2(marker.system_position.x, self.canvas.get_height() - marker.system_position.y)
-                                    };
-                                    
-                                    // Convert to new system coordinates
-                                    marker.system_position = self.coordinate_system.to_system_coordinates(canvas_pos);
-                                }
-                            }
-                        }
-                    });
+// Loaded background image. `pixels` are kept so the texture can be
+// re-blended when `key` no longer matches the current settings.
+struct BackgroundImage {
+    pixels: image::RgbaImage,
+    texture: egui::TextureHandle,
+    key: BackgroundKey,
+    average_luminance: f32, // Sampled once on load for the auto-contrast grid
+}
 
-                    ui.collapsing("Markers", |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Marker Color:");
-                            egui::color_picker::color_edit_button_srgba(
-                                ui,
-                                &mut self.ui_state.marker_color,
-                                egui::color_picker::Alpha::Opaque,
-                            );
-                        });
-                    });
+// Everything the blended background texture depends on
+#[derive(Clone, Copy, PartialEq)]
+struct BackgroundKey {
+    mode: CompositeMode,
+    base: Color32, // Canvas color behind the image
+    gamma: f32,
+}
 
-                    ui.separator();
+// Everything the fast-path marker mesh depends on
+#[derive(PartialEq)]
+struct MarkerMeshKey {
+    generation: u64,
+    origin: egui::Pos2, // Screen position of the canvas's top-left corner
+    zoom: f32,
+    color_by: ColorBy,
+    colormap: Colormap,
+    playback_marker: Option<usize>, // Every other marker is dimmed during playback
+    hidden_groups: Vec<u32>,
+}
 
-                    ui.heading("Current Position");
-                    ui.horizontal(|ui| {
-                        let x = self.ui_state.current_position.x as i32;
-                        let y = self.ui_state.current_position.y as i32;
-                        let coords_text = format!("({}, {})", x, y);
-                        ui.label(coords_text.clone());
-                        if ui.button("Copy").clicked() {
-                            self.copy_to_clipboard(coords_text);
-                        }
-                    });
+// Collapsing header whose open state is kept by the caller, so it can be saved with the
+// settings. Returns true when the header was clicked.
+fn collapsing_section(ui: &mut Ui, title: &str, open: bool, add_contents: impl FnOnce(&mut Ui)) -> bool {
+    egui::CollapsingHeader::new(title)
+        .open(Some(open))
+        .show(ui, add_contents)
+        .header_response
+        .clicked()
+}
 
-                    if self.grid.is_snapping_enabled() {
-                        ui.label("Snapping enabled");
-                    } else {
-                        let x = self.ui_state.current_position_raw.x as f32;
-                        let y = self.ui_state.current_position_raw.y as f32;
-                        ui.label(format!("Raw: ({:.1}, {:.1})", x, y));
-                    }
+fn validation_color(passed: bool) -> Color32 {
+    if passed {
+        Color32::from_rgb(60, 170, 60)
+    } else {
+        Color32::from_rgb(210, 50, 50)
+    }
+}
 
-                    ui.separator();
+// Canvas positions the markers would move to if they kept their numeric
+// values under the given origin mode
+fn numeric_migration_positions(
+    markers: &[Marker],
+    system: &CoordinateSystem,
+    origin_mode: OriginMode,
+) -> Vec<egui::Pos2> {
+    let mut target_system = system.clone();
+    target_system.set_origin_mode(origin_mode);
+    markers
+        .iter()
+        .map(|marker| target_system.from_system_coordinates(marker.system_position))
+        .collect()
+}
 
-                    ui.heading("Saved Markers");
+// Switches `system` to a new origin and updates the markers to match
+fn migrate_markers(
+    markers: &mut [Marker],
+    system: &mut CoordinateSystem,
+    origin_mode: OriginMode,
+    migration: OriginMigration,
+) {
+    let moved_positions = numeric_migration_positions(markers, system, origin_mode);
+    system.set_origin_mode(origin_mode);
+    match migration {
+        OriginMigration::KeepVisual => {
+            for marker in markers.iter_mut() {
+                marker.system_position = system.to_system_coordinates(marker.position);
+            }
+        }
+        OriginMigration::KeepNumeric => {
+            for (marker, position) in markers.iter_mut().zip(moved_positions) {
+                marker.position = position;
+            }
+        }
+    }
+}
 
-                    let mut marker_to_remove: Option<usize> = None;
+// Keep per-marker index sets in step with the marker list after a removal
+fn shift_indices_after_removal(indices: &mut HashSet<usize>, removed: usize) {
+    *indices = indices
+        .iter()
+        .filter(|&&i| i != removed)
+        .map(|&i| if i > removed { i - 1 } else { i })
+        .collect();
+}
 
-                    if !self.markers.is_empty() {
-                        if ui.button("Copy All Coordinates").clicked() {
-                            let all_coords = self
-                                .markers
-                                .iter()
-                                .enumerate()
-                                .map(|(i, marker)| {
-                                    let x = marker.system_position.x as i32;
-                                    let y = marker.system_position.y as i32;
-                                    format!("{}. ({}, {})", i + 1, x, y)
-                                })
-                                .collect::<Vec<String>>()
-                                .join("\n");
+const BACKGROUND_IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
 
-                            self.copy_to_clipboard(all_coords);
-                        }
-                    }
+// One button per file, returning the clicked path. Files that have since
+// been moved or deleted stay listed but can't be clicked.
+fn recent_file_entries(ui: &mut Ui, paths: &[PathBuf]) -> Option<PathBuf> {
+    if paths.is_empty() {
+        ui.label("No recent files");
+        return None;
+    }
+    let mut clicked = None;
+    for path in paths {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => path.display().to_string(),
+        };
+        let response = ui
+            .add_enabled(path.exists(), egui::Button::new(name))
+            .on_hover_text(path.display().to_string())
+            .on_disabled_hover_text(format!("{} no longer exists", path.display()));
+        if response.clicked() {
+            clicked = Some(path.clone());
+        }
+    }
+    clicked
+}
 
-                    egui::ScrollArea::vertical()
-                        .max_height(200.0)
-                        .show(ui, |ui| {
-                            let markers_data: Vec<(usize, i32, i32, String)> = self
-                                .markers
-                                .iter()
-                                .enumerate()
-                                .map(|(i, marker)| {
-                                    let x = marker.system_position.x as i32;
-                                    let y = marker.system_position.y as i32;
-                                    let coords = format!("{}, {}", x, y);
-                                    (i, x, y, coords)
-                                })
-                                .collect();
+fn is_background_image_path(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => BACKGROUND_IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
 
-                            for (i, x, y, coords) in markers_data {
-                                let marker_text = format!("{}. ({}, {})", i + 1, x, y);
-                                ui.horizontal(|ui| {
-                                    ui.label(marker_text);
+fn shift_indices_after_insertion(indices: &mut HashSet<usize>, inserted: usize) {
+    *indices = indices
+        .iter()
+        .map(|&i| if i >= inserted { i + 1 } else { i })
+        .collect();
+}
 
-                                    if ui.button("Copy").clicked() {
-                                        self.copy_to_clipboard(coords.clone());
-                                    }
+fn copy_image_to_clipboard(image: &image::RgbaImage) -> Result<(), arboard::Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    clipboard.set_image(arboard::ImageData {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        bytes: std::borrow::Cow::Borrowed(image.as_raw()),
+    })
+}
 
-                                    if ui.button("Delete").clicked() {
-                                        marker_to_remove = Some(i);
-                                    }
-                                });
-                            }
-                        });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    if let Some(index) = marker_to_remove {
-                        if index < self.markers.len() {
-                            self.markers.remove(index);
-                        }
-                    }
+    // An 800x600 canvas, so a flip about 1080 would give the wrong answer
+    fn system(origin_mode: OriginMode) -> CoordinateSystem {
+        let mut system = CoordinateSystem::new(origin_mode);
+        system.update_canvas_size(800.0, 600.0);
+        system
+    }
 
-                    ui.separator();
+    fn marker_at(system: &CoordinateSystem, position: egui::Pos2) -> Marker {
+        Marker::new(position, system.to_system_coordinates(position), Color32::WHITE, 0)
+    }
 
-                    ui.collapsing("Appearance", |ui| {
-                        ui.checkbox(&mut self.ui_state.dark_mode, "Dark Mode");
-                    });
+    #[test]
+    fn keep_visual_leaves_positions_and_renumbers() {
+        let mut system = system(OriginMode::TopLeft);
+        let mut markers = vec![marker_at(&system, egui::pos2(100.0, 150.0))];
+        migrate_markers(&mut markers, &mut system, OriginMode::BottomLeft, OriginMigration::KeepVisual);
+        assert_eq!(markers[0].position, egui::pos2(100.0, 150.0));
+        assert_eq!(markers[0].system_position, egui::pos2(100.0, 450.0));
+    }
 
-                    ui.collapsing("Help", |ui| {
-                        ui.label("• Click to place a marker");
-                        ui.label("• Right-click to remove a marker at cursor position");
-                        ui.label("• Use 'Delete' button to remove specific markers from the list");
-                        ui.label("• Use 'Copy All Coordinates' to copy all marker coordinates at once");
-                        ui.label("• Middle-click or Alt+drag to pan");
-                        ui.label("• Scroll to zoom in/out");
-                        ui.label("• Adjust grid settings for precise positioning");
-                        ui.label("• Grid snapping finds the nearest grid intersection to your cursor");
-                    });
-                });
-            });
+    #[test]
+    fn keep_numeric_keeps_values_and_moves_points() {
+        let mut system = system(OriginMode::TopLeft);
+        let mut markers = vec![marker_at(&system, egui::pos2(100.0, 150.0))];
+        migrate_markers(&mut markers, &mut system, OriginMode::BottomLeft, OriginMigration::KeepNumeric);
+        assert_eq!(markers[0].position, egui::pos2(100.0, 450.0));
+        assert_eq!(markers[0].system_position, egui::pos2(100.0, 150.0));
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let response = self.draw_canvas(ui);
-            self.handle_canvas_interactions(ui, response);
-        });
+    #[test]
+    fn numeric_migration_to_center_origin() {
+        let system = system(OriginMode::TopLeft);
+        let markers = vec![
+            marker_at(&system, egui::pos2(100.0, 150.0)),
+            marker_at(&system, egui::pos2(0.0, 0.0)),
+        ];
+        let positions = numeric_migration_positions(&markers, &system, OriginMode::Center);
+        assert_eq!(positions, vec![egui::pos2(500.0, 150.0), egui::pos2(400.0, 300.0)]);
+    }
 
-        ctx.request_repaint();
+    #[test]
+    fn numeric_migration_back_to_top_left() {
+        let system = system(OriginMode::BottomLeft);
+        let markers = vec![marker_at(&system, egui::pos2(100.0, 150.0))];
+        assert_eq!(markers[0].system_position, egui::pos2(100.0, 450.0));
+        let positions = numeric_migration_positions(&markers, &system, OriginMode::TopLeft);
+        assert_eq!(positions, vec![egui::pos2(100.0, 450.0)]);
     }
 }
@@ -1,11 +1,29 @@
 
 use egui::{Pos2, Vec2, Rect};
 
+const VIEW_TRANSITION_SECONDS: f64 = 0.3;
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 10.0;
+pub const DEFAULT_ZOOM: f32 = 0.5;
+// Smooth zoom covers ~98% of the way to a new target in three of these (150 ms)
+const ZOOM_SMOOTH_TIME: f32 = 0.05;
+
+// Animated move between two views, each a canvas-space center and zoom
+struct ViewTransition {
+    from: (Pos2, f32),
+    to: (Pos2, f32),
+    start_time: f64,
+}
+
 pub struct Canvas {
     width: f32,
     height: f32,
     offset: Vec2,
     zoom: f32,
+    zoom_target: f32,   // Where smooth zoom is heading; equals `zoom` when idle
+    zoom_velocity: f32, // Change of ln(zoom) per second, kept when the target moves mid-animation
+    zoom_anchor: Vec2,  // Screen offset from the view center that stays put while zooming
+    transition: Option<ViewTransition>,
 }
 
 impl Canvas {
@@ -14,7 +32,11 @@ impl Canvas {
             width,
             height,
             offset: Vec2::ZERO,
-            zoom: 0.5, // Start at 50% zoom
+            zoom: DEFAULT_ZOOM,
+            zoom_target: DEFAULT_ZOOM,
+            zoom_velocity: 0.0,
+            zoom_anchor: Vec2::ZERO,
+            transition: None,
         }
     }
 
@@ -30,18 +52,17 @@ impl Canvas {
     pub fn get_width(&self) -> f32 {
         self.width
     }
-    
-    pub fn get_height(&self) -> f32 {
-        self.height
-    }
 
     pub fn pan(&mut self, delta: Vec2) {
+        self.transition = None;
         self.offset += delta;
     }
 
     pub fn zoom_at(&mut self, factor: f32, pos: Pos2, view_rect: Rect) {
+        self.transition = None;
         let old_zoom = self.zoom;
-        self.zoom = (self.zoom * factor).clamp(0.1, 10.0);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_target = self.zoom;
         
         let view_center = view_rect.center();
         let mouse_offset = pos - view_center;
@@ -49,8 +70,113 @@ impl Canvas {
     }
 
     pub fn reset_view(&mut self) {
+        self.transition = None;
         self.offset = Vec2::ZERO;
-        self.zoom = 0.5;
+        self.zoom = DEFAULT_ZOOM;
+        self.zoom_target = DEFAULT_ZOOM;
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.transition = None;
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_target = self.zoom;
+    }
+
+    /// Like zoom_at, but only sets the target that tick_zoom eases towards
+    pub fn zoom_towards(&mut self, factor: f32, pos: Pos2, view_rect: Rect) {
+        self.transition = None;
+        self.zoom_target = (self.zoom_target * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_anchor = pos - view_rect.center();
+    }
+
+    /// Moves the zoom a frame's worth towards its target, keeping the anchor
+    /// point fixed on screen
+    pub fn tick_zoom(&mut self, dt: f32) {
+        if self.zoom == self.zoom_target {
+            self.zoom_velocity = 0.0;
+            return;
+        }
+        // Critically damped exponential decay in ln(zoom), so zooming in and
+        // out feel symmetric and fast scrolling keeps its momentum
+        let omega = 2.0 / ZOOM_SMOOTH_TIME;
+        let x = omega * dt;
+        let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+        let change = self.zoom.ln() - self.zoom_target.ln();
+        let temp = (self.zoom_velocity + omega * change) * dt;
+        self.zoom_velocity = (self.zoom_velocity - omega * temp) * decay;
+        let mut zoom = (self.zoom_target.ln() + (change + temp) * decay).exp();
+        if (zoom / self.zoom_target - 1.0).abs() < 1e-3 {
+            zoom = self.zoom_target;
+        }
+
+        let old_zoom = self.zoom;
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset -= self.zoom_anchor * (self.zoom / old_zoom - 1.0);
+    }
+
+    pub fn set_offset(&mut self, offset: Vec2) {
+        self.transition = None;
+        self.offset = offset;
+    }
+
+    /// Canvas position shown at the center of the view
+    pub fn view_center(&self) -> Pos2 {
+        Pos2::new(self.width * 0.5, self.height * 0.5) - self.offset / self.zoom
+    }
+
+    /// Centers the view on a canvas position at the given zoom
+    pub fn set_view(&mut self, center: Pos2, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_target = self.zoom;
+        self.offset = self.centered_offset(center, self.zoom);
+    }
+
+    /// Offset that puts a canvas position at the center of the view at the given zoom
+    pub fn centered_offset(&self, center: Pos2, zoom: f32) -> Vec2 {
+        (Pos2::new(self.width * 0.5, self.height * 0.5) - center) * zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+    }
+
+    /// Starts a smooth zoom that keeps the view center in place
+    pub fn smooth_zoom_to(&mut self, zoom: f32) {
+        self.transition = None;
+        self.zoom_target = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_anchor = Vec2::ZERO;
+    }
+
+    /// Zoom at which a canvas region fills the view; the other axis gets
+    /// extra room when the aspect ratios differ
+    pub fn zoom_to_fit(region: Rect, view_rect: Rect) -> f32 {
+        let zoom_x = view_rect.width() / region.width().max(f32::EPSILON);
+        let zoom_y = view_rect.height() / region.height().max(f32::EPSILON);
+        zoom_x.min(zoom_y).clamp(MIN_ZOOM, MAX_ZOOM)
+    }
+
+    pub fn animate_to(&mut self, center: Pos2, zoom: f32, now: f64) {
+        self.transition = Some(ViewTransition {
+            from: (self.view_center(), self.zoom),
+            to: (center, zoom),
+            start_time: now,
+        });
+    }
+
+    /// Advances a running view transition; returns true while it's still animating
+    pub fn update_transition(&mut self, now: f64) -> bool {
+        let transition = match &self.transition {
+            Some(transition) => transition,
+            None => return false,
+        };
+        let t = ((now - transition.start_time) / VIEW_TRANSITION_SECONDS).clamp(0.0, 1.0) as f32;
+        let eased = t * t * (3.0 - 2.0 * t);
+        let (from_center, from_zoom) = transition.from;
+        let (to_center, to_zoom) = transition.to;
+        // Interpolate zoom geometrically so zooming in and out feel symmetric
+        let zoom = from_zoom * (to_zoom / from_zoom).powf(eased);
+        let center = from_center.lerp(to_center, eased);
+        if t >= 1.0 {
+            self.transition = None;
+        }
+        self.set_view(center, zoom);
+        self.transition.is_some()
     }
 
     pub fn get_offset(&self) -> Vec2 {
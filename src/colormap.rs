@@ -0,0 +1,167 @@
+use crate::marker::Marker;
+use egui::Color32;
+
+// Evenly spaced stops, interpolated linearly in sRGB
+const VIRIDIS: [(u8, u8, u8); 9] = [
+    (68, 1, 84),
+    (71, 45, 123),
+    (59, 82, 139),
+    (44, 114, 142),
+    (33, 145, 140),
+    (40, 174, 128),
+    (94, 201, 98),
+    (173, 220, 48),
+    (253, 231, 37),
+];
+
+const TURBO: [(u8, u8, u8); 11] = [
+    (48, 18, 59),
+    (70, 98, 215),
+    (54, 170, 249),
+    (26, 228, 182),
+    (114, 254, 94),
+    (199, 239, 52),
+    (251, 185, 56),
+    (245, 105, 24),
+    (201, 41, 3),
+    (144, 12, 0),
+    (122, 4, 3),
+];
+
+const GRAYSCALE: [(u8, u8, u8); 2] = [(0, 0, 0), (255, 255, 255)];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,
+    Turbo,
+    Grayscale,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 3] = [Colormap::Viridis, Colormap::Turbo, Colormap::Grayscale];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Turbo => "Turbo",
+            Colormap::Grayscale => "Grayscale",
+        }
+    }
+
+    fn stops(&self) -> &'static [(u8, u8, u8)] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Turbo => &TURBO,
+            Colormap::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// Color at `t` in 0..=1; values outside are clamped
+    pub fn sample(&self, t: f32) -> Color32 {
+        let stops = self.stops();
+        let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (scaled.floor() as usize).min(stops.len() - 2);
+        let fraction = scaled - index as f32;
+        let (a, b) = (stops[index], stops[index + 1]);
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * fraction).round() as u8;
+        Color32::from_rgb(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+    }
+}
+
+// Marker attribute that drives the colormap
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorBy {
+    Stored, // Each marker's own color
+    X,
+    Y,
+    Index,
+    DistanceFromOrigin,
+}
+
+impl ColorBy {
+    pub const ALL: [ColorBy; 5] = [
+        ColorBy::Stored,
+        ColorBy::X,
+        ColorBy::Y,
+        ColorBy::Index,
+        ColorBy::DistanceFromOrigin,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorBy::Stored => "Stored colors",
+            ColorBy::X => "X position",
+            ColorBy::Y => "Y position",
+            ColorBy::Index => "Placement index",
+            ColorBy::DistanceFromOrigin => "Distance from origin",
+        }
+    }
+
+    fn value(&self, index: usize, marker: &Marker) -> f32 {
+        let position = marker.system_position;
+        match self {
+            ColorBy::Stored => 0.0,
+            ColorBy::X => position.x,
+            ColorBy::Y => position.y,
+            ColorBy::Index => index as f32,
+            ColorBy::DistanceFromOrigin => position.to_vec2().length(),
+        }
+    }
+}
+
+/// Colormapped colors for each marker plus the (min, max) value range they
+/// span, or None when markers keep their stored colors
+pub fn marker_colors(markers: &[Marker], color_by: ColorBy, colormap: Colormap) -> Option<(Vec<Color32>, (f32, f32))> {
+    if color_by == ColorBy::Stored || markers.is_empty() {
+        return None;
+    }
+    let values: Vec<f32> = markers
+        .iter()
+        .enumerate()
+        .map(|(i, marker)| color_by.value(i, marker))
+        .collect();
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    // A single value (or identical ones) sits in the middle of the map
+    let colors = values
+        .iter()
+        .map(|&value| {
+            let t = if max > min { (value - min) / (max - min) } else { 0.5 };
+            colormap.sample(t)
+        })
+        .collect();
+    Some((colors, (min, max)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_hits_first_and_last_stops() {
+        for colormap in Colormap::ALL {
+            let stops = colormap.stops();
+            let (r, g, b) = stops[0];
+            assert_eq!(colormap.sample(0.0), Color32::from_rgb(r, g, b));
+            let (r, g, b) = stops[stops.len() - 1];
+            assert_eq!(colormap.sample(1.0), Color32::from_rgb(r, g, b));
+        }
+    }
+
+    #[test]
+    fn sample_clamps_out_of_range_values() {
+        for colormap in Colormap::ALL {
+            assert_eq!(colormap.sample(-1.0), colormap.sample(0.0));
+            assert_eq!(colormap.sample(2.0), colormap.sample(1.0));
+        }
+    }
+
+    #[test]
+    fn sample_midpoint() {
+        // Odd stop counts put 0.5 exactly on the middle stop
+        assert_eq!(Colormap::Viridis.sample(0.5), Color32::from_rgb(33, 145, 140));
+        assert_eq!(Colormap::Turbo.sample(0.5), Color32::from_rgb(199, 239, 52));
+        // Two stops interpolate halfway, rounding 127.5 up
+        assert_eq!(Colormap::Grayscale.sample(0.5), Color32::from_rgb(128, 128, 128));
+    }
+}
@@ -0,0 +1,155 @@
+use egui::{Pos2, Vec2};
+
+const HISTORY_LIMIT: usize = 10;
+
+// A quick-placement command typed into the command entry popup
+pub enum GridCommand {
+    Cell(i32, i32),     // "col,row" grid intersection
+    Absolute(f32, f32), // "@x,y" pixel position
+}
+
+impl GridCommand {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let (absolute, body) = match input.strip_prefix('@') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+
+        let parts: Vec<&str> = body.split(',').map(str::trim).collect();
+        if parts.len() != 2 {
+            return Err("Expected col,row or @x,y".to_string());
+        }
+
+        if absolute {
+            let x = parts[0].parse::<f32>().map_err(|_| format!("Invalid x value '{}'", parts[0]))?;
+            let y = parts[1].parse::<f32>().map_err(|_| format!("Invalid y value '{}'", parts[1]))?;
+            Ok(GridCommand::Absolute(x, y))
+        } else {
+            let col = parts[0].parse::<i32>().map_err(|_| format!("Invalid column '{}'", parts[0]))?;
+            let row = parts[1].parse::<i32>().map_err(|_| format!("Invalid row '{}'", parts[1]))?;
+            Ok(GridCommand::Cell(col, row))
+        }
+    }
+
+    /// Position in the active coordinate system
+    pub fn system_position(&self, grid_size: Vec2) -> Pos2 {
+        match *self {
+            GridCommand::Cell(col, row) => Pos2::new(col as f32 * grid_size.x, row as f32 * grid_size.y),
+            GridCommand::Absolute(x, y) => Pos2::new(x, y),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CommandEntry {
+    pub open: bool,
+    pub input: String,
+    pub error: Option<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+impl CommandEntry {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.input.clear();
+        self.error = None;
+        self.history_index = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.error = None;
+    }
+
+    pub fn push_history(&mut self, command: String) {
+        self.history.retain(|entry| *entry != command);
+        self.history.push(command);
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        self.history_index = None;
+    }
+
+    // Step back to an older command
+    pub fn history_previous(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    // Step forward to a newer command, ending on an empty input
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.input = self.history[index + 1].clone();
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.input.clear();
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_scale_by_the_grid_size_of_each_axis() {
+        let command = GridCommand::parse(" 3, -2 ").unwrap();
+        assert_eq!(command.system_position(Vec2::new(10.0, 20.0)), Pos2::new(30.0, -40.0));
+    }
+
+    #[test]
+    fn absolute_positions_ignore_the_grid() {
+        let command = GridCommand::parse("@12.5,40").unwrap();
+        assert_eq!(command.system_position(Vec2::new(10.0, 20.0)), Pos2::new(12.5, 40.0));
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        assert!(GridCommand::parse("3").is_err());
+        assert!(GridCommand::parse("1,2,3").is_err());
+        assert_eq!(GridCommand::parse("1.5,2").err().unwrap(), "Invalid column '1.5'");
+        assert_eq!(GridCommand::parse("@x,2").err().unwrap(), "Invalid x value 'x'");
+    }
+
+    #[test]
+    fn history_steps_back_and_forward() {
+        let mut entry = CommandEntry::default();
+        entry.push_history("1,1".to_string());
+        entry.push_history("2,2".to_string());
+        entry.push_history("1,1".to_string()); // Moves to the newest instead of repeating
+        entry.history_previous();
+        assert_eq!(entry.input, "1,1");
+        entry.history_previous();
+        assert_eq!(entry.input, "2,2");
+        entry.history_previous();
+        assert_eq!(entry.input, "2,2");
+        entry.history_next();
+        assert_eq!(entry.input, "1,1");
+        entry.history_next();
+        assert_eq!(entry.input, "");
+    }
+
+    #[test]
+    fn history_keeps_the_newest_commands() {
+        let mut entry = CommandEntry::default();
+        for i in 0..HISTORY_LIMIT + 2 {
+            entry.push_history(format!("{},0", i));
+        }
+        assert_eq!(entry.history.len(), HISTORY_LIMIT);
+        assert_eq!(entry.history[0], "2,0");
+    }
+}
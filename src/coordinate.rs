@@ -1,48 +1,395 @@
 use egui::Pos2;
 
+// Units positions are displayed and copied in. Stored positions stay in pixels.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateUnits {
+    Pixels,
+    Normalized, // Fractions of the canvas size, 0..1 across it
+    Percent,    // As Normalized, scaled to 0..100 for CSS
+}
+
+impl CoordinateUnits {
+    pub const ALL: [CoordinateUnits; 3] = [
+        CoordinateUnits::Pixels,
+        CoordinateUnits::Normalized,
+        CoordinateUnits::Percent,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CoordinateUnits::Pixels => "Pixels",
+            CoordinateUnits::Normalized => "Normalized (0..1)",
+            CoordinateUnits::Percent => "Percentage (%)",
+        }
+    }
+}
+
+// Where (0, 0) sits on the canvas and which way Y grows from it
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OriginMode {
+    TopLeft,    // Y grows downwards, same as the canvas
+    BottomLeft, // Y grows upwards
+    Center,     // Y grows upwards, as in most game engines and graphics APIs
+}
+
+impl OriginMode {
+    pub const ALL: [OriginMode; 3] = [OriginMode::TopLeft, OriginMode::BottomLeft, OriginMode::Center];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OriginMode::TopLeft => "Origin at Top-Left (0,0)",
+            OriginMode::BottomLeft => "Origin at Bottom-Left (0,0)",
+            OriginMode::Center => "Origin at Center (0,0)",
+        }
+    }
+
+    pub fn y_up(&self) -> bool {
+        *self != OriginMode::TopLeft
+    }
+}
+
+// Common scales for pixel values, taking 96 pixels per inch as CSS does
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScalePreset {
+    Pixels,
+    Inches,
+    Millimeters,
+    Dp, // Android density-independent pixels on a 1.5x (hdpi) screen
+}
+
+impl ScalePreset {
+    pub const ALL: [ScalePreset; 4] = [
+        ScalePreset::Pixels,
+        ScalePreset::Inches,
+        ScalePreset::Millimeters,
+        ScalePreset::Dp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScalePreset::Pixels => "Pixels",
+            ScalePreset::Inches => "Inches",
+            ScalePreset::Millimeters => "Millimeters",
+            ScalePreset::Dp => "dp",
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        match self {
+            ScalePreset::Pixels => 1.0,
+            ScalePreset::Inches => 1.0 / 96.0,
+            ScalePreset::Millimeters => 25.4 / 96.0,
+            ScalePreset::Dp => 1.0 / 1.5,
+        }
+    }
+
+    // Pixels get no label, matching the unscaled readout
+    pub fn unit_label(&self) -> &'static str {
+        match self {
+            ScalePreset::Pixels => "",
+            ScalePreset::Inches => "in",
+            ScalePreset::Millimeters => "mm",
+            ScalePreset::Dp => "dp",
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct CoordinateSystem {
-    origin_top_left: bool,
+    origin_mode: OriginMode,
+    canvas_width: f32,
     canvas_height: f32,
+    custom_origin: Option<Pos2>, // Canvas position overriding the mode's origin
+    units: CoordinateUnits,
+    scale: f32,         // Multiplies pixel values on display and copy; stored positions are unscaled
+    unit_label: String, // Shown after scaled values, e.g. "mm"
 }
 
 impl CoordinateSystem {
-    pub fn new(origin_top_left: bool) -> Self {
+    pub fn new(origin_mode: OriginMode) -> Self {
         Self {
-            origin_top_left,
-            canvas_height: 1080.0, // Default height, will be updated
+            origin_mode,
+            // Default size, will be updated
+            canvas_width: 1920.0,
+            canvas_height: 1080.0,
+            custom_origin: None,
+            units: CoordinateUnits::Pixels,
+            scale: 1.0,
+            unit_label: String::new(),
         }
     }
 
-    pub fn set_origin_top_left(&mut self, origin_top_left: bool) {
-        self.origin_top_left = origin_top_left;
+    pub fn set_scale(&mut self, scale: f32, unit_label: &str) {
+        self.scale = scale;
+        self.unit_label = unit_label.to_string();
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn unit_label(&self) -> &str {
+        &self.unit_label
+    }
+
+    /// The preset matching the current scale and label, or None for a custom one
+    pub fn scale_preset(&self) -> Option<ScalePreset> {
+        ScalePreset::ALL
+            .into_iter()
+            .find(|preset| preset.scale() == self.scale && preset.unit_label() == self.unit_label)
+    }
+
+    /// A pixel value in the configured units
+    pub fn apply_scale(&self, pos: Pos2) -> Pos2 {
+        Pos2::new(pos.x * self.scale, pos.y * self.scale)
     }
 
-    pub fn is_origin_top_left(&self) -> bool {
-        self.origin_top_left
+    pub fn set_units(&mut self, units: CoordinateUnits) {
+        self.units = units;
     }
 
-    // Add method to update canvas height
-    pub fn update_canvas_height(&mut self, height: f32) {
+    pub fn units(&self) -> CoordinateUnits {
+        self.units
+    }
+
+    pub fn set_origin_mode(&mut self, origin_mode: OriginMode) {
+        self.origin_mode = origin_mode;
+    }
+
+    pub fn origin_mode(&self) -> OriginMode {
+        self.origin_mode
+    }
+
+    pub fn update_canvas_size(&mut self, width: f32, height: f32) {
+        self.canvas_width = width;
         self.canvas_height = height;
     }
 
+    pub fn set_custom_origin(&mut self, origin: Option<Pos2>) {
+        self.custom_origin = origin;
+    }
+
+    pub fn custom_origin(&self) -> Option<Pos2> {
+        self.custom_origin
+    }
+
+    /// Canvas position of the system's (0, 0) point
+    pub fn origin_canvas_pos(&self) -> Pos2 {
+        match (self.custom_origin, self.origin_mode) {
+            (Some(origin), _) => origin,
+            (None, OriginMode::TopLeft) => Pos2::ZERO,
+            (None, OriginMode::BottomLeft) => Pos2::new(0.0, self.canvas_height),
+            (None, OriginMode::Center) => Pos2::new(self.canvas_width / 2.0, self.canvas_height / 2.0),
+        }
+    }
+
     /// Converts canvas coordinates to the chosen coordinate system
     pub fn to_system_coordinates(&self, canvas_pos: Pos2) -> Pos2 {
-        if self.origin_top_left {
-            canvas_pos // Top-left origin, same as canvas
+        let origin = self.origin_canvas_pos();
+        if self.origin_mode.y_up() {
+            // Y grows upwards, need to flip Y relative to the origin
+            Pos2::new(canvas_pos.x - origin.x, origin.y - canvas_pos.y)
         } else {
-            // Bottom-left origin, need to flip Y relative to canvas height
-            Pos2::new(canvas_pos.x, self.canvas_height - canvas_pos.y)
+            // Y grows downwards, same as canvas
+            Pos2::new(canvas_pos.x - origin.x, canvas_pos.y - origin.y)
         }
     }
 
     /// Converts from the chosen coordinate system back to canvas coordinates
     pub fn from_system_coordinates(&self, system_pos: Pos2) -> Pos2 {
-        if self.origin_top_left {
-            system_pos // Top-left origin, same as canvas
+        let origin = self.origin_canvas_pos();
+        if self.origin_mode.y_up() {
+            // Flip Y back relative to the origin
+            Pos2::new(origin.x + system_pos.x, origin.y - system_pos.y)
         } else {
-            // Bottom-left origin, need to flip Y back relative to canvas height
-            Pos2::new(system_pos.x, self.canvas_height - system_pos.y)
+            Pos2::new(origin.x + system_pos.x, origin.y + system_pos.y)
+        }
+    }
+}
+
+/// Device pixel ratio of the screenshot on the canvas. Canvas positions are
+/// device pixels; CSS pixels are derived for display only.
+pub struct PixelRatio {
+    pub ratio: f32,
+    pub keep_half_pixels: bool, // Round CSS values to .5 instead of whole pixels
+}
+
+impl PixelRatio {
+    pub const PRESETS: [f32; 4] = [1.0, 1.5, 2.0, 3.0];
+
+    pub fn to_css(&self, device_pos: Pos2) -> Pos2 {
+        let round = |value: f32| {
+            let css = value / self.ratio;
+            if self.keep_half_pixels {
+                (css * 2.0).round() / 2.0
+            } else {
+                css.round()
+            }
+        };
+        Pos2::new(round(device_pos.x), round(device_pos.y))
+    }
+}
+
+/// `pos` as a fraction of `size`. Edges divide a value by itself, so they
+/// come out as exactly 0.0 and 1.0.
+pub fn normalize(pos: Pos2, size: (f32, f32)) -> Pos2 {
+    Pos2::new(pos.x / size.0, pos.y / size.1)
+}
+
+pub fn format_fraction(value: f32, decimals: usize) -> String {
+    // Avoid "-0.0000" just left of the origin
+    let value = if value.abs() < 0.5 * 10f32.powi(-(decimals as i32)) { 0.0 } else { value };
+    format!("{:.*}", decimals, value)
+}
+
+// Pixel value without a trailing ".0" on whole values
+pub fn format_pixel(value: f32) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i32)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+pub fn format_pixels(pos: Pos2) -> String {
+    format!("{}, {}", format_pixel(pos.x), format_pixel(pos.y))
+}
+
+// Rotation/mirroring applied to displayed, copied and exported values only,
+// for target devices that show the screenshot rotated
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputTransform {
+    None,
+    Rotate90Cw,
+    Rotate90Ccw,
+    Rotate180,
+    MirrorX,
+    MirrorY,
+}
+
+impl OutputTransform {
+    pub const ALL: [OutputTransform; 6] = [
+        OutputTransform::None,
+        OutputTransform::Rotate90Cw,
+        OutputTransform::Rotate90Ccw,
+        OutputTransform::Rotate180,
+        OutputTransform::MirrorX,
+        OutputTransform::MirrorY,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputTransform::None => "None",
+            OutputTransform::Rotate90Cw => "Rotate 90° CW",
+            OutputTransform::Rotate90Ccw => "Rotate 90° CCW",
+            OutputTransform::Rotate180 => "Rotate 180°",
+            OutputTransform::MirrorX => "Mirror X",
+            OutputTransform::MirrorY => "Mirror Y",
+        }
+    }
+
+    /// Maps a position on a `size` canvas into the transformed output
+    pub fn apply(&self, pos: Pos2, size: (f32, f32)) -> Pos2 {
+        let (width, height) = size;
+        match self {
+            OutputTransform::None => pos,
+            OutputTransform::Rotate90Cw => Pos2::new(height - pos.y, pos.x),
+            OutputTransform::Rotate90Ccw => Pos2::new(pos.y, width - pos.x),
+            OutputTransform::Rotate180 => Pos2::new(width - pos.x, height - pos.y),
+            OutputTransform::MirrorX => Pos2::new(width - pos.x, pos.y),
+            OutputTransform::MirrorY => Pos2::new(pos.x, height - pos.y),
+        }
+    }
+
+    /// Size of the output for a `size` canvas; quarter turns swap the axes
+    pub fn output_size(&self, size: (f32, f32)) -> (f32, f32) {
+        match self {
+            OutputTransform::Rotate90Cw | OutputTransform::Rotate90Ccw => (size.1, size.0),
+            _ => size,
+        }
+    }
+}
+
+/// 2D affine matrix [a b tx; c d ty; 0 0 1] applied to output coordinates
+#[derive(Clone, Copy, PartialEq)]
+pub struct AffineTransform {
+    pub a: f32,
+    pub b: f32,
+    pub tx: f32,
+    pub c: f32,
+    pub d: f32,
+    pub ty: f32,
+}
+
+impl AffineTransform {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0);
+    pub const FLIP_X: Self = Self::new(-1.0, 0.0, 0.0, 1.0);
+    pub const FLIP_Y: Self = Self::new(1.0, 0.0, 0.0, -1.0);
+    pub const ROTATE_90: Self = Self::new(0.0, -1.0, 1.0, 0.0);
+    pub const SCALE_2: Self = Self::new(2.0, 0.0, 0.0, 2.0);
+
+    // Linear part only, no translation
+    const fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+        Self {
+            a,
+            b,
+            tx: 0.0,
+            c,
+            d,
+            ty: 0.0,
+        }
+    }
+
+    pub fn apply(&self, pos: Pos2) -> Pos2 {
+        Pos2::new(
+            self.a * pos.x + self.b * pos.y + self.tx,
+            self.c * pos.x + self.d * pos.y + self.ty,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn css_at_fractional_ratio() {
+        let whole = PixelRatio { ratio: 1.5, keep_half_pixels: false };
+        assert_eq!(whole.to_css(Pos2::new(300.0, 151.0)), Pos2::new(200.0, 101.0));
+        let half = PixelRatio { ratio: 1.5, keep_half_pixels: true };
+        assert_eq!(half.to_css(Pos2::new(300.0, 151.0)), Pos2::new(200.0, 100.5));
+    }
+
+    #[test]
+    fn css_at_triple_ratio() {
+        let whole = PixelRatio { ratio: 3.0, keep_half_pixels: false };
+        assert_eq!(whole.to_css(Pos2::new(100.0, 3.0)), Pos2::new(33.0, 1.0));
+        let half = PixelRatio { ratio: 3.0, keep_half_pixels: true };
+        assert_eq!(half.to_css(Pos2::new(100.0, 3.0)), Pos2::new(33.5, 1.0));
+    }
+
+    #[test]
+    fn output_transform_maps_corners() {
+        // Non-square so swapped axes show up
+        let size = (400.0, 300.0);
+        let corners = [Pos2::new(0.0, 0.0), Pos2::new(400.0, 0.0), Pos2::new(0.0, 300.0), Pos2::new(400.0, 300.0)];
+        let expected = |transform: OutputTransform| -> [(f32, f32); 4] {
+            match transform {
+                OutputTransform::None => [(0.0, 0.0), (400.0, 0.0), (0.0, 300.0), (400.0, 300.0)],
+                OutputTransform::Rotate90Cw => [(300.0, 0.0), (300.0, 400.0), (0.0, 0.0), (0.0, 400.0)],
+                OutputTransform::Rotate90Ccw => [(0.0, 400.0), (0.0, 0.0), (300.0, 400.0), (300.0, 0.0)],
+                OutputTransform::Rotate180 => [(400.0, 300.0), (0.0, 300.0), (400.0, 0.0), (0.0, 0.0)],
+                OutputTransform::MirrorX => [(400.0, 0.0), (0.0, 0.0), (400.0, 300.0), (0.0, 300.0)],
+                OutputTransform::MirrorY => [(0.0, 300.0), (400.0, 300.0), (0.0, 0.0), (400.0, 0.0)],
+            }
+        };
+        for transform in OutputTransform::ALL {
+            let (width, height) = transform.output_size(size);
+            for (corner, (x, y)) in corners.iter().zip(expected(transform)) {
+                let mapped = transform.apply(*corner, size);
+                assert_eq!(mapped, Pos2::new(x, y), "{} {:?}", transform.label(), corner);
+                assert!(mapped.x <= width && mapped.y <= height, "{} leaves the output", transform.label());
+            }
         }
     }
 }
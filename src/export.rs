@@ -0,0 +1,1710 @@
+use crate::annotation::Annotation;
+use crate::coordinate::{self, OutputTransform};
+use crate::grid::{Grid, GridStyle};
+use crate::marker::{Marker, MarkerKind};
+use crate::session::SessionInfo;
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use egui::{Color32, Pos2, Rect};
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MM_PER_INCH: f32 = 25.4;
+const METERS_PER_INCH: f32 = 0.0254;
+
+// Sizes in canvas px at 1x, scaled with the rest of an annotated PNG
+const LABEL_FONT_SIZE: f32 = 14.0;
+const TITLE_FONT_SIZE: f32 = 16.0;
+const TITLE_BAND_HEIGHT: f32 = 32.0;
+
+/// Stroke color of annotations, on canvas and in exports
+pub const ANNOTATION_COLOR: Color32 = Color32::from_rgb(255, 140, 0);
+
+/// Output size above which the export UI warns before writing
+pub const LARGE_EXPORT_MEGAPIXELS: f32 = 50.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    FigmaPixels,
+    FigmaPercent,
+    Svg,
+    Png,
+    CssGrid,
+    HtmlArea,
+    AndroidPointResource,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 7] = [
+        ExportFormat::FigmaPixels,
+        ExportFormat::FigmaPercent,
+        ExportFormat::Svg,
+        ExportFormat::Png,
+        ExportFormat::CssGrid,
+        ExportFormat::HtmlArea,
+        ExportFormat::AndroidPointResource,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::FigmaPixels => "Figma plugin JSON (px)",
+            ExportFormat::FigmaPercent => "Figma plugin JSON (%)",
+            ExportFormat::Svg => "SVG image",
+            ExportFormat::Png => "PNG image",
+            ExportFormat::CssGrid => "CSS Grid template",
+            ExportFormat::HtmlArea => "HTML image map",
+            ExportFormat::AndroidPointResource => "Android dimen resources",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::FigmaPixels | ExportFormat::FigmaPercent => "json",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+            ExportFormat::CssGrid => "css",
+            ExportFormat::HtmlArea => "html",
+            ExportFormat::AndroidPointResource => "xml",
+        }
+    }
+}
+
+// Color space that marker colors are meant to be read in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorProfile {
+    Srgb,
+    LinearRgb,
+    DisplayP3,
+}
+
+impl ColorProfile {
+    pub const ALL: [ColorProfile; 3] = [ColorProfile::Srgb, ColorProfile::LinearRgb, ColorProfile::DisplayP3];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "sRGB",
+            ColorProfile::LinearRgb => "Linear RGB",
+            ColorProfile::DisplayP3 => "Display P3",
+        }
+    }
+
+    // Value for the SVG `color-profile` attribute
+    fn svg_name(&self) -> &'static str {
+        match self {
+            ColorProfile::Srgb => "sRGB",
+            ColorProfile::LinearRgb => "linearRGB",
+            ColorProfile::DisplayP3 => "display-p3",
+        }
+    }
+}
+
+// Layout of a PNG export around the canvas
+#[derive(Clone, Copy, PartialEq)]
+pub struct PngLayout {
+    pub scale: f32,  // Image pixels per canvas px, e.g. 2.0 for supersampled slides
+    pub margin: f32, // Canvas px around every side
+    pub background: Color32,
+    pub title: bool,  // Session name and date below the image
+    pub labels: bool, // Coordinate label beside each marker
+}
+
+impl Default for PngLayout {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            margin: 0.0,
+            background: Color32::WHITE,
+            title: false,
+            labels: false,
+        }
+    }
+}
+
+impl PngLayout {
+    /// Pixel dimensions of the exported image
+    pub fn output_size(&self, canvas_size: (f32, f32)) -> (u32, u32) {
+        let title_height = if self.title { TITLE_BAND_HEIGHT } else { 0.0 };
+        let width = (canvas_size.0 + 2.0 * self.margin) * self.scale;
+        let height = (canvas_size.1 + 2.0 * self.margin + title_height) * self.scale;
+        (width.round() as u32, height.round() as u32)
+    }
+}
+
+// Settings shared by every export format
+pub struct ExportSettings<'a> {
+    pub dpi: f32,
+    pub gamma: f32,
+    pub color_profile: ColorProfile,
+    pub output_transform: OutputTransform, // Applied to JSON coordinates only
+    pub css_grid_fractional: bool,         // fr tracks instead of px
+    pub html_area_radius: u32,
+    pub android_dp_scale: f32, // dp per exported px
+    pub png_layout: PngLayout,
+    pub session: SessionInfo,
+    pub annotations: Vec<Annotation>, // Vector formats only
+    pub layers: CanvasLayers<'a>,     // PNG and SVG only
+}
+
+// How a background image is blended with the canvas color behind it
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompositeMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Dodge,
+    Burn,
+}
+
+impl CompositeMode {
+    pub const ALL: [CompositeMode; 6] = [
+        CompositeMode::Normal,
+        CompositeMode::Multiply,
+        CompositeMode::Screen,
+        CompositeMode::Overlay,
+        CompositeMode::Dodge,
+        CompositeMode::Burn,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CompositeMode::Normal => "Normal",
+            CompositeMode::Multiply => "Multiply",
+            CompositeMode::Screen => "Screen",
+            CompositeMode::Overlay => "Overlay",
+            CompositeMode::Dodge => "Color Dodge",
+            CompositeMode::Burn => "Color Burn",
+        }
+    }
+
+    // CSS mix-blend-mode with the same effect, for SVG export
+    fn css_name(&self) -> &'static str {
+        match self {
+            CompositeMode::Normal => "normal",
+            CompositeMode::Multiply => "multiply",
+            CompositeMode::Screen => "screen",
+            CompositeMode::Overlay => "overlay",
+            CompositeMode::Dodge => "color-dodge",
+            CompositeMode::Burn => "color-burn",
+        }
+    }
+}
+
+/// Blends the RGB of `overlay` onto `base` per channel. Alpha is ignored and
+/// the result is opaque.
+pub fn blend_image(base: Color32, overlay: Color32, mode: CompositeMode) -> Color32 {
+    let blend = |base: u8, overlay: u8| {
+        let (b, o) = (base as f32 / 255.0, overlay as f32 / 255.0);
+        let value = match mode {
+            CompositeMode::Normal => o,
+            CompositeMode::Multiply => b * o,
+            CompositeMode::Screen => 1.0 - (1.0 - b) * (1.0 - o),
+            // Multiply in the shadows of the base, screen in its highlights
+            CompositeMode::Overlay => {
+                if b < 0.5 {
+                    2.0 * b * o
+                } else {
+                    1.0 - 2.0 * (1.0 - b) * (1.0 - o)
+                }
+            }
+            CompositeMode::Dodge => {
+                if o >= 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - o)).min(1.0)
+                }
+            }
+            CompositeMode::Burn => {
+                if o <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / o).min(1.0)
+                }
+            }
+        };
+        (value * 255.0).round() as u8
+    };
+    Color32::from_rgb(
+        blend(base.r(), overlay.r()),
+        blend(base.g(), overlay.g()),
+        blend(base.b(), overlay.b()),
+    )
+}
+
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+// Lookup table re-encoding 8-bit values meant for a 2.2 display for one with
+// the given gamma: higher values brighten the midtones, lower ones darken them
+fn gamma_table(gamma: f32) -> [u8; 256] {
+    let exponent = DEFAULT_GAMMA / gamma;
+    let mut table = [0; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = ((value as f32 / 255.0).powf(exponent) * 255.0).round() as u8;
+    }
+    table
+}
+
+/// Background image pixels gamma corrected and blended onto `base`, ready to
+/// upload as a texture. Each pixel keeps its own alpha.
+pub fn composite_image(image: &RgbaImage, base: Color32, mode: CompositeMode, gamma: f32) -> egui::ColorImage {
+    let size = [image.width() as usize, image.height() as usize];
+    if mode == CompositeMode::Normal && gamma == DEFAULT_GAMMA {
+        return egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    }
+    let table = gamma_table(gamma);
+    let pixels = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            let corrected = Color32::from_rgb(table[r as usize], table[g as usize], table[b as usize]);
+            let blended = blend_image(base, corrected, mode);
+            Color32::from_rgba_unmultiplied(blended.r(), blended.g(), blended.b(), a)
+        })
+        .collect();
+    egui::ColorImage { size, pixels }
+}
+
+/// Mean Rec. 709 luma of the image from 0 (black) to 1 (white), weighting
+/// each pixel by its alpha. Fully transparent images count as black.
+pub fn average_luminance(image: &RgbaImage) -> f32 {
+    let (mut luma_sum, mut alpha_sum) = (0.0f64, 0.0f64);
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let luma = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+        luma_sum += luma * a as f64;
+        alpha_sum += a as f64;
+    }
+    if alpha_sum == 0.0 {
+        0.0
+    } else {
+        (luma_sum / alpha_sum / 255.0) as f32
+    }
+}
+
+// Payload consumed by a Figma plugin. The plugin is expected to:
+//   1. find the frame named `frame` on the current page (or create one of
+//      `width` x `height` if it doesn't exist),
+//   2. for `units == "px"`, scale each point by (frame.width / width,
+//      frame.height / height) when the frame size differs from the screenshot,
+//   3. for `units == "percent"`, place each point at
+//      (x / 100 * frame.width, y / 100 * frame.height),
+//   4. create a small node named `name` at the resulting position, relative to
+//      the frame's top-left corner.
+// Coordinates are always top-left based, regardless of the picker's origin.
+#[derive(Serialize)]
+pub struct FigmaExport {
+    pub frame: String,
+    pub width: f32,
+    pub height: f32,
+    pub units: &'static str,
+    pub points: Vec<FigmaPoint>,
+    #[serde(flatten)]
+    pub session: SessionInfo, // Title, author etc. as top-level fields
+}
+
+#[derive(Serialize)]
+pub struct FigmaPoint {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+impl FigmaExport {
+    pub fn new(
+        frame: &str,
+        canvas_size: (f32, f32),
+        markers: &[Marker],
+        percent: bool,
+        transform: OutputTransform,
+    ) -> Self {
+        let (width, height) = transform.output_size(canvas_size);
+        let points = markers
+            .iter()
+            .enumerate()
+            .map(|(i, marker)| {
+                let position = transform.apply(marker.position, canvas_size);
+                let (x, y) = if percent {
+                    (position.x / width * 100.0, position.y / height * 100.0)
+                } else {
+                    (position.x, position.y)
+                };
+                FigmaPoint {
+                    name: match marker.label_text() {
+                        Some(label) => label.to_string(),
+                        None => format!("Marker {}", i + 1),
+                    },
+                    x,
+                    y,
+                    note: marker.note.clone(),
+                }
+            })
+            .collect();
+
+        Self {
+            frame: frame.to_string(),
+            width,
+            height,
+            units: if percent { "percent" } else { "px" },
+            points,
+            session: SessionInfo::default(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+pub struct SvgExportOptions {
+    pub dpi: f32,
+    pub color_profile: ColorProfile,
+}
+
+impl Default for SvgExportOptions {
+    fn default() -> Self {
+        Self {
+            dpi: 96.0,
+            color_profile: ColorProfile::Srgb,
+        }
+    }
+}
+
+pub struct PngExportOptions {
+    pub dpi: f32,
+    pub gamma: f32, // Written to the gAMA chunk
+}
+
+impl Default for PngExportOptions {
+    fn default() -> Self {
+        Self { dpi: 96.0, gamma: 2.2 }
+    }
+}
+
+pub fn color_hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+// Render markers as an SVG document. The viewBox stays in canvas pixels while
+// width/height are given in millimetres so vector editors show the physical size.
+pub fn to_svg(
+    canvas_size: (f32, f32),
+    markers: &[Marker],
+    annotations: &[Annotation],
+    layers: &CanvasLayers,
+    options: &SvgExportOptions,
+) -> String {
+    let (width, height) = canvas_size;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" viewBox=\"0 0 {} {}\" color-profile=\"{}\">\n",
+        width / options.dpi * MM_PER_INCH,
+        height / options.dpi * MM_PER_INCH,
+        width,
+        height,
+        options.color_profile.svg_name()
+    );
+    if !layers.grid_above_image {
+        svg.push_str(&svg_grid(canvas_size, layers));
+    }
+    if let Some(layer) = &layers.image {
+        svg.push_str(&svg_image(layer));
+    }
+    if layers.grid_above_image {
+        svg.push_str(&svg_grid(canvas_size, layers));
+    }
+    for marker in markers {
+        svg.push_str(&format!(
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{}\"/>\n",
+            marker.position.x,
+            marker.position.y,
+            color_hex(marker.color)
+        ));
+    }
+    for annotation in annotations {
+        svg.push_str(&format!(
+            "  <path d=\"{}\" fill=\"none\" stroke=\"{}\"/>\n",
+            annotation.svg_path(),
+            color_hex(ANNOTATION_COLOR)
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Grid lines (or dots) over the canvas as an SVG group, empty without a grid
+fn svg_grid(canvas_size: (f32, f32), layers: &CanvasLayers) -> String {
+    let (grid, color) = match layers.grid {
+        Some(grid) => grid,
+        None => return String::new(),
+    };
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let opacity = a as f32 / 255.0;
+    let area = Rect::from_min_size(Pos2::ZERO, egui::vec2(canvas_size.0, canvas_size.1));
+    // One SVG unit per canvas px, thinned out like the PNG grid
+    let (zoom, min_spacing, max_spacing) = (1.0, 5.0, f32::INFINITY);
+    let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+    let dash = if grid.style() == GridStyle::Dashed { " stroke-dasharray=\"4 4\"" } else { "" };
+    // Rotated grids run past the canvas, so clip them to it
+    let mut svg = format!(
+        "  <clipPath id=\"grid-clip\"><rect width=\"{}\" height=\"{}\"/></clipPath>\n  \
+         <g fill=\"{}\" stroke=\"{}\" stroke-width=\"1\"{} clip-path=\"url(#grid-clip)\">\n",
+        canvas_size.0, canvas_size.1, hex, hex, dash
+    );
+    let minor_opacity = |major: bool| if major { opacity } else { opacity / 2.0 };
+    if grid.style() == GridStyle::Dots {
+        for (pos, major) in grid.dots(area, zoom, min_spacing, max_spacing) {
+            svg.push_str(&format!(
+                "    <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill-opacity=\"{}\"/>\n",
+                pos.x,
+                pos.y,
+                if major { 1.5 } else { 1.0 },
+                minor_opacity(major)
+            ));
+        }
+    } else {
+        for line in grid.lines(area, zoom, min_spacing, max_spacing) {
+            svg.push_str(&format!(
+                "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke-opacity=\"{}\"/>\n",
+                line.from.x,
+                line.from.y,
+                line.to.x,
+                line.to.y,
+                minor_opacity(line.major)
+            ));
+        }
+    }
+    svg.push_str("  </g>\n");
+    svg
+}
+
+// The background image embedded as a PNG data URI. Gamma is baked into the
+// pixels; the blend mode and opacity are left to the SVG renderer.
+fn svg_image(layer: &ImageLayer) -> String {
+    let table = gamma_table(layer.gamma);
+    let mut pixels = layer.pixels.clone();
+    for pixel in pixels.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = table[*channel as usize];
+        }
+    }
+    let mut png = Vec::new();
+    if pixels.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).is_err() {
+        return String::new();
+    }
+    let blend = match layer.mode {
+        CompositeMode::Normal => String::new(),
+        mode => format!(" style=\"mix-blend-mode: {}\"", mode.css_name()),
+    };
+    format!(
+        "  <image width=\"{}\" height=\"{}\" opacity=\"{}\"{} href=\"data:image/png;base64,{}\"/>\n",
+        pixels.width(),
+        pixels.height(),
+        layer.opacity,
+        blend,
+        base64_encode(&png)
+    )
+}
+
+// Standard base64 with padding, as used in data URIs
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Standalone SVG the size of the canvas with a line through `markers` in
+/// order, a closed <polygon> with `close_path`, else a <polyline>. Coordinates
+/// are canvas pixels from the top-left.
+pub fn to_svg_outline(canvas_size: (f32, f32), markers: &[Marker], close_path: bool, circles: bool) -> String {
+    let (width, height) = canvas_size;
+    let mut svg = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        width, height, width, height
+    );
+    let points: Vec<String> = markers
+        .iter()
+        .map(|marker| format!("{},{}", marker.position.x, marker.position.y))
+        .collect();
+    svg.push_str(&format!(
+        "  <{} points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+        if close_path { "polygon" } else { "polyline" },
+        points.join(" "),
+        color_hex(ANNOTATION_COLOR)
+    ));
+    if circles {
+        for marker in markers {
+            svg.push_str(&format!(
+                "  <circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{}\"/>\n",
+                marker.position.x,
+                marker.position.y,
+                color_hex(marker.color)
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Background image drawn at 1 image px per canvas unit, blended like on screen
+#[derive(Clone, Copy)]
+pub struct ImageLayer<'a> {
+    pub pixels: &'a RgbaImage,
+    pub mode: CompositeMode,
+    pub opacity: f32, // 0 to 1
+    pub gamma: f32,
+}
+
+// What's drawn on the canvas underneath the markers
+#[derive(Clone, Copy)]
+pub struct CanvasLayers<'a> {
+    pub grid: Option<(&'a Grid, Color32)>, // Grid and line color
+    pub image: Option<ImageLayer<'a>>,
+    pub grid_above_image: bool,
+}
+
+impl Default for CanvasLayers<'_> {
+    fn default() -> Self {
+        Self {
+            grid: None,
+            image: None,
+            grid_above_image: true,
+        }
+    }
+}
+
+// Describes where the canvas lands in a rendered image and what to draw
+pub struct RenderView<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub canvas_origin: Pos2, // Image position of canvas (0, 0)
+    pub zoom: f32,           // Image pixels per canvas unit
+    pub marker_radius: f32,
+    pub background: Color32,
+    pub border: Option<Color32>,
+    pub layers: CanvasLayers<'a>,
+    pub labels: Vec<String>,          // Text right of each marker, in marker order; empty to skip
+    pub label_color: Color32,
+    pub label_size: f32, // Font size in image pixels
+}
+
+// Alpha-blend a premultiplied color onto a pixel
+fn blend_pixel(image: &mut RgbaImage, x: u32, y: u32, color: Color32) {
+    let pixel = image.get_pixel_mut(x, y);
+    let inverse_alpha = 255 - color.a() as u32;
+    let src = [color.r(), color.g(), color.b(), color.a()];
+    for (channel, src) in pixel.0.iter_mut().zip(src) {
+        *channel = (src as u32 + *channel as u32 * inverse_alpha / 255).min(255) as u8;
+    }
+}
+
+fn fill_rect(image: &mut RgbaImage, rect: Rect, color: Color32) {
+    let min_x = rect.min.x.round().max(0.0) as u32;
+    let min_y = rect.min.y.round().max(0.0) as u32;
+    let max_x = rect.max.x.round().min(image.width() as f32).max(0.0) as u32;
+    let max_y = rect.max.y.round().min(image.height() as f32).max(0.0) as u32;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            blend_pixel(image, x, y, color);
+        }
+    }
+}
+
+fn fill_circle(image: &mut RgbaImage, center: Pos2, radius: f32, color: Color32) {
+    let bounds = Rect::from_center_size(center, egui::vec2(radius, radius) * 2.0);
+    let min_x = bounds.min.x.floor().max(0.0) as u32;
+    let min_y = bounds.min.y.floor().max(0.0) as u32;
+    let max_x = bounds.max.x.ceil().min(image.width() as f32).max(0.0) as u32;
+    let max_y = bounds.max.y.ceil().min(image.height() as f32).max(0.0) as u32;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let dx = x as f32 + 0.5 - center.x;
+            let dy = y as f32 + 0.5 - center.y;
+            if dx * dx + dy * dy <= radius * radius {
+                blend_pixel(image, x, y, color);
+            }
+        }
+    }
+}
+
+// One pixel wide line, stepping a pixel at a time along its longer axis so
+// no pixel is blended twice. With `dash`, alternating dashes and gaps of that
+// length are drawn. Only pixels inside `clip` are touched.
+fn draw_line(image: &mut RgbaImage, from: Pos2, to: Pos2, clip: Rect, dash: Option<f32>, color: Color32) {
+    let image_rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(image.width() as f32, image.height() as f32));
+    let clip = clip.intersect(image_rect);
+    let delta = to - from;
+    let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.0) as u32;
+    let length = delta.length();
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        if let Some(dash) = dash {
+            if (t * length / dash) as u32 % 2 == 1 {
+                continue;
+            }
+        }
+        let pos = from + delta * t;
+        // Half-open like pixel ranges, so a line on the far edge stays outside
+        if pos.x >= clip.min.x && pos.x < clip.max.x && pos.y >= clip.min.y && pos.y < clip.max.y {
+            blend_pixel(image, pos.x as u32, pos.y as u32, color);
+        }
+    }
+}
+
+// Outline a rect with a stroke centered on its edges
+fn stroke_rect(image: &mut RgbaImage, rect: Rect, width: f32, color: Color32) {
+    let outer = rect.expand(width / 2.0);
+    let inner = rect.shrink(width / 2.0);
+    let edges = [
+        Rect::from_min_max(outer.min, Pos2::new(outer.max.x, inner.min.y)),
+        Rect::from_min_max(Pos2::new(outer.min.x, inner.max.y), outer.max),
+        Rect::from_min_max(Pos2::new(outer.min.x, inner.min.y), Pos2::new(inner.min.x, inner.max.y)),
+        Rect::from_min_max(Pos2::new(inner.max.x, inner.min.y), Pos2::new(outer.max.x, inner.max.y)),
+    ];
+    for edge in edges {
+        fill_rect(image, edge, color);
+    }
+}
+
+// Software renderer shared by image export and the viewport snapshot
+pub fn render_image(view: &RenderView, canvas_size: (f32, f32), markers: &[Marker]) -> RgbaImage {
+    let (width, height) = canvas_size;
+    let background = view.background;
+    let mut image = RgbaImage::from_pixel(
+        view.width,
+        view.height,
+        Rgba([background.r(), background.g(), background.b(), background.a()]),
+    );
+    let canvas_rect = Rect::from_min_size(view.canvas_origin, egui::vec2(width, height) * view.zoom);
+
+    if !view.layers.grid_above_image {
+        draw_grid(&mut image, view, canvas_rect);
+    }
+    if let Some(layer) = &view.layers.image {
+        draw_image_layer(&mut image, layer, view, canvas_rect);
+    }
+    if view.layers.grid_above_image {
+        draw_grid(&mut image, view, canvas_rect);
+    }
+
+    if let Some(color) = view.border {
+        stroke_rect(&mut image, canvas_rect, 2.0, color);
+    }
+
+    for marker in markers {
+        let center = canvas_rect.min + marker.position.to_vec2() * view.zoom;
+        fill_circle(&mut image, center, view.marker_radius, marker.color);
+    }
+
+    if let Some(font) = default_font().filter(|_| !view.labels.is_empty()) {
+        for (marker, label) in markers.iter().zip(&view.labels) {
+            let center = canvas_rect.min + marker.position.to_vec2() * view.zoom;
+            // Same offset from the dot as on screen, in image pixels
+            let left = center.x + view.marker_radius * 2.0;
+            let color = if marker.ghost { view.label_color.gamma_multiply(0.3) } else { view.label_color };
+            draw_text(&mut image, &font, view.label_size, left, center.y, label, color);
+        }
+    }
+
+    image
+}
+
+fn draw_grid(image: &mut RgbaImage, view: &RenderView, canvas_rect: Rect) {
+    let (grid, color) = match view.layers.grid {
+        Some(grid) => grid,
+        None => return,
+    };
+    let minor_color = color.gamma_multiply(0.5);
+    let to_image = |pos: Pos2| canvas_rect.min + pos.to_vec2() * view.zoom;
+    let area = Rect::from_min_size(Pos2::ZERO, canvas_rect.size() / view.zoom);
+    // Lines are thinned out like on screen but never subdivided
+    let (min_spacing, max_spacing) = (5.0, f32::INFINITY);
+    if grid.style() == GridStyle::Dots {
+        for (pos, major) in grid.dots(area, view.zoom, min_spacing, max_spacing) {
+            let (radius, color) = if major { (1.5, color) } else { (1.0, minor_color) };
+            fill_circle(image, to_image(pos), radius, color);
+        }
+        return;
+    }
+    let dash = if grid.style() == GridStyle::Dashed { Some(4.0) } else { None };
+    for line in grid.lines(area, view.zoom, min_spacing, max_spacing) {
+        let color = if line.major { color } else { minor_color };
+        draw_line(image, to_image(line.from), to_image(line.to), canvas_rect, dash, color);
+    }
+}
+
+// Nearest-neighbour scaled like the on-screen texture and clipped to the canvas
+fn draw_image_layer(image: &mut RgbaImage, layer: &ImageLayer, view: &RenderView, canvas_rect: Rect) {
+    let source = layer.pixels;
+    let image_rect = Rect::from_min_size(
+        view.canvas_origin,
+        egui::vec2(source.width() as f32, source.height() as f32) * view.zoom,
+    );
+    let bounds = image_rect.intersect(canvas_rect);
+    let min_x = bounds.min.x.round().max(0.0) as u32;
+    let min_y = bounds.min.y.round().max(0.0) as u32;
+    let max_x = bounds.max.x.round().min(image.width() as f32).max(0.0) as u32;
+    let max_y = bounds.max.y.round().min(image.height() as f32).max(0.0) as u32;
+    let table = gamma_table(layer.gamma);
+    for y in min_y..max_y {
+        let source_y = ((y as f32 + 0.5 - view.canvas_origin.y) / view.zoom) as u32;
+        for x in min_x..max_x {
+            let source_x = ((x as f32 + 0.5 - view.canvas_origin.x) / view.zoom) as u32;
+            let [r, g, b, a] = source.get_pixel(source_x.min(source.width() - 1), source_y.min(source.height() - 1)).0;
+            let corrected = Color32::from_rgb(table[r as usize], table[g as usize], table[b as usize]);
+            let blended = blend_image(view.background, corrected, layer.mode);
+            let alpha = (a as f32 * layer.opacity).round() as u8;
+            let color = Color32::from_rgba_unmultiplied(blended.r(), blended.g(), blended.b(), alpha);
+            blend_pixel(image, x, y, color);
+        }
+    }
+}
+
+// egui's bundled UI font, so exported text matches the on-screen labels
+fn default_font() -> Option<FontArc> {
+    let fonts = egui::FontDefinitions::default();
+    let data = fonts.font_data.get("Ubuntu-Light")?;
+    FontArc::try_from_vec(data.font.to_vec()).ok()
+}
+
+fn text_width(font: &FontArc, size: f32, text: &str) -> f32 {
+    let font = font.as_scaled(PxScale::from(size));
+    text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+}
+
+// Draw a single line of text with its left edge at `left` and its vertical
+// center at `center_y`
+fn draw_text(image: &mut RgbaImage, font: &FontArc, size: f32, left: f32, center_y: f32, text: &str, color: Color32) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let baseline = center_y + (scaled.ascent() + scaled.descent()) / 2.0;
+    let mut x = left;
+    for c in text.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        let glyph = glyph_id.with_scale_and_position(size, ab_glyph::point(x, baseline));
+        x += scaled.h_advance(glyph_id);
+        if let Some(outline) = font.outline_glyph(glyph) {
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i32 + gx as i32;
+                let py = bounds.min.y as i32 + gy as i32;
+                if px >= 0 && py >= 0 && (px as u32) < image.width() && (py as u32) < image.height() {
+                    blend_pixel(image, px as u32, py as u32, color.gamma_multiply(coverage));
+                }
+            });
+        }
+    }
+}
+
+// Black on light backgrounds, white on dark ones
+fn contrasting_text_color(background: Color32) -> Color32 {
+    let luminance = 0.299 * background.r() as f32 + 0.587 * background.g() as f32 + 0.114 * background.b() as f32;
+    if luminance > 128.0 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    }
+}
+
+// YYYY-MM-DD in UTC, from days since the Unix epoch (Hinnant's civil_from_days)
+pub fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Rasterize markers with the given margin, scale and annotations. Every size
+// is multiplied by the layout scale so the result looks the same at any scale.
+pub fn render_png(
+    name: &str,
+    canvas_size: (f32, f32),
+    markers: &[Marker],
+    layout: &PngLayout,
+    layers: CanvasLayers,
+) -> RgbaImage {
+    let (width, height) = layout.output_size(canvas_size);
+    let scale = layout.scale;
+    let view = RenderView {
+        width,
+        height,
+        canvas_origin: Pos2::new(layout.margin * scale, layout.margin * scale),
+        zoom: scale,
+        marker_radius: 5.0 * scale,
+        background: layout.background,
+        border: None,
+        layers,
+        labels: Vec::new(), // Drawn below in the PNG's own label style
+        label_color: Color32::TRANSPARENT,
+        label_size: 0.0,
+    };
+    let mut image = render_image(&view, canvas_size, markers);
+
+    let font = match default_font() {
+        Some(font) if layout.labels || layout.title => font,
+        _ => return image,
+    };
+    let text_color = contrasting_text_color(layout.background);
+
+    if layout.labels {
+        for marker in markers {
+            let center = view.canvas_origin + marker.position.to_vec2() * scale;
+            let label = format!(
+                "({}, {})",
+                marker.system_position.x as i32, marker.system_position.y as i32
+            );
+            let color = if marker.ghost { text_color.gamma_multiply(0.3) } else { text_color };
+            draw_text(&mut image, &font, LABEL_FONT_SIZE * scale, center.x + 10.0 * scale, center.y, &label, color);
+        }
+    }
+
+    if layout.title {
+        let title = format!("{} \u{2014} {}", name, format_date(SystemTime::now()));
+        let size = TITLE_FONT_SIZE * scale;
+        let band_top = (canvas_size.1 + 2.0 * layout.margin) * scale;
+        let left = (width as f32 - text_width(&font, size, &title)) / 2.0;
+        draw_text(&mut image, &font, size, left, band_top + TITLE_BAND_HEIGHT * scale / 2.0, &title, text_color);
+    }
+
+    image
+}
+
+// Encode an image as PNG, recording the DPI in the pHYs chunk
+pub fn encode_png(image: &RgbaImage, options: &PngExportOptions) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels_per_meter = (options.dpi / METERS_PER_INCH).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: pixels_per_meter,
+            yppu: pixels_per_meter,
+            unit: png::Unit::Meter,
+        }));
+        // gAMA stores the encoding exponent, i.e. 1 / display gamma
+        encoder.set_source_gamma(png::ScaledFloat::new(1.0 / options.gamma));
+
+        let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+        writer
+            .write_image_data(image.as_raw())
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(bytes)
+}
+
+// Track sizes between consecutive grid lines, in canvas px
+fn grid_tracks(mut lines: Vec<f32>) -> Vec<f32> {
+    lines.sort_by(|a, b| a.total_cmp(b));
+    lines.dedup();
+    lines.windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+fn format_tracks(tracks: &[f32], fractional: bool) -> String {
+    if tracks.is_empty() {
+        return "none".to_string();
+    }
+    let total: f32 = tracks.iter().sum();
+    tracks
+        .iter()
+        .map(|&track| {
+            if fractional {
+                // Scaled so evenly spaced lines come out as 1fr each
+                let fr = track / total * tracks.len() as f32;
+                format!("{}fr", (fr * 100.0).round() / 100.0)
+            } else {
+                format!("{}px", track.round())
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// CSS grid template from column/row line markers, ready to paste into dev tools
+pub fn to_css_grid(markers: &[Marker], fractional: bool) -> String {
+    let lines_of = |kind: MarkerKind, axis: fn(Pos2) -> f32| {
+        markers
+            .iter()
+            .filter(|marker| marker.kind == kind)
+            .map(|marker| axis(marker.position).round())
+            .collect::<Vec<f32>>()
+    };
+    let columns = grid_tracks(lines_of(MarkerKind::ColumnLine, |pos| pos.x));
+    let rows = grid_tracks(lines_of(MarkerKind::RowLine, |pos| pos.y));
+    format!(
+        ".grid {{\n  display: grid;\n  grid-template-columns: {};\n  grid-template-rows: {};\n}}\n",
+        format_tracks(&columns, fractional),
+        format_tracks(&rows, fractional)
+    )
+}
+
+/// `clip-path: polygon(32.0% 10.0%, ...);` with canvas positions as
+/// percentages of the canvas size. CSS measures from the top-left, whatever
+/// the picker's origin.
+pub fn to_clip_path(positions: &[Pos2], canvas_size: (f32, f32), decimals: usize) -> String {
+    let points: Vec<String> = positions
+        .iter()
+        .map(|pos| {
+            let percent = |value: f32, size: f32| coordinate::format_fraction(value / size * 100.0, decimals);
+            format!("{}% {}%", percent(pos.x, canvas_size.0), percent(pos.y, canvas_size.1))
+        })
+        .collect();
+    format!("clip-path: polygon({});", points.join(", "))
+}
+
+// The label, else the first line of the note, else "Marker N"
+fn marker_label(index: usize, marker: &Marker) -> String {
+    if let Some(label) = marker.label_text() {
+        return label.to_string();
+    }
+    match marker.note.lines().next() {
+        Some(line) if !line.trim().is_empty() => line.trim().to_string(),
+        _ => format!("Marker {}", index + 1),
+    }
+}
+
+// Android resource names allow only lowercase letters, digits and underscores
+// and must not start with a digit
+fn android_resource_name(label: &str) -> String {
+    let name: String = label
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+// values/*.xml with an `_x` and `_y` <dimen> per marker, top-left based like
+// the JSON exports. Column and row lines only get the axis they define.
+pub fn to_android_point_resources(
+    canvas_size: (f32, f32),
+    markers: &[Marker],
+    transform: OutputTransform,
+    dp_scale: f32,
+) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n");
+    let mut used_names = HashSet::new();
+    for (i, marker) in markers.iter().enumerate() {
+        let mut name = android_resource_name(&marker_label(i, marker));
+        // Notes can repeat, but resource names must not. The numbered
+        // fallback can itself match another label, so keep counting up.
+        let base = name.clone();
+        let mut suffix = i + 1;
+        while !used_names.insert(name.clone()) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        let position = transform.apply(marker.position, canvas_size);
+        let axes = match marker.kind {
+            MarkerKind::Point => vec![("x", position.x), ("y", position.y)],
+            MarkerKind::ColumnLine => vec![("x", position.x)],
+            MarkerKind::RowLine => vec![("y", position.y)],
+        };
+        for (axis, value) in axes {
+            let dp = (value * dp_scale * 100.0).round() / 100.0;
+            xml.push_str(&format!("    <dimen name=\"{}_{}\">{}dp</dimen>\n", name, axis, dp));
+        }
+    }
+    xml.push_str("</resources>\n");
+    xml
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CsvDelimiter {
+    Comma,
+    Tab,
+    Semicolon, // What spreadsheets expect in locales with a decimal comma
+}
+
+impl CsvDelimiter {
+    pub const ALL: [CsvDelimiter; 3] = [CsvDelimiter::Comma, CsvDelimiter::Tab, CsvDelimiter::Semicolon];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CsvDelimiter::Comma => "Comma",
+            CsvDelimiter::Tab => "Tab",
+            CsvDelimiter::Semicolon => "Semicolon",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CsvDelimiter::Comma => ",",
+            CsvDelimiter::Tab => "\t",
+            CsvDelimiter::Semicolon => ";",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    Index,
+    X, // In the user's coordinate system
+    Y,
+    CanvasX, // Top-left based canvas pixels
+    CanvasY,
+    Color,
+    Label,
+}
+
+impl CsvColumn {
+    pub const ALL: [CsvColumn; 7] = [
+        CsvColumn::Index,
+        CsvColumn::X,
+        CsvColumn::Y,
+        CsvColumn::CanvasX,
+        CsvColumn::CanvasY,
+        CsvColumn::Color,
+        CsvColumn::Label,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            CsvColumn::Index => "index",
+            CsvColumn::X => "x",
+            CsvColumn::Y => "y",
+            CsvColumn::CanvasX => "canvas_x",
+            CsvColumn::CanvasY => "canvas_y",
+            CsvColumn::Color => "color",
+            CsvColumn::Label => "label",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CsvOptions {
+    pub delimiter: CsvDelimiter,
+    pub include_header: bool,
+    pub columns: [bool; 7], // Whether each of CsvColumn::ALL is written
+    pub integers: bool,     // Round coordinates to whole pixels
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: CsvDelimiter::Comma,
+            include_header: true,
+            columns: [true; 7],
+            integers: false,
+        }
+    }
+}
+
+// Quotes free text that would otherwise break the row
+fn csv_field(text: &str, delimiter: &str) -> String {
+    if text.contains(delimiter) || text.contains('"') || text.contains('\n') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// One row per marker, with 1-based indices matching the marker list.
+/// `positions` are already in the user's coordinate system.
+pub fn to_csv(markers: &[Marker], positions: &[Pos2], options: &CsvOptions) -> String {
+    let columns: Vec<CsvColumn> = CsvColumn::ALL
+        .into_iter()
+        .zip(options.columns)
+        .filter(|(_, enabled)| *enabled)
+        .map(|(column, _)| column)
+        .collect();
+    let delimiter = options.delimiter.as_str();
+    let number = |value: f32| {
+        if options.integers {
+            value.round().to_string()
+        } else {
+            value.to_string()
+        }
+    };
+
+    let mut csv = String::new();
+    if options.include_header {
+        let headers: Vec<&str> = columns.iter().map(CsvColumn::header).collect();
+        csv.push_str(&headers.join(delimiter));
+        csv.push('\n');
+    }
+    for (i, (marker, position)) in markers.iter().zip(positions).enumerate() {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match column {
+                CsvColumn::Index => (i + 1).to_string(),
+                CsvColumn::X => number(position.x),
+                CsvColumn::Y => number(position.y),
+                CsvColumn::CanvasX => number(marker.position.x),
+                CsvColumn::CanvasY => number(marker.position.y),
+                CsvColumn::Color => color_hex(marker.color),
+                CsvColumn::Label => csv_field(marker.label_text().unwrap_or_default(), delimiter),
+            })
+            .collect();
+        csv.push_str(&fields.join(delimiter));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// A number as a source code literal: `412` for integers, otherwise always
+/// with a decimal point (`412.0`) so typed languages read it as a float
+pub fn code_number(value: f32, integer: bool, decimals: usize) -> String {
+    if integer {
+        format!("{}", value.round() as i64)
+    } else {
+        format!("{:.*}", decimals.max(1), value)
+    }
+}
+
+// A marker ready for a code export, with coordinates already as literals
+pub struct CodePoint {
+    pub x: String,
+    pub y: String,
+    pub label: Option<String>,
+}
+
+// Double-quoted string literal. JSON escaping is also valid in Rust, Python and JS.
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+// {"x": 412, "y": 391}, or [412, 391] with `arrays`. Coordinates keep the
+// digits of their literals; labels are null when unset.
+fn json_point(point: &CodePoint, arrays: bool, with_labels: bool) -> serde_json::Value {
+    let number = |literal: &str| serde_json::from_str(literal).unwrap_or(serde_json::Value::Null);
+    let (x, y) = (number(&point.x), number(&point.y));
+    let label = match &point.label {
+        Some(label) => serde_json::Value::from(label.as_str()),
+        None => serde_json::Value::Null,
+    };
+    match (arrays, with_labels) {
+        (false, false) => serde_json::json!({ "x": x, "y": y }),
+        (false, true) => serde_json::json!({ "x": x, "y": y, "label": label }),
+        (true, false) => serde_json::json!([x, y]),
+        (true, true) => serde_json::json!([x, y, label]),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    C,
+    Json,
+}
+
+impl CodeLanguage {
+    pub const ALL: [CodeLanguage; 5] = [
+        CodeLanguage::Rust,
+        CodeLanguage::Python,
+        CodeLanguage::JavaScript,
+        CodeLanguage::C,
+        CodeLanguage::Json,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CodeLanguage::Rust => "Rust array",
+            CodeLanguage::Python => "Python list",
+            CodeLanguage::JavaScript => "JavaScript array",
+            CodeLanguage::C => "C array",
+            CodeLanguage::Json => "JSON",
+        }
+    }
+
+    pub fn example(&self) -> &'static str {
+        match self {
+            CodeLanguage::Rust => "let coords: &[(i32, i32)] = &[(412, 391), ...];",
+            CodeLanguage::Python => "coords = [(412, 391), ...]",
+            CodeLanguage::JavaScript => "const coords = [{ x: 412, y: 391 }, ...];",
+            CodeLanguage::C => "const int coords[][2] = {{412, 391}, ...};",
+            CodeLanguage::Json => "[{\"x\":412,\"y\":391},...]",
+        }
+    }
+}
+
+// Marker lists as source code, one method per language. Where the language
+// has comments, labelled lists are laid out one point per line with the
+// label as a trailing comment.
+pub struct CodeExporter {
+    pub points: Vec<CodePoint>,
+    pub integers: bool, // Literals are whole numbers, which picks the element type
+}
+
+impl CodeExporter {
+    pub fn new(points: Vec<CodePoint>, integers: bool) -> Self {
+        Self { points, integers }
+    }
+
+    // `items` between `open` and `close` on one line, or one per line when a
+    // point has a label to put in a `comment`
+    fn list(&self, items: Vec<String>, open: &str, close: &str, comment: Option<&str>) -> String {
+        let comment = match comment {
+            Some(comment) if self.points.iter().any(|p| p.label.is_some()) => comment,
+            _ => return format!("{}{}{}", open, items.join(", "), close),
+        };
+        let mut lines = vec![open.to_string()];
+        for (item, point) in items.iter().zip(&self.points) {
+            lines.push(match &point.label {
+                // A line break would end the comment early
+                Some(label) => format!("    {}, {} {}", item, comment, label.replace(['\r', '\n'], " ")),
+                None => format!("    {},", item),
+            });
+        }
+        lines.push(close.to_string());
+        lines.join("\n")
+    }
+
+    /// `let coords: &[(i32, i32)] = &[(412, 391), ...];`, or `egui::Pos2`
+    /// values for floats
+    pub fn rust(&self) -> String {
+        let (element, items): (&str, Vec<String>) = if self.integers {
+            ("(i32, i32)", self.points.iter().map(|p| format!("({}, {})", p.x, p.y)).collect())
+        } else {
+            ("egui::Pos2", self.points.iter().map(|p| format!("egui::pos2({}, {})", p.x, p.y)).collect())
+        };
+        format!("let coords: &[{}] = {};", element, self.list(items, "&[", "]", Some("//")))
+    }
+
+    /// `coords = [(412, 391), ...]`, or `[{"x": 412, "y": 391}, ...]` with
+    /// `dicts`. With `with_labels` the label is a third tuple element or a
+    /// "label" key, None when unset, instead of a comment.
+    pub fn python(&self, dicts: bool, with_labels: bool) -> String {
+        let items = self
+            .points
+            .iter()
+            .map(|p| {
+                let label = match &p.label {
+                    Some(label) => quote(label),
+                    None => "None".to_string(),
+                };
+                match (dicts, with_labels) {
+                    (false, false) => format!("({}, {})", p.x, p.y),
+                    (false, true) => format!("({}, {}, {})", p.x, p.y, label),
+                    (true, false) => format!("{{\"x\": {}, \"y\": {}}}", p.x, p.y),
+                    (true, true) => format!("{{\"x\": {}, \"y\": {}, \"label\": {}}}", p.x, p.y, label),
+                }
+            })
+            .collect();
+        let comment = if with_labels { None } else { Some("#") };
+        format!("coords = {}", self.list(items, "[", "]", comment))
+    }
+
+    /// `const coords = [{ x: 412, y: 391 }, ...];`
+    pub fn javascript(&self) -> String {
+        let items = self.points.iter().map(|p| format!("{{ x: {}, y: {} }}", p.x, p.y)).collect();
+        format!("const coords = {};", self.list(items, "[", "]", Some("//")))
+    }
+
+    /// `const int coords[][2] = {{412, 391}, ...};`, or `float` with `f`
+    /// suffixed literals. No markers gives `{}`, which only C23 accepts.
+    pub fn c(&self) -> String {
+        let (element, suffix) = if self.integers { ("int", "") } else { ("float", "f") };
+        let items = self
+            .points
+            .iter()
+            .map(|p| format!("{{{}{}, {}{}}}", p.x, suffix, p.y, suffix))
+            .collect();
+        format!("const {} coords[][2] = {};", element, self.list(items, "{", "}", Some("//")))
+    }
+
+    /// Compact JSON array, e.g. `[{"x":412,"y":391},...]` or `[[412,391],...]`.
+    /// JSON has no comments, so labels only appear with `with_labels`.
+    pub fn json(&self, arrays: bool, with_labels: bool) -> String {
+        let items = self.points.iter().map(|p| json_point(p, arrays, with_labels)).collect();
+        serde_json::Value::Array(items).to_string()
+    }
+
+    /// The first point alone, e.g. `{"x":412,"y":391}`, for copying one marker
+    pub fn json_single(&self, arrays: bool, with_labels: bool) -> String {
+        match self.points.first() {
+            Some(point) => json_point(point, arrays, with_labels).to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+// Write next to the destination and rename over it, so a failed write never
+// leaves a truncated file behind
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    std::fs::write(&temp_path, contents)?;
+    let result = std::fs::rename(&temp_path, path);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Clickable image map with a circle <area> per point marker, in image pixels.
+// Column/row line markers have no area equivalent and are left out.
+pub fn to_html_area_map(canvas_size: (f32, f32), markers: &[Marker], radius: u32) -> String {
+    let mut html = format!(
+        "<img src=\"screenshot.png\" width=\"{}\" height=\"{}\" alt=\"\" usemap=\"#coordinate_map\">\n",
+        canvas_size.0.round(),
+        canvas_size.1.round()
+    );
+    html.push_str("<map name=\"coordinate_map\">\n");
+    for (i, marker) in markers.iter().enumerate() {
+        if marker.kind != MarkerKind::Point {
+            continue;
+        }
+        let label = marker_label(i, marker);
+        html.push_str(&format!(
+            "  <area shape=\"circle\" coords=\"{},{},{}\" href=\"#\" alt=\"{}\">\n",
+            marker.position.x.round(),
+            marker.position.y.round(),
+            radius,
+            escape_html(&label)
+        ));
+    }
+    html.push_str("</map>\n");
+    html
+}
+
+// Session info as a CSS comment block, empty when there's nothing to say
+fn css_header(session: &SessionInfo) -> String {
+    let lines = session.header_lines();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut header = String::from("/*\n");
+    for line in lines {
+        if line.is_empty() {
+            header.push_str(" *\n");
+        } else {
+            // Keep user text from closing the comment early
+            header.push_str(&format!(" * {}\n", line.replace("*/", "* /")));
+        }
+    }
+    header.push_str(" */\n");
+    header
+}
+
+// Serialize markers in the given format
+pub fn export_markers(
+    format: ExportFormat,
+    name: &str,
+    canvas_size: (f32, f32),
+    markers: &[Marker],
+    settings: &ExportSettings,
+) -> Result<Vec<u8>, String> {
+    match format {
+        ExportFormat::FigmaPixels => {
+            let mut export = FigmaExport::new(name, canvas_size, markers, false, settings.output_transform);
+            export.session = settings.session.clone();
+            Ok(export.to_json().into_bytes())
+        }
+        ExportFormat::FigmaPercent => {
+            let mut export = FigmaExport::new(name, canvas_size, markers, true, settings.output_transform);
+            export.session = settings.session.clone();
+            Ok(export.to_json().into_bytes())
+        }
+        ExportFormat::Svg => {
+            let options = SvgExportOptions {
+                dpi: settings.dpi,
+                color_profile: settings.color_profile,
+            };
+            Ok(to_svg(canvas_size, markers, &settings.annotations, &settings.layers, &options).into_bytes())
+        }
+        ExportFormat::Png => {
+            let options = PngExportOptions {
+                dpi: settings.dpi,
+                gamma: settings.gamma,
+            };
+            let title = match settings.session.title.as_str() {
+                "" => name,
+                title => title,
+            };
+            let image = render_png(title, canvas_size, markers, &settings.png_layout, settings.layers);
+            encode_png(&image, &options)
+        }
+        ExportFormat::HtmlArea => Ok(to_html_area_map(canvas_size, markers, settings.html_area_radius).into_bytes()),
+        ExportFormat::AndroidPointResource => Ok(to_android_point_resources(
+            canvas_size,
+            markers,
+            settings.output_transform,
+            settings.android_dp_scale,
+        )
+        .into_bytes()),
+        ExportFormat::CssGrid => {
+            let css = css_header(&settings.session) + &to_css_grid(markers, settings.css_grid_fractional);
+            Ok(css.into_bytes())
+        }
+    }
+}
+
+// Make a group name safe to use as part of a file name on any platform
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let trimmed = sanitized.trim_matches('_');
+    if trimmed.is_empty() {
+        "group".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+// Build "{basename}_{group}.{extension}" for each group, adding numeric
+// suffixes when names collide after sanitizing (case-insensitively, since
+// not every file system is case-sensitive)
+pub fn split_file_names(basename: &str, group_names: &[&str], extension: &str) -> Vec<String> {
+    let mut used = HashSet::new();
+    group_names
+        .iter()
+        .map(|name| {
+            let stem = format!("{}_{}", basename, sanitize_file_name(name));
+            let mut candidate = stem.clone();
+            let mut suffix = 2;
+            while !used.insert(candidate.to_lowercase()) {
+                candidate = format!("{}_{}", stem, suffix);
+                suffix += 1;
+            }
+            format!("{}.{}", candidate, extension)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker_at(x: f32, y: f32) -> Marker {
+        Marker::new(Pos2::new(x, y), Pos2::new(x, y), Color32::RED, 0)
+    }
+
+    #[test]
+    fn figma_pixels_keep_canvas_coordinates() {
+        let markers = [marker_at(50.0, 25.0), marker_at(200.0, 100.0)];
+        let export = FigmaExport::new("Frame", (200.0, 100.0), &markers, false, OutputTransform::None);
+        assert_eq!((export.width, export.height, export.units), (200.0, 100.0, "px"));
+        assert_eq!(export.points[0].name, "Marker 1");
+        assert_eq!((export.points[0].x, export.points[0].y), (50.0, 25.0));
+        assert_eq!((export.points[1].x, export.points[1].y), (200.0, 100.0));
+    }
+
+    #[test]
+    fn figma_points_are_named_by_label() {
+        let mut labeled = marker_at(10.0, 10.0);
+        labeled.label = Some(" submit_button ".to_string());
+        let mut blank = marker_at(20.0, 20.0);
+        blank.label = Some("  ".to_string());
+        let export = FigmaExport::new("Frame", (100.0, 100.0), &[labeled, blank], false, OutputTransform::None);
+        assert_eq!(export.points[0].name, "submit_button");
+        assert_eq!(export.points[1].name, "Marker 2");
+    }
+
+    #[test]
+    fn figma_percent_is_relative_to_canvas_size() {
+        let markers = [marker_at(50.0, 25.0), marker_at(200.0, 100.0)];
+        let export = FigmaExport::new("Frame", (200.0, 100.0), &markers, true, OutputTransform::None);
+        assert_eq!(export.units, "percent");
+        assert_eq!((export.points[0].x, export.points[0].y), (25.0, 25.0));
+        assert_eq!((export.points[1].x, export.points[1].y), (100.0, 100.0));
+
+        let json: serde_json::Value = serde_json::from_str(&export.to_json()).unwrap();
+        assert_eq!(json["frame"], "Frame");
+        assert_eq!(json["units"], "percent");
+        assert_eq!(json["points"][0]["x"], 25.0);
+    }
+
+    #[test]
+    fn blend_modes_against_known_values() {
+        let base = Color32::from_rgb(255, 128, 0);
+        let overlay = Color32::from_rgb(128, 128, 128);
+        assert_eq!(blend_image(base, overlay, CompositeMode::Normal), overlay);
+        assert_eq!(blend_image(base, overlay, CompositeMode::Multiply), Color32::from_rgb(128, 64, 0));
+        assert_eq!(blend_image(base, overlay, CompositeMode::Screen), Color32::from_rgb(255, 192, 128));
+        assert_eq!(blend_image(base, overlay, CompositeMode::Overlay), Color32::from_rgb(255, 128, 0));
+        assert_eq!(blend_image(base, overlay, CompositeMode::Dodge), Color32::from_rgb(255, 255, 0));
+        assert_eq!(blend_image(base, overlay, CompositeMode::Burn), Color32::from_rgb(255, 2, 0));
+    }
+
+    #[test]
+    fn blend_modes_with_neutral_overlays() {
+        let base = Color32::from_rgb(40, 90, 200);
+        assert_eq!(blend_image(base, Color32::WHITE, CompositeMode::Multiply), base);
+        assert_eq!(blend_image(base, Color32::BLACK, CompositeMode::Screen), base);
+        assert_eq!(blend_image(base, Color32::BLACK, CompositeMode::Dodge), base);
+        assert_eq!(blend_image(base, Color32::WHITE, CompositeMode::Burn), base);
+    }
+
+    #[test]
+    fn composite_keeps_pixel_alpha() {
+        let image = RgbaImage::from_raw(1, 1, vec![255, 255, 255, 0]).unwrap();
+        let composited = composite_image(&image, Color32::from_rgb(20, 20, 20), CompositeMode::Multiply, 2.2);
+        assert_eq!(composited.pixels[0], Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn composite_applies_display_gamma() {
+        let image = RgbaImage::from_raw(3, 1, vec![0, 0, 0, 255, 128, 128, 128, 255, 255, 255, 255, 255]).unwrap();
+        let composite = |gamma| composite_image(&image, Color32::BLACK, CompositeMode::Normal, gamma).pixels;
+        assert_eq!(composite(2.2)[1], Color32::from_gray(128));
+        // Black and white stay put, midtones move
+        let brighter = composite(3.0);
+        assert_eq!((brighter[0], brighter[2]), (Color32::BLACK, Color32::WHITE));
+        assert!(brighter[1].r() > 128);
+        assert!(composite(1.0)[1].r() < 128);
+    }
+
+    #[test]
+    fn luminance_of_flat_images() {
+        assert_eq!(average_luminance(&RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]))), 0.0);
+        assert!((average_luminance(&RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]))) - 1.0).abs() < 1e-6);
+        // Transparent pixels don't pull the average down
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([255, 255, 255, 255]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        assert!((average_luminance(&image) - 1.0).abs() < 1e-6);
+    }
+
+    fn grid_and_image_view<'a>(pixels: &'a RgbaImage, grid: &'a Grid, grid_above_image: bool) -> RenderView<'a> {
+        RenderView {
+            width: 10,
+            height: 10,
+            canvas_origin: Pos2::ZERO,
+            zoom: 1.0,
+            marker_radius: 0.0,
+            background: Color32::BLACK,
+            border: None,
+            layers: CanvasLayers {
+                grid: Some((grid, Color32::RED)),
+                image: Some(ImageLayer {
+                    pixels,
+                    mode: CompositeMode::Normal,
+                    opacity: 1.0,
+                    gamma: DEFAULT_GAMMA,
+                }),
+                grid_above_image,
+            },
+            labels: Vec::new(),
+            label_color: Color32::TRANSPARENT,
+            label_size: 0.0,
+        }
+    }
+
+    #[test]
+    fn grid_layer_order_in_rendered_image() {
+        let pixels = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 255, 255]));
+        let grid = Grid::new(5.0, true);
+        let above = render_image(&grid_and_image_view(&pixels, &grid, true), (10.0, 10.0), &[]);
+        assert_eq!(above.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(above.get_pixel(2, 2).0, [0, 0, 255, 255]);
+        // An opaque image hides the grid underneath
+        let below = render_image(&grid_and_image_view(&pixels, &grid, false), (10.0, 10.0), &[]);
+        assert_eq!(below.get_pixel(0, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn png_export_draws_the_canvas_layers() {
+        let pixels = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 255, 255]));
+        let grid = Grid::new(5.0, true);
+        let layers = grid_and_image_view(&pixels, &grid, true).layers;
+        let image = render_png("Test", (10.0, 10.0), &[], &PngLayout::default(), layers);
+        assert_eq!(image.get_pixel(5, 2).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(2, 2).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn svg_export_layers_the_grid_against_the_image() {
+        let pixels = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let grid = Grid::new(5.0, true);
+        let options = SvgExportOptions::default();
+        for grid_above_image in [true, false] {
+            let mut layers = grid_and_image_view(&pixels, &grid, grid_above_image).layers;
+            layers.image.as_mut().unwrap().mode = CompositeMode::Multiply;
+            let svg = to_svg((10.0, 10.0), &[], &[], &layers, &options);
+            let (grid_at, image_at) = (svg.find("<g ").unwrap(), svg.find("<image ").unwrap());
+            assert_eq!(grid_at > image_at, grid_above_image);
+            assert!(svg.contains("mix-blend-mode: multiply"));
+            assert_eq!(svg.matches("<line ").count(), 6);
+        }
+        assert!(!to_svg((10.0, 10.0), &[], &[], &CanvasLayers::default(), &options).contains("<g "));
+    }
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_encode(b"Ma"), "TWE=");
+        assert_eq!(base64_encode(b"M"), "TQ==");
+        assert_eq!(base64_encode(&[0xff, 0xfe]), "//4=");
+        assert_eq!(base64_encode(b""), "");
+    }
+
+    #[test]
+    fn android_resource_names_stay_unique() {
+        let mut markers = [marker_at(1.0, 2.0), marker_at(3.0, 4.0), marker_at(5.0, 6.0), marker_at(7.0, 8.0)];
+        markers[0].label = Some("button_3".to_string());
+        markers[1].label = Some("button".to_string());
+        markers[2].label = Some("button".to_string());
+        markers[3].label = Some("button".to_string());
+        let xml = to_android_point_resources((100.0, 100.0), &markers, OutputTransform::None, 1.0);
+        // The third marker's numbered fallback "button_3" is already a label
+        assert!(xml.contains("<dimen name=\"button_3_x\">1dp</dimen>"));
+        assert!(xml.contains("<dimen name=\"button_x\">3dp</dimen>"));
+        assert!(xml.contains("<dimen name=\"button_4_x\">5dp</dimen>"));
+        assert!(xml.contains("<dimen name=\"button_5_x\">7dp</dimen>"));
+    }
+
+    #[test]
+    fn sanitize_replaces_path_characters() {
+        assert_eq!(sanitize_file_name("Nav/Header"), "Nav_Header");
+        assert_eq!(sanitize_file_name("..\\secret"), "secret");
+        assert_eq!(sanitize_file_name("v1.2 buttons"), "v1_2_buttons");
+        assert_eq!(sanitize_file_name("../.."), "group");
+    }
+
+    #[test]
+    fn sanitize_keeps_unicode_letters() {
+        assert_eq!(sanitize_file_name("Größe"), "Größe");
+        assert_eq!(sanitize_file_name("ボタン 1"), "ボタン_1");
+        assert_eq!(sanitize_file_name("⭐"), "group");
+    }
+
+    #[test]
+    fn split_names_suffix_collisions() {
+        let names = split_file_names("markers", &["a/b", "a.b", "A_B", "Größe"], "json");
+        assert_eq!(names, ["markers_a_b.json", "markers_a_b_2.json", "markers_A_B_3.json", "markers_Größe.json"]);
+    }
+}
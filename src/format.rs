@@ -0,0 +1,308 @@
+use egui::Color32;
+
+// High-contrast qualitative palette (ColorBrewer Set1 and Dark2)
+const GROUP_PALETTE: [Color32; 14] = [
+    Color32::from_rgb(228, 26, 28),
+    Color32::from_rgb(55, 126, 184),
+    Color32::from_rgb(77, 175, 74),
+    Color32::from_rgb(152, 78, 163),
+    Color32::from_rgb(255, 127, 0),
+    Color32::from_rgb(166, 86, 40),
+    Color32::from_rgb(247, 129, 191),
+    Color32::from_rgb(153, 153, 153),
+    Color32::from_rgb(27, 158, 119),
+    Color32::from_rgb(217, 95, 2),
+    Color32::from_rgb(117, 112, 179),
+    Color32::from_rgb(231, 41, 138),
+    Color32::from_rgb(102, 166, 30),
+    Color32::from_rgb(230, 171, 2),
+];
+
+// sRGB -> CIELAB (D65)
+fn to_lab(color: Color32) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(color.r()), linear(color.g()), linear(color.b()));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_distance(a: Color32, b: Color32) -> f32 {
+    let (a, b) = (to_lab(a), to_lab(b));
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Palette color farthest (in CIELAB) from every color already in use.
+/// Colors of deleted groups simply stop counting, so their slot frees up.
+pub fn next_group_color(existing_colors: &[Color32]) -> Color32 {
+    let nearest_distance = |candidate: Color32| {
+        existing_colors
+            .iter()
+            .map(|&existing| lab_distance(candidate, existing))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    // Ties keep the earlier palette entry, so an empty set starts at the top
+    let mut best = GROUP_PALETTE[0];
+    let mut best_distance = nearest_distance(best);
+    for &candidate in &GROUP_PALETTE[1..] {
+        let distance = nearest_distance(candidate);
+        if distance > best_distance {
+            best = candidate;
+            best_distance = distance;
+        }
+    }
+    best
+}
+
+pub const TEMPLATE_PLACEHOLDERS: [&str; 10] = ["i", "x", "y", "label", "group", "color", "nx", "ny", "w", "h"];
+
+// Values substituted into a copy template for one marker
+pub struct TemplateValues {
+    pub index: usize,
+    pub x: String,
+    pub y: String,
+    pub label: Option<String>, // Falls back to "Marker <index>"
+    pub group: String,
+    pub color: Color32,
+    pub normalized: (f32, f32),
+    pub canvas_size: (f32, f32),
+}
+
+impl TemplateValues {
+    fn get(&self, placeholder: &str) -> Option<String> {
+        let value = match placeholder {
+            "i" => self.index.to_string(),
+            "x" => self.x.clone(),
+            "y" => self.y.clone(),
+            "label" => match &self.label {
+                Some(label) => label.clone(),
+                None => format!("Marker {}", self.index),
+            },
+            "group" => self.group.clone(),
+            "color" => format!("#{:02x}{:02x}{:02x}", self.color.r(), self.color.g(), self.color.b()),
+            "nx" => format!("{:.4}", self.normalized.0),
+            "ny" => format!("{:.4}", self.normalized.1),
+            "w" => self.canvas_size.0.to_string(),
+            "h" => self.canvas_size.1.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+enum TemplatePart<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+// Length of the placeholder name at the start of `text`
+fn name_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+// Splits a template into text and `{name}` placeholders. `{{name}}` writes a
+// literal `{name}`. Every other brace is kept as text, so JSON-shaped templates
+// such as `{"p": {"x": {x}}}` need no escaping.
+fn parse_template(template: &str) -> Vec<TemplatePart<'_>> {
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    let bytes = template.as_bytes();
+    while i < bytes.len() {
+        if bytes[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        if bytes.get(i + 1) == Some(&b'{') {
+            let end = i + 2 + name_len(&template[i + 2..]);
+            if end > i + 2 && bytes[end..].starts_with(b"}}") {
+                // Drop the outer braces of the escape
+                parts.push(TemplatePart::Text(&template[text_start..i]));
+                parts.push(TemplatePart::Text(&template[i + 1..end + 1]));
+                i = end + 2;
+                text_start = i;
+                continue;
+            }
+        }
+        let end = i + 1 + name_len(&template[i + 1..]);
+        if end > i + 1 && bytes.get(end) == Some(&b'}') {
+            parts.push(TemplatePart::Text(&template[text_start..i]));
+            parts.push(TemplatePart::Placeholder(&template[i + 1..end]));
+            i = end + 1;
+            text_start = i;
+            continue;
+        }
+        i += 1;
+    }
+    parts.push(TemplatePart::Text(&template[text_start..]));
+    parts
+}
+
+/// Checks that every placeholder in the template is known. Unknown ones
+/// still render (verbatim), so this only feeds a warning.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    for part in parse_template(template) {
+        if let TemplatePart::Placeholder(name) = part {
+            if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+                return Err(format!("Unknown placeholder {{{}}}", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Unknown placeholders are kept as written, braces included
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let mut output = String::new();
+    for part in parse_template(template) {
+        match part {
+            TemplatePart::Text(text) => output.push_str(text),
+            TemplatePart::Placeholder(name) => match values.get(name) {
+                Some(value) => output.push_str(&value),
+                None => output.push_str(&format!("{{{}}}", name)),
+            },
+        }
+    }
+    output
+}
+
+// Lets single-line fields express line breaks and tabs
+pub fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n").replace("\\t", "\t")
+}
+
+// One numbered line of a share summary
+pub struct SummaryEntry {
+    pub index: usize,
+    pub label: Option<String>,
+    pub coordinates: String,
+}
+
+/// Plain-text overview for pasting into chat or tickets, e.g.
+/// "Canvas 1920x1080, origin top-left" followed by "1. login button — (412, 96)"
+pub fn share_summary(heading: Option<&str>, canvas_line: &str, entries: &[SummaryEntry]) -> String {
+    let mut lines = Vec::new();
+    if let Some(heading) = heading {
+        lines.push(heading.to_string());
+    }
+    lines.push(canvas_line.to_string());
+    for entry in entries {
+        lines.push(match &entry.label {
+            Some(label) => format!("{}. {} \u{2014} ({})", entry.index, label, entry.coordinates),
+            None => format!("{}. ({})", entry.index, entry.coordinates),
+        });
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(label: Option<&str>) -> TemplateValues {
+        TemplateValues {
+            index: 3,
+            x: "412".to_string(),
+            y: "391".to_string(),
+            label: label.map(str::to_string),
+            group: "Buttons".to_string(),
+            color: Color32::from_rgb(255, 0, 128),
+            normalized: (0.25, 0.5),
+            canvas_size: (1920.0, 1080.0),
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let output = render_template("{i}: {x},{y} {group} {color} {nx} {ny} {w}x{h}", &values(None));
+        assert_eq!(output, "3: 412,391 Buttons #ff0080 0.2500 0.5000 1920x1080");
+    }
+
+    #[test]
+    fn json_braces_need_no_escaping() {
+        let template = r#"{"id": {i}, "pos": [{x}, {y}], "label": "{label}"}"#;
+        let output = render_template(template, &values(Some("OK")));
+        assert_eq!(output, r#"{"id": 3, "pos": [412, 391], "label": "OK"}"#);
+    }
+
+    #[test]
+    fn placeholder_before_closing_braces() {
+        let output = render_template(r#"{"p": {"x": {x}}}"#, &values(None));
+        assert_eq!(output, r#"{"p": {"x": 412}}"#);
+        assert!(serde_json::from_str::<serde_json::Value>(&output).is_ok());
+    }
+
+    #[test]
+    fn double_braces_escape_a_placeholder() {
+        assert_eq!(render_template("{{x}} = {x}", &values(None)), "{x} = 412");
+        assert_eq!(render_template("{{label}}", &values(Some("OK"))), "{label}");
+    }
+
+    #[test]
+    fn other_double_braces_are_kept() {
+        assert_eq!(render_template("{{ {x} }}", &values(None)), "{{ 412 }}");
+        assert_eq!(render_template("}}{{", &values(None)), "}}{{");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept_verbatim() {
+        assert_eq!(render_template("{z} {x}", &values(None)), "{z} 412");
+        assert!(validate_template("{z}").is_err());
+        assert!(validate_template("{{z}}").is_ok());
+        assert!(validate_template(r#"{"x": {x}}"#).is_ok());
+    }
+
+    #[test]
+    fn missing_label_falls_back_to_marker_number() {
+        assert_eq!(render_template("{label}", &values(None)), "Marker 3");
+        assert_eq!(render_template("{label}", &values(Some("OK button"))), "OK button");
+    }
+
+    #[test]
+    fn unescape_turns_escapes_into_whitespace() {
+        assert_eq!(unescape(r"a\nb\tc"), "a\nb\tc");
+    }
+
+    fn entry(index: usize, label: Option<&str>, coordinates: &str) -> SummaryEntry {
+        SummaryEntry {
+            index,
+            label: label.map(str::to_string),
+            coordinates: coordinates.to_string(),
+        }
+    }
+
+    #[test]
+    fn share_summary_lists_entries_under_canvas_line() {
+        let entries = [entry(1, Some("login button"), "412, 96"), entry(3, None, "10, 20")];
+        let summary = share_summary(None, "Canvas 1920x1080, origin top-left", &entries);
+        assert_eq!(
+            summary,
+            "Canvas 1920x1080, origin top-left\n1. login button \u{2014} (412, 96)\n3. (10, 20)"
+        );
+    }
+
+    #[test]
+    fn share_summary_heading_comes_first() {
+        let summary = share_summary(Some("Checkout \u{2014} 2026-10-16"), "Canvas 800x600, origin centered", &[]);
+        assert_eq!(summary, "Checkout \u{2014} 2026-10-16\nCanvas 800x600, origin centered");
+    }
+}
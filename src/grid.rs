@@ -1,26 +1,154 @@
+use egui::emath::Rot2;
+use egui::{Color32, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+// Subdivision lines closer together than this on screen aren't drawn
+const MIN_SUBDIVISION_SPACING: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SnapMode {
+    Grid,
+    NearestMarker, // Falls back to the grid when no marker is in range
+}
+
+impl SnapMode {
+    pub const ALL: [SnapMode; 2] = [SnapMode::Grid, SnapMode::NearestMarker];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SnapMode::Grid => "Grid",
+            SnapMode::NearestMarker => "Nearest marker",
+        }
+    }
+}
+
+// How quantizing resolves values exactly halfway between two lattice steps
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    HalfUp,   // Ties go towards +infinity
+    HalfEven, // Ties go to the even multiple, so repeated rounding doesn't drift
+}
+
+impl RoundingMode {
+    pub const ALL: [RoundingMode; 2] = [RoundingMode::HalfUp, RoundingMode::HalfEven];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RoundingMode::HalfUp => "Half up",
+            RoundingMode::HalfEven => "Half to even",
+        }
+    }
+}
+
+// Where the grid is drawn relative to the background image
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GridLayer {
+    BelowImage,
+    AboveImage,
+}
+
+impl GridLayer {
+    pub const ALL: [GridLayer; 2] = [GridLayer::BelowImage, GridLayer::AboveImage];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GridLayer::BelowImage => "Below image",
+            GridLayer::AboveImage => "Above image",
+        }
+    }
+}
+
+// How grid lines are drawn
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GridStyle {
+    #[default]
+    Lines,
+    Dashed,
+    Dots, // A dot at each intersection instead of lines
+}
+
+impl GridStyle {
+    pub const ALL: [GridStyle; 3] = [GridStyle::Lines, GridStyle::Dashed, GridStyle::Dots];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GridStyle::Lines => "Lines",
+            GridStyle::Dashed => "Dashed",
+            GridStyle::Dots => "Dots",
+        }
+    }
+}
+
+/// Grid line color that stands out against an image of the given average
+/// luminance (0 to 1): light lines on dark images, dark lines on light ones
+pub fn contrasting_grid_color(luminance: f32) -> Color32 {
+    if luminance < 0.5 {
+        Color32::from_white_alpha(90)
+    } else {
+        Color32::from_black_alpha(90)
+    }
+}
+
+// A grid line in canvas coordinates
+pub struct GridLine {
+    pub from: Pos2,
+    pub to: Pos2,
+    pub vertical: bool, // Runs along the grid's Y axis, i.e. crosses the X axis at `offset`
+    pub offset: f32,    // Distance from the origin along the grid's axis
+    pub major: bool,
+}
+
 pub struct Grid {
-    size: f32,
+    size: Vec2, // Cell width and height in canvas units
+    style: GridStyle,
+    subdivisions: u32, // Minor cells per grid cell along each axis, 1 for none
+    rotation: f32,     // Degrees clockwise around the canvas origin
     visible: bool,
     snapping: bool,
+    snap_mode: SnapMode,
 }
 
 impl Grid {
     pub fn new(size: f32, visible: bool) -> Self {
         Self {
-            size,
+            size: Vec2::splat(size),
+            style: GridStyle::Lines,
+            subdivisions: 1,
+            rotation: 0.0,
             visible,
             snapping: false,
+            snap_mode: SnapMode::Grid,
         }
     }
 
-    pub fn get_size(&self) -> f32 {
+    pub fn get_size(&self) -> Vec2 {
         self.size
     }
 
-    pub fn set_size(&mut self, size: f32) {
+    pub fn set_size(&mut self, size: Vec2) {
         self.size = size;
     }
 
+    pub fn style(&self) -> GridStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: GridStyle) {
+        self.style = style;
+    }
+
+    pub fn set_subdivisions(&mut self, subdivisions: u32) {
+        self.subdivisions = subdivisions.max(1);
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
     pub fn is_visible(&self) -> bool {
         self.visible
     }
@@ -36,4 +164,398 @@ impl Grid {
     pub fn set_snapping(&mut self, snapping: bool) {
         self.snapping = snapping;
     }
+
+    pub fn snap_mode(&self) -> SnapMode {
+        self.snap_mode
+    }
+
+    pub fn set_snap_mode(&mut self, snap_mode: SnapMode) {
+        self.snap_mode = snap_mode;
+    }
+
+    fn rotation_transform(&self) -> Rot2 {
+        Rot2::from_angle(self.rotation.to_radians())
+    }
+
+    /// Canvas position in the grid's own (rotated) axes
+    pub fn canvas_to_grid_pos(&self, pos: Pos2) -> Pos2 {
+        (self.rotation_transform().inverse() * pos.to_vec2()).to_pos2()
+    }
+
+    pub fn grid_to_canvas_pos(&self, pos: Pos2) -> Pos2 {
+        (self.rotation_transform() * pos.to_vec2()).to_pos2()
+    }
+
+    /// Nearest grid intersection to a canvas position
+    pub fn snap(&self, pos: Pos2) -> Pos2 {
+        let local = self.canvas_to_grid_pos(pos);
+        let round = |value: f32, step: f32| if step > 0.0 { (value / step).round() * step } else { value };
+        self.grid_to_canvas_pos(Pos2::new(round(local.x, self.size.x), round(local.y, self.size.y)))
+    }
+
+    fn is_on_line(value: f32, size: f32) -> bool {
+        let offset = value.rem_euclid(size);
+        offset < 0.01 || size - offset < 0.01
+    }
+
+    // Step between drawn lines along one axis and how many steps apart the
+    // major lines are. Subdivisions win over the adaptive spacing while
+    // they're far enough apart to see.
+    fn drawn_step(&self, size: f32, zoom: f32, min_spacing: f32, max_spacing: f32) -> (f32, i32) {
+        let subdivision = size / self.subdivisions as f32;
+        if self.subdivisions > 1 && subdivision * zoom >= MIN_SUBDIVISION_SPACING {
+            return (subdivision, self.subdivisions as i32);
+        }
+        match adaptive_spacing(size, zoom, min_spacing, max_spacing) {
+            (_, Some(minor)) => (minor, 2),
+            (major, None) => (major, 1),
+        }
+    }
+
+    // Steps and line indices along both grid axes that cover `area` (canvas
+    // units), or None when there's nothing sensible to draw
+    fn drawn_lattice(
+        &self,
+        area: Rect,
+        zoom: f32,
+        min_spacing: f32,
+        max_spacing: f32,
+    ) -> Option<[(f32, i32, i32, i32); 2]> {
+        if self.size.x <= 0.0 || self.size.y <= 0.0 || zoom <= 0.0 {
+            return None;
+        }
+        let corners = [area.left_top(), area.right_top(), area.left_bottom(), area.right_bottom()];
+        let bounds = Rect::from_points(&corners.map(|corner| self.canvas_to_grid_pos(corner)));
+        let axis = |size: f32, min: f32, max: f32| {
+            let (step, major_every) = self.drawn_step(size, zoom, min_spacing, max_spacing);
+            (step, major_every, (min / step).floor() as i32, (max / step).ceil() as i32)
+        };
+        let axes = [
+            axis(self.size.x, bounds.min.x, bounds.max.x),
+            axis(self.size.y, bounds.min.y, bounds.max.y),
+        ];
+        // Lines closer than 2 screen px would just fill the area
+        if axes.iter().any(|(step, ..)| step * zoom < 2.0) {
+            return None;
+        }
+        Some(axes)
+    }
+
+    /// Lines covering `area` (canvas units), spaced for `zoom` as described
+    /// in `adaptive_spacing`. They run past the area when the grid is rotated,
+    /// so clip them when drawing.
+    pub fn lines(&self, area: Rect, zoom: f32, min_spacing: f32, max_spacing: f32) -> Vec<GridLine> {
+        let [(step_x, major_x, first_x, last_x), (step_y, major_y, first_y, last_y)] =
+            match self.drawn_lattice(area, zoom, min_spacing, max_spacing) {
+                Some(axes) => axes,
+                None => return Vec::new(),
+            };
+        let (min_y, max_y) = (first_y as f32 * step_y, last_y as f32 * step_y);
+        let (min_x, max_x) = (first_x as f32 * step_x, last_x as f32 * step_x);
+        let vertical = (first_x..=last_x).map(|i| {
+            let x = i as f32 * step_x;
+            GridLine {
+                from: self.grid_to_canvas_pos(Pos2::new(x, min_y)),
+                to: self.grid_to_canvas_pos(Pos2::new(x, max_y)),
+                vertical: true,
+                offset: x,
+                major: i.rem_euclid(major_x) == 0,
+            }
+        });
+        let horizontal = (first_y..=last_y).map(|i| {
+            let y = i as f32 * step_y;
+            GridLine {
+                from: self.grid_to_canvas_pos(Pos2::new(min_x, y)),
+                to: self.grid_to_canvas_pos(Pos2::new(max_x, y)),
+                vertical: false,
+                offset: y,
+                major: i.rem_euclid(major_y) == 0,
+            }
+        });
+        vertical.chain(horizontal).collect()
+    }
+
+    /// Intersections of the lines from `lines`, with whether both lines are
+    /// major, for the dotted style
+    pub fn dots(&self, area: Rect, zoom: f32, min_spacing: f32, max_spacing: f32) -> Vec<(Pos2, bool)> {
+        let [(step_x, major_x, first_x, last_x), (step_y, major_y, first_y, last_y)] =
+            match self.drawn_lattice(area, zoom, min_spacing, max_spacing) {
+                Some(axes) => axes,
+                None => return Vec::new(),
+            };
+        let mut dots = Vec::new();
+        for i in first_x..=last_x {
+            for j in first_y..=last_y {
+                let pos = self.grid_to_canvas_pos(Pos2::new(i as f32 * step_x, j as f32 * step_y));
+                if area.contains(pos) {
+                    dots.push((pos, i.rem_euclid(major_x) == 0 && j.rem_euclid(major_y) == 0));
+                }
+            }
+        }
+        dots
+    }
+}
+
+// A named grid configuration offered in the Grid settings
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredGridPreset")]
+pub struct GridPreset {
+    pub name: String,
+    pub size_x: f32,
+    pub size_y: f32,
+    pub style: GridStyle,
+    pub subdivisions: u32,
+    pub rotation: f32, // Degrees
+}
+
+// GridPreset as read from settings. Presets saved before the per-axis sizes
+// only have "size", which applies to both axes; the other fields default.
+#[derive(Deserialize)]
+#[serde(default)]
+struct StoredGridPreset {
+    name: String,
+    size: Option<f32>,
+    size_x: Option<f32>,
+    size_y: Option<f32>,
+    style: GridStyle,
+    subdivisions: u32,
+    rotation: f32,
+}
+
+impl Default for StoredGridPreset {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            size: None,
+            size_x: None,
+            size_y: None,
+            style: GridStyle::Lines,
+            subdivisions: 1,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl From<StoredGridPreset> for GridPreset {
+    fn from(stored: StoredGridPreset) -> Self {
+        let size = stored.size.unwrap_or(45.0);
+        Self {
+            name: stored.name,
+            size_x: stored.size_x.unwrap_or(size),
+            size_y: stored.size_y.unwrap_or(size),
+            style: stored.style,
+            subdivisions: stored.subdivisions.max(1),
+            rotation: stored.rotation,
+        }
+    }
+}
+
+impl GridPreset {
+    /// A plain square grid
+    pub fn new(name: &str, size: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            size_x: size,
+            size_y: size,
+            style: GridStyle::Lines,
+            subdivisions: 1,
+            rotation: 0.0,
+        }
+    }
+
+    /// The current settings of `grid` under a new name
+    pub fn from_grid(name: &str, grid: &Grid) -> Self {
+        Self {
+            name: name.to_string(),
+            size_x: grid.size.x,
+            size_y: grid.size.y,
+            style: grid.style,
+            subdivisions: grid.subdivisions,
+            rotation: grid.rotation,
+        }
+    }
+
+    pub fn built_in() -> Vec<GridPreset> {
+        vec![
+            GridPreset::new("8px pixel art", 8.0),
+            GridPreset::new("12-column web layout (80px)", 80.0),
+            GridPreset::new("Bootstrap (30px)", 30.0),
+            GridPreset::new("Game tile (64px)", 64.0),
+        ]
+    }
+}
+
+/// Rounds `value` to the nearest multiple of `step`. Values already on the
+/// lattice are returned unchanged so they never pick up float error.
+pub fn quantize(value: f32, step: f32, rounding: RoundingMode) -> f32 {
+    if step <= 0.0 {
+        return value;
+    }
+    let steps = value / step;
+    let lower = steps.floor();
+    let fraction = steps - lower;
+    let rounded = if (fraction - 0.5).abs() > 1e-4 {
+        steps.round()
+    } else {
+        match rounding {
+            RoundingMode::HalfUp => lower + 1.0,
+            RoundingMode::HalfEven if lower.rem_euclid(2.0) == 0.0 => lower,
+            RoundingMode::HalfEven => lower + 1.0,
+        }
+    };
+    let quantized = rounded * step;
+    if (quantized - value).abs() < 1e-4 {
+        value
+    } else {
+        quantized
+    }
+}
+
+/// Spacing of the drawn grid lines in canvas units, keeping the on-screen gap
+/// between `min_spacing` and `max_spacing`. When the grid had to be thinned
+/// out, the second value is the step of the minor lines between major ones.
+pub fn adaptive_spacing(size: f32, zoom: f32, min_spacing: f32, max_spacing: f32) -> (f32, Option<f32>) {
+    if size <= 0.0 || zoom <= 0.0 {
+        return (size, None);
+    }
+    let mut major = size;
+    let mut thinned = false;
+    while major * zoom < min_spacing {
+        major *= 2.0;
+        thinned = true;
+    }
+    // Halving stops short of the minimum, so this can't undo the loop above
+    while major * zoom > max_spacing && major * zoom / 2.0 >= min_spacing {
+        major /= 2.0;
+    }
+    if thinned {
+        (major, Some(major / 2.0))
+    } else {
+        (major, None)
+    }
+}
+
+/// Scores a marker as a snap target for the cursor; higher is better. Distance
+/// (relative to `radius`) costs up to 1.0 and each axis lying on a grid line
+/// earns 0.25, so an on-grid marker beats a slightly closer off-grid one.
+pub fn snap_score(marker: Pos2, cursor: Pos2, grid: &Grid, radius: f32) -> f32 {
+    let distance_penalty = (marker - cursor).length() / radius;
+    let local = grid.canvas_to_grid_pos(marker);
+    let alignment_bonus = [(local.x, grid.size.x), (local.y, grid.size.y)]
+        .iter()
+        .filter(|&&(value, size)| Grid::is_on_line(value, size))
+        .count() as f32
+        * 0.25;
+    alignment_bonus - distance_penalty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_up_breaks_ties_towards_positive_infinity() {
+        assert_eq!(quantize(5.0, 10.0, RoundingMode::HalfUp), 10.0);
+        assert_eq!(quantize(15.0, 10.0, RoundingMode::HalfUp), 20.0);
+        assert_eq!(quantize(-5.0, 10.0, RoundingMode::HalfUp), 0.0);
+        assert_eq!(quantize(-15.0, 10.0, RoundingMode::HalfUp), -10.0);
+    }
+
+    #[test]
+    fn half_even_breaks_ties_towards_even_multiples() {
+        assert_eq!(quantize(5.0, 10.0, RoundingMode::HalfEven), 0.0);
+        assert_eq!(quantize(15.0, 10.0, RoundingMode::HalfEven), 20.0);
+        assert_eq!(quantize(25.0, 10.0, RoundingMode::HalfEven), 20.0);
+        assert_eq!(quantize(-5.0, 10.0, RoundingMode::HalfEven), 0.0);
+        assert_eq!(quantize(-15.0, 10.0, RoundingMode::HalfEven), -20.0);
+    }
+
+    #[test]
+    fn ties_with_fractional_steps() {
+        assert_eq!(quantize(0.75, 0.5, RoundingMode::HalfUp), 1.0);
+        assert_eq!(quantize(0.75, 0.5, RoundingMode::HalfEven), 1.0);
+        assert_eq!(quantize(1.25, 0.5, RoundingMode::HalfEven), 1.0);
+    }
+
+    #[test]
+    fn non_ties_round_to_nearest_in_both_modes() {
+        for rounding in RoundingMode::ALL {
+            assert_eq!(quantize(14.0, 10.0, rounding), 10.0);
+            assert_eq!(quantize(16.0, 10.0, rounding), 20.0);
+            assert_eq!(quantize(-16.0, 10.0, rounding), -20.0);
+        }
+    }
+
+    #[test]
+    fn snapping_uses_the_size_of_each_axis() {
+        let mut grid = Grid::new(10.0, true);
+        grid.set_size(Vec2::new(10.0, 20.0));
+        assert_eq!(grid.snap(Pos2::new(14.0, 14.0)), Pos2::new(10.0, 20.0));
+        assert_eq!(grid.snap(Pos2::new(-6.0, 9.0)), Pos2::new(-10.0, 0.0));
+    }
+
+    #[test]
+    fn rotated_grids_snap_along_their_own_axes() {
+        let mut grid = Grid::new(10.0, true);
+        grid.set_size(Vec2::new(10.0, 20.0));
+        grid.set_rotation(90.0);
+        // The grid's X axis now runs down the canvas
+        let snapped = grid.snap(Pos2::new(3.0, 9.0));
+        assert!((snapped - Pos2::new(0.0, 10.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn subdivisions_add_minor_lines_between_grid_lines() {
+        let mut grid = Grid::new(10.0, true);
+        grid.set_subdivisions(2);
+        let area = Rect::from_min_max(Pos2::ZERO, Pos2::new(20.0, 10.0));
+        let lines = grid.lines(area, 1.0, 1.0, 100.0);
+        let vertical: Vec<(f32, bool)> = lines
+            .iter()
+            .filter(|line| line.vertical)
+            .map(|line| (line.offset, line.major))
+            .collect();
+        assert_eq!(vertical, [(0.0, true), (5.0, false), (10.0, true), (15.0, false), (20.0, true)]);
+        assert_eq!(lines.iter().filter(|line| !line.vertical).count(), 3);
+        // Too dense to see, so only the grid lines are left
+        assert!(grid.lines(area, 0.5, 1.0, 100.0).iter().all(|line| line.major));
+    }
+
+    #[test]
+    fn dots_sit_on_intersections_inside_the_area() {
+        let grid = Grid::new(10.0, true);
+        let area = Rect::from_min_max(Pos2::new(-5.0, 0.0), Pos2::new(10.0, 10.0));
+        let dots: Vec<Pos2> = grid.dots(area, 1.0, 1.0, 100.0).into_iter().map(|(pos, _)| pos).collect();
+        assert_eq!(
+            dots,
+            [Pos2::new(0.0, 0.0), Pos2::new(0.0, 10.0), Pos2::new(10.0, 0.0), Pos2::new(10.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn presets_round_trip_every_field() {
+        let mut grid = Grid::new(8.0, true);
+        grid.set_size(Vec2::new(8.0, 12.0));
+        grid.set_style(GridStyle::Dots);
+        grid.set_subdivisions(4);
+        grid.set_rotation(30.0);
+        let preset = GridPreset::from_grid("Iso", &grid);
+        let json = serde_json::to_string(&preset).unwrap();
+        assert!(serde_json::from_str::<GridPreset>(&json).unwrap() == preset);
+    }
+
+    #[test]
+    fn presets_saved_with_a_single_size_still_load() {
+        let preset: GridPreset = serde_json::from_str(r#"{"name": "Old", "size": 8.0}"#).unwrap();
+        assert!(preset == GridPreset::new("Old", 8.0));
+    }
+
+    #[test]
+    fn values_on_the_lattice_are_untouched() {
+        for rounding in RoundingMode::ALL {
+            assert_eq!(quantize(30.0, 10.0, rounding), 30.0);
+            assert_eq!(quantize(0.3, 0.1, rounding), 0.3);
+            assert_eq!(quantize(7.0, 0.0, rounding), 7.0);
+        }
+    }
 }
@@ -0,0 +1,121 @@
+use crate::format;
+use egui::Color32;
+
+#[derive(Clone, PartialEq)]
+pub struct Group {
+    pub id: u32,
+    pub name: String,
+    pub visible: bool,
+    pub color: Color32, // Color for the group's markers
+}
+
+// Named marker groups. Markers refer to groups by id so that renaming or
+// deleting a group never invalidates marker data.
+pub struct GroupManager {
+    groups: Vec<Group>,
+    active: u32,
+    next_id: u32,
+}
+
+impl Default for GroupManager {
+    fn default() -> Self {
+        Self {
+            groups: vec![Group {
+                id: Self::DEFAULT_GROUP,
+                name: "Default".to_string(),
+                visible: true,
+                color: Color32::from_rgb(0, 120, 255),
+            }],
+            active: Self::DEFAULT_GROUP,
+            next_id: 1,
+        }
+    }
+}
+
+impl GroupManager {
+    pub const DEFAULT_GROUP: u32 = 0;
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn groups_mut(&mut self) -> &mut [Group] {
+        &mut self.groups
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Group> {
+        self.groups.iter().find(|group| group.id == id)
+    }
+
+    pub fn active(&self) -> u32 {
+        self.active
+    }
+
+    pub fn set_active(&mut self, id: u32) {
+        if self.get(id).is_some() {
+            self.active = id;
+        }
+    }
+
+    // New groups get the palette color most distinct from the current groups
+    pub fn add(&mut self, name: String) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let existing: Vec<Color32> = self.groups.iter().map(|group| group.color).collect();
+        self.groups.push(Group {
+            id,
+            name,
+            visible: true,
+            color: format::next_group_color(&existing),
+        });
+        id
+    }
+
+    // The default group can't be removed; its markers have nowhere else to go
+    pub fn remove(&mut self, id: u32) -> bool {
+        if id == Self::DEFAULT_GROUP {
+            return false;
+        }
+        let before = self.groups.len();
+        self.groups.retain(|group| group.id != id);
+        if self.active == id {
+            self.active = Self::DEFAULT_GROUP;
+        }
+        self.groups.len() != before
+    }
+
+    /// Puts back an earlier copy of `groups()`, e.g. to undo an import that
+    /// created some. The active group falls back to the default one if it's gone.
+    pub fn restore(&mut self, groups: Vec<Group>) {
+        self.groups = groups;
+        if self.get(self.active).is_none() {
+            self.active = Self::DEFAULT_GROUP;
+        }
+    }
+
+    pub fn is_visible(&self, id: u32) -> bool {
+        match self.get(id) {
+            Some(group) => group.visible,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restoring_drops_groups_added_since() {
+        let mut groups = GroupManager::default();
+        let snapshot = groups.groups().to_vec();
+        let id = groups.add("Imported".to_string());
+        groups.set_active(id);
+        groups.restore(snapshot);
+        assert_eq!(groups.groups().len(), 1);
+        assert!(groups.get(id).is_none());
+        assert_eq!(groups.active(), GroupManager::DEFAULT_GROUP);
+        // Ids aren't reused, so markers from the undone import can't land in a later group
+        assert_ne!(groups.add("Next".to_string()), id);
+    }
+}
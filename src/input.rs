@@ -0,0 +1,275 @@
+use egui::{Key, KeyboardShortcut, ModifierNames, Modifiers, PointerButton, Response};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BindingModifier {
+    None,
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+impl BindingModifier {
+    pub const ALL: [BindingModifier; 4] = [
+        BindingModifier::None,
+        BindingModifier::Shift,
+        BindingModifier::Ctrl,
+        BindingModifier::Alt,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BindingModifier::None => "No modifier",
+            BindingModifier::Shift => "Shift",
+            BindingModifier::Ctrl => "Ctrl",
+            BindingModifier::Alt => "Alt",
+        }
+    }
+
+    // The modifier currently held, preferring Shift, then Ctrl/Cmd, then Alt
+    fn held(modifiers: Modifiers) -> Self {
+        if modifiers.shift {
+            BindingModifier::Shift
+        } else if modifiers.command {
+            BindingModifier::Ctrl
+        } else if modifiers.alt {
+            BindingModifier::Alt
+        } else {
+            BindingModifier::None
+        }
+    }
+}
+
+pub const BUTTONS: [PointerButton; 5] = [
+    PointerButton::Primary,
+    PointerButton::Secondary,
+    PointerButton::Middle,
+    PointerButton::Extra1,
+    PointerButton::Extra2,
+];
+
+pub fn button_label(button: PointerButton) -> &'static str {
+    match button {
+        PointerButton::Primary => "Left",
+        PointerButton::Secondary => "Right",
+        PointerButton::Middle => "Middle",
+        PointerButton::Extra1 => "Back",
+        PointerButton::Extra2 => "Forward",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub button: PointerButton,
+    pub modifier: BindingModifier,
+}
+
+impl Binding {
+    const fn new(button: PointerButton) -> Self {
+        Self {
+            button,
+            modifier: BindingModifier::None,
+        }
+    }
+
+    pub fn clicked(&self, response: &Response, modifiers: Modifiers) -> bool {
+        response.clicked_by(self.button) && BindingModifier::held(modifiers) == self.modifier
+    }
+
+    pub fn dragged(&self, response: &Response, modifiers: Modifiers) -> bool {
+        response.dragged_by(self.button) && BindingModifier::held(modifiers) == self.modifier
+    }
+
+    // e.g. "Shift+Middle"
+    pub fn label(&self) -> String {
+        match self.modifier {
+            BindingModifier::None => button_label(self.button).to_string(),
+            modifier => format!("{}+{}", modifier.label(), button_label(self.button)),
+        }
+    }
+}
+
+// Left-button drags the canvas handles itself. Ctrl+Shift is reported as Shift by
+// BindingModifier::held, so marquee zoom shares the rectangle selection binding.
+const BUILT_IN_DRAGS: [(&str, Binding); 4] = [
+    ("dragging markers", Binding::new(PointerButton::Primary)),
+    ("dragging the origin", Binding::new(PointerButton::Primary)),
+    (
+        "rectangle selection",
+        Binding {
+            button: PointerButton::Primary,
+            modifier: BindingModifier::Shift,
+        },
+    ),
+    (
+        "marquee zoom",
+        Binding {
+            button: PointerButton::Primary,
+            modifier: BindingModifier::Shift,
+        },
+    ),
+];
+
+// Pointer bindings for the canvas actions
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseBindings {
+    pub place: Binding,
+    pub delete: Binding,
+    pub pan: Binding,
+}
+
+impl Default for MouseBindings {
+    fn default() -> Self {
+        Self {
+            place: Binding::new(PointerButton::Primary),
+            delete: Binding::new(PointerButton::Secondary),
+            pan: Binding::new(PointerButton::Middle),
+        }
+    }
+}
+
+impl MouseBindings {
+    // Alt+Left drag always pans unless an action has been bound to it
+    pub fn alt_drag_pans(&self) -> bool {
+        let alt_left = Binding {
+            button: PointerButton::Primary,
+            modifier: BindingModifier::Alt,
+        };
+        self.place != alt_left && self.delete != alt_left
+    }
+
+    pub fn entries_mut(&mut self) -> [(&'static str, &mut Binding); 3] {
+        [
+            ("Place marker", &mut self.place),
+            ("Delete marker", &mut self.delete),
+            ("Pan", &mut self.pan),
+        ]
+    }
+
+    /// Describes the first pair of actions sharing a binding, if any. Placing and
+    /// deleting are clicks, which egui tells apart from the built-in drags, but
+    /// panning is a drag and can't take one of those over.
+    pub fn conflict(&self) -> Option<String> {
+        let entries = [("Place marker", self.place), ("Delete marker", self.delete), ("Pan", self.pan)];
+        for (i, (name, binding)) in entries.iter().enumerate() {
+            for (other_name, other) in &entries[i + 1..] {
+                if binding == other {
+                    return Some(format!("{} and {} can't both use {}", name, other_name, binding.label()));
+                }
+            }
+        }
+        for (gesture, binding) in BUILT_IN_DRAGS {
+            if self.pan == binding {
+                return Some(format!("Pan can't use {}, which is used for {}", binding.label(), gesture));
+            }
+        }
+        None
+    }
+}
+
+// Keyboard shortcuts for global actions
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub command_entry: KeyboardShortcut,
+    pub duplicate: KeyboardShortcut,
+    pub copy_view: KeyboardShortcut,
+    pub shortcuts_overlay: KeyboardShortcut,
+    pub hide_overlays: KeyboardShortcut, // Held, or pressed to toggle when latching
+    pub reset_zoom: KeyboardShortcut,
+    pub reset_pan: KeyboardShortcut,
+    pub undo: KeyboardShortcut,
+    pub redo: KeyboardShortcut, // Cmd+Shift+Z also redoes
+    pub set_origin: KeyboardShortcut,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            command_entry: KeyboardShortcut::new(Modifiers::NONE, Key::G),
+            duplicate: KeyboardShortcut::new(Modifiers::COMMAND, Key::D),
+            copy_view: KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::C),
+            shortcuts_overlay: KeyboardShortcut::new(Modifiers::NONE, Key::F1),
+            hide_overlays: KeyboardShortcut::new(Modifiers::NONE, Key::Tab),
+            reset_zoom: KeyboardShortcut::new(Modifiers::NONE, Key::Num0),
+            reset_pan: KeyboardShortcut::new(Modifiers::NONE, Key::Home),
+            undo: KeyboardShortcut::new(Modifiers::COMMAND, Key::Z),
+            redo: KeyboardShortcut::new(Modifiers::COMMAND, Key::Y),
+            set_origin: KeyboardShortcut::new(Modifiers::NONE, Key::O),
+        }
+    }
+}
+
+// e.g. "Ctrl+Shift+C", or "Cmd+Shift+C" on macOS
+fn shortcut_label(shortcut: &KeyboardShortcut) -> String {
+    shortcut.format(&ModifierNames::NAMES, cfg!(target_os = "macos"))
+}
+
+/// (action, key) rows for the shortcut cheat sheet
+pub fn list_shortcuts(bindings: &KeyBindings) -> Vec<(&'static str, String)> {
+    let command = ModifierNames::NAMES.format(&Modifiers::COMMAND, cfg!(target_os = "macos"));
+    vec![
+        ("Place marker at typed coordinate", shortcut_label(&bindings.command_entry)),
+        ("Duplicate selected markers", shortcut_label(&bindings.duplicate)),
+        ("Undo marker change", shortcut_label(&bindings.undo)),
+        ("Redo marker change", format!("{} / {}+Shift+Z", shortcut_label(&bindings.redo), command)),
+        ("Copy view as image", shortcut_label(&bindings.copy_view)),
+        ("Recall view bookmark", "1\u{2013}9".to_string()),
+        ("Save view bookmark", format!("{}+1\u{2013}9", command)),
+        ("Reset zoom", shortcut_label(&bindings.reset_zoom)),
+        ("Reset pan", shortcut_label(&bindings.reset_pan)),
+        ("Set origin with the next click", shortcut_label(&bindings.set_origin)),
+        ("Zoom to dragged region", format!("{}+Shift+Drag", command)),
+        ("Hide overlays", shortcut_label(&bindings.hide_overlays)),
+        ("Cancel / close popup", "Esc".to_string()),
+        ("Show keyboard shortcuts", shortcut_label(&bindings.shortcuts_overlay)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(button: PointerButton, modifier: BindingModifier) -> Binding {
+        Binding { button, modifier }
+    }
+
+    #[test]
+    fn defaults_do_not_conflict() {
+        assert_eq!(MouseBindings::default().conflict(), None);
+    }
+
+    #[test]
+    fn actions_sharing_a_binding_conflict() {
+        let bindings = MouseBindings {
+            delete: binding(PointerButton::Primary, BindingModifier::None),
+            ..MouseBindings::default()
+        };
+        assert!(bindings.conflict().is_some());
+    }
+
+    #[test]
+    fn pan_cannot_take_over_built_in_drags() {
+        for modifier in [BindingModifier::None, BindingModifier::Shift] {
+            let bindings = MouseBindings {
+                pan: binding(PointerButton::Primary, modifier),
+                ..MouseBindings::default()
+            };
+            assert!(bindings.conflict().is_some());
+        }
+        let ctrl_left = MouseBindings {
+            pan: binding(PointerButton::Primary, BindingModifier::Ctrl),
+            ..MouseBindings::default()
+        };
+        assert_eq!(ctrl_left.conflict(), None);
+    }
+
+    #[test]
+    fn clicks_can_share_a_button_with_built_in_drags() {
+        let bindings = MouseBindings {
+            delete: binding(PointerButton::Primary, BindingModifier::Shift),
+            ..MouseBindings::default()
+        };
+        assert_eq!(bindings.conflict(), None);
+    }
+}
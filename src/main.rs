@@ -1,9 +1,30 @@
+mod analysis;
+mod annotation;
 mod app;
 mod canvas;
+mod colormap;
+mod command;
 mod coordinate;
+mod export;
+mod format;
 mod grid;
+mod group;
+mod input;
 mod marker;
+mod measurement;
+mod project;
+mod recent;
+mod region;
+mod ruler;
+mod session;
+mod settings;
+mod stamp;
+#[cfg(feature = "websocket")]
+mod stream;
+mod system_color;
 mod ui;
+mod undo;
+mod validation;
 
 use app::CoordinatePickerApp;
 
@@ -11,6 +32,7 @@ fn main() -> eframe::Result<()> {
     let native_options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(1280.0, 800.0)),
         min_window_size: Some(egui::vec2(800.0, 600.0)),
+        drag_and_drop_support: true, // Dropping an image loads it as the canvas background
         ..Default::default()
     };
     
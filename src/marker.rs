@@ -1,17 +1,145 @@
 use egui::{Color32, Pos2};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
 
+// What a marker stands for. Line markers only carry meaning along one axis.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Point,
+    ColumnLine, // x only
+    RowLine,    // y only
+}
+
+impl MarkerKind {
+    pub const ALL: [MarkerKind; 3] = [MarkerKind::Point, MarkerKind::ColumnLine, MarkerKind::RowLine];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MarkerKind::Point => "Point",
+            MarkerKind::ColumnLine => "Column line",
+            MarkerKind::RowLine => "Row line",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Marker {
     pub position: Pos2,         // Position in canvas coordinates
     pub system_position: Pos2,  // Position in the chosen coordinate system
     pub color: Color32,
+    pub group: u32,             // Id of the owning group
+    pub kind: MarkerKind,
+    pub ghost: bool, // Visual reference only: not copied, snapped to or right-click deleted
+    pub label: Option<String>, // Short name such as "submit_button"
+    pub note: String,
+    pub created_at: SystemTime,
 }
 
 impl Marker {
-    pub fn new(position: Pos2, system_position: Pos2, color: Color32) -> Self {
+    pub fn new(position: Pos2, system_position: Pos2, color: Color32, group: u32) -> Self {
         Self {
             position,
             system_position,
             color,
+            group,
+            kind: MarkerKind::Point,
+            ghost: false,
+            label: None,
+            note: String::new(),
+            created_at: SystemTime::now(),
+        }
+    }
+
+    const GHOST_ALPHA: f32 = 0.3;
+
+    pub fn display_color(&self) -> Color32 {
+        self.ghosted(self.color)
+    }
+
+    // Fades any color the marker is drawn in when it's a ghost
+    pub fn ghosted(&self, color: Color32) -> Color32 {
+        if self.ghost {
+            color.gamma_multiply(Self::GHOST_ALPHA)
+        } else {
+            color
+        }
+    }
+
+    /// The label, unless it's missing or blank
+    pub fn label_text(&self) -> Option<&str> {
+        self.label.as_deref().map(str::trim).filter(|label| !label.is_empty())
+    }
+
+    const NOTE_PREVIEW_CHARS: usize = 60;
+
+    // First line of the note, cut short for tooltips
+    pub fn note_preview(&self) -> String {
+        let first_line = self.note.lines().next().unwrap_or_default();
+        let mut preview: String = first_line.chars().take(Self::NOTE_PREVIEW_CHARS).collect();
+        if preview.len() < self.note.trim_end().len() {
+            preview.push('…');
+        }
+        preview
+    }
+
+    pub fn age_label(&self) -> String {
+        let seconds = self.created_at.elapsed().map(|age| age.as_secs()).unwrap_or(0);
+        match seconds {
+            0..=59 => "just now".to_string(),
+            60..=3599 => format!("{} min ago", seconds / 60),
+            _ => format!("{} h ago", seconds / 3600),
         }
     }
 }
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// The app's marker list. Any mutable access moves it to a new generation,
+/// so caches built from the markers can tell when to rebuild without
+/// comparing them.
+#[derive(Default)]
+pub struct MarkerList {
+    markers: Vec<Marker>,
+    generation: u64,
+}
+
+impl MarkerList {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl Deref for MarkerList {
+    type Target = Vec<Marker>;
+
+    fn deref(&self) -> &Vec<Marker> {
+        &self.markers
+    }
+}
+
+impl DerefMut for MarkerList {
+    fn deref_mut(&mut self) -> &mut Vec<Marker> {
+        // Shared across lists, so a replaced list never reuses a generation
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        &mut self.markers
+    }
+}
+
+impl<'a> IntoIterator for &'a MarkerList {
+    type Item = &'a Marker;
+    type IntoIter = std::slice::Iter<'a, Marker>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut MarkerList {
+    type Item = &'a mut Marker;
+    type IntoIter = std::slice::IterMut<'a, Marker>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
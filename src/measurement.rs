@@ -0,0 +1,509 @@
+use crate::analysis;
+use crate::project::Point;
+use egui::{Pos2, Rect};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeasurementKind {
+    Distance,
+    Angle,
+    Rect,
+    Circle,
+}
+
+impl MeasurementKind {
+    pub const ALL: [MeasurementKind; 4] = [
+        MeasurementKind::Distance,
+        MeasurementKind::Angle,
+        MeasurementKind::Rect,
+        MeasurementKind::Circle,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MeasurementKind::Distance => "Distance",
+            MeasurementKind::Angle => "Angle",
+            MeasurementKind::Rect => "Rect",
+            MeasurementKind::Circle => "Circle",
+        }
+    }
+
+    // The `type` value in JSON and CSV files
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MeasurementKind::Distance => "distance",
+            MeasurementKind::Angle => "angle",
+            MeasurementKind::Rect => "rect",
+            MeasurementKind::Circle => "circle",
+        }
+    }
+
+    // Canvas points stored for round-tripping, see Measurement::canvas_points
+    fn point_count(&self) -> usize {
+        match self {
+            MeasurementKind::Angle => 3,
+            _ => 2,
+        }
+    }
+}
+
+// A distance, angle, rectangle or circle kept on the canvas. Points are in
+// canvas coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Measurement {
+    Distance { from: Pos2, to: Pos2 },
+    Angle { arm_a: Pos2, vertex: Pos2, arm_b: Pos2 },
+    Rect { rect: Rect },
+    Circle { center: Pos2, radius: f32 },
+}
+
+impl Measurement {
+    /// The points a file stores to rebuild the measurement: both endpoints,
+    /// arm/vertex/arm, the min and max corners, or the center and the point
+    /// on the circle to its right
+    pub fn canvas_points(&self) -> Vec<Pos2> {
+        match *self {
+            Measurement::Distance { from, to } => vec![from, to],
+            Measurement::Angle { arm_a, vertex, arm_b } => vec![arm_a, vertex, arm_b],
+            Measurement::Rect { rect } => vec![rect.min, rect.max],
+            Measurement::Circle { center, radius } => vec![center, center + egui::vec2(radius, 0.0)],
+        }
+    }
+
+    /// Inverse of canvas_points
+    pub fn from_canvas_points(kind: MeasurementKind, points: &[Pos2]) -> Result<Self, String> {
+        if points.len() != kind.point_count() {
+            return Err(format!(
+                "{} needs {} canvas points, got {}",
+                kind.label(),
+                kind.point_count(),
+                points.len()
+            ));
+        }
+        if points.iter().any(|point| !point.x.is_finite() || !point.y.is_finite()) {
+            return Err(format!("{} has a canvas point that isn't a number", kind.label()));
+        }
+        Ok(match kind {
+            MeasurementKind::Distance => Measurement::Distance {
+                from: points[0],
+                to: points[1],
+            },
+            MeasurementKind::Angle => Measurement::Angle {
+                arm_a: points[0],
+                vertex: points[1],
+                arm_b: points[2],
+            },
+            MeasurementKind::Rect => Measurement::Rect {
+                rect: Rect::from_two_pos(points[0], points[1]),
+            },
+            MeasurementKind::Circle => Measurement::Circle {
+                center: points[0],
+                radius: points[0].distance(points[1]),
+            },
+        })
+    }
+
+    /// The file form, with values measured after mapping canvas points
+    /// through `to_units` (the active coordinate system and units)
+    pub fn record(&self, to_units: &dyn Fn(Pos2) -> Pos2) -> MeasurementRecord {
+        let canvas: Vec<Point> = self.canvas_points().into_iter().map(Point::from).collect();
+        let pair = [canvas[0], canvas[1]];
+        match *self {
+            Measurement::Distance { from, to } => {
+                let (from, to) = (to_units(from), to_units(to));
+                MeasurementRecord::Distance {
+                    from: from.into(),
+                    to: to.into(),
+                    length: from.distance(to),
+                    canvas: pair,
+                }
+            }
+            Measurement::Angle { arm_a, vertex, arm_b } => {
+                let (arm_a, vertex, arm_b) = (to_units(arm_a), to_units(vertex), to_units(arm_b));
+                MeasurementRecord::Angle {
+                    arm_a: arm_a.into(),
+                    vertex: vertex.into(),
+                    arm_b: arm_b.into(),
+                    degrees: analysis::signed_angle(vertex, arm_a, arm_b).map_or(0.0, f32::abs),
+                    canvas: [canvas[0], canvas[1], canvas[2]],
+                }
+            }
+            Measurement::Rect { rect } => {
+                // The system may flip an axis, so the converted corners are sorted again
+                let (a, b) = (to_units(rect.min), to_units(rect.max));
+                MeasurementRecord::Rect {
+                    x: a.x.min(b.x),
+                    y: a.y.min(b.y),
+                    w: (b.x - a.x).abs(),
+                    h: (b.y - a.y).abs(),
+                    canvas: pair,
+                }
+            }
+            Measurement::Circle { center, radius } => {
+                let edge = to_units(center + egui::vec2(radius, 0.0));
+                let center = to_units(center);
+                MeasurementRecord::Circle {
+                    center: center.into(),
+                    radius: center.distance(edge),
+                    canvas: pair,
+                }
+            }
+        }
+    }
+}
+
+// A measurement in a project file. Values are in the exporting app's
+// coordinate system and units; `canvas` holds the canvas points it is
+// rebuilt from on import.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MeasurementRecord {
+    Distance {
+        from: Point,
+        to: Point,
+        length: f32,
+        canvas: [Point; 2],
+    },
+    Angle {
+        arm_a: Point,
+        vertex: Point,
+        arm_b: Point,
+        degrees: f32, // Interior angle, 0 to 180
+        canvas: [Point; 3],
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        canvas: [Point; 2],
+    },
+    Circle {
+        center: Point,
+        radius: f32,
+        canvas: [Point; 2],
+    },
+}
+
+impl MeasurementRecord {
+    pub fn kind(&self) -> MeasurementKind {
+        match self {
+            MeasurementRecord::Distance { .. } => MeasurementKind::Distance,
+            MeasurementRecord::Angle { .. } => MeasurementKind::Angle,
+            MeasurementRecord::Rect { .. } => MeasurementKind::Rect,
+            MeasurementRecord::Circle { .. } => MeasurementKind::Circle,
+        }
+    }
+
+    fn canvas(&self) -> &[Point] {
+        match self {
+            MeasurementRecord::Angle { canvas, .. } => canvas,
+            MeasurementRecord::Distance { canvas, .. }
+            | MeasurementRecord::Rect { canvas, .. }
+            | MeasurementRecord::Circle { canvas, .. } => canvas,
+        }
+    }
+
+    pub fn to_measurement(&self) -> Result<Measurement, String> {
+        let points: Vec<Pos2> = self.canvas().iter().map(|&point| point.into()).collect();
+        Measurement::from_canvas_points(self.kind(), &points)
+    }
+
+    /// The measured value for lists and canvas labels, e.g. "120.5" or "45.0°"
+    pub fn value_text(&self) -> String {
+        match self {
+            MeasurementRecord::Distance { length, .. } => format!("{:.1}", length),
+            MeasurementRecord::Angle { degrees, .. } => format!("{:.1}\u{b0}", degrees),
+            MeasurementRecord::Rect { w, h, .. } => format!("{:.1} x {:.1}", w, h),
+            MeasurementRecord::Circle { radius, .. } => format!("r {:.1}", radius),
+        }
+    }
+}
+
+/// Columns of the measurements CSV, always all of them in this order. Each
+/// row fills the cells its type uses and leaves the rest empty: x1/y1 to
+/// x2/y2 and `length` for distances, x1/y1 to x3/y3 (arm, vertex, arm) and
+/// `degrees` for angles, x1/y1 with `w` and `h` for rects, and x1/y1 with
+/// `radius` for circles. The canvas columns follow Measurement::canvas_points.
+pub const CSV_COLUMNS: [&str; 18] = [
+    "type",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "x3",
+    "y3",
+    "length",
+    "degrees",
+    "w",
+    "h",
+    "radius",
+    "canvas_x1",
+    "canvas_y1",
+    "canvas_x2",
+    "canvas_y2",
+    "canvas_x3",
+    "canvas_y3",
+];
+
+pub fn to_csv(records: &[MeasurementRecord], delimiter: &str, include_header: bool) -> String {
+    let mut csv = String::new();
+    if include_header {
+        csv.push_str(&CSV_COLUMNS.join(delimiter));
+        csv.push('\n');
+    }
+    for record in records {
+        let mut cells = vec![String::new(); CSV_COLUMNS.len()];
+        let mut set = |column: &str, value: f32| {
+            if let Some(i) = CSV_COLUMNS.iter().position(|&name| name == column) {
+                cells[i] = value.to_string();
+            }
+        };
+        let points: Vec<Point> = match record {
+            MeasurementRecord::Distance { from, to, length, .. } => {
+                set("length", *length);
+                vec![*from, *to]
+            }
+            MeasurementRecord::Angle {
+                arm_a,
+                vertex,
+                arm_b,
+                degrees,
+                ..
+            } => {
+                set("degrees", *degrees);
+                vec![*arm_a, *vertex, *arm_b]
+            }
+            MeasurementRecord::Rect { x, y, w, h, .. } => {
+                set("w", *w);
+                set("h", *h);
+                vec![Point { x: *x, y: *y }]
+            }
+            MeasurementRecord::Circle { center, radius, .. } => {
+                set("radius", *radius);
+                vec![*center]
+            }
+        };
+        for (i, point) in points.iter().enumerate() {
+            set(&format!("x{}", i + 1), point.x);
+            set(&format!("y{}", i + 1), point.y);
+        }
+        for (i, point) in record.canvas().iter().enumerate() {
+            set(&format!("canvas_x{}", i + 1), point.x);
+            set(&format!("canvas_y{}", i + 1), point.y);
+        }
+        cells[0] = record.kind().type_name().to_string();
+        csv.push_str(&cells.join(delimiter));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Rebuilds measurements from the canvas columns of a CSV written by to_csv.
+/// The delimiter is detected; with a header row columns are found by name,
+/// otherwise they are expected in CSV_COLUMNS order. Fails with one message
+/// per bad row.
+pub fn from_csv(csv: &str) -> Result<Vec<Measurement>, Vec<String>> {
+    let mut lines = csv.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+    let first = match lines.peek() {
+        Some((_, line)) => *line,
+        None => return Ok(Vec::new()),
+    };
+    let delimiter = [",", "\t", ";"]
+        .into_iter()
+        .max_by_key(|delimiter| first.matches(delimiter).count())
+        .unwrap_or(",");
+    let split = |line: &str| -> Vec<String> { line.split(delimiter).map(|cell| cell.trim().to_string()).collect() };
+
+    let mut columns: Vec<String> = CSV_COLUMNS.iter().map(|name| name.to_string()).collect();
+    if split(first).first().map(String::as_str) == Some("type") {
+        columns = split(first);
+        lines.next();
+    }
+
+    let mut measurements = Vec::new();
+    let mut errors = Vec::new();
+    for (line_index, line) in lines {
+        let cells = split(line);
+        let cell = |name: &str| {
+            columns
+                .iter()
+                .position(|column| column == name)
+                .and_then(|i| cells.get(i))
+                .filter(|cell| !cell.is_empty())
+        };
+        let row = line_index + 1;
+        let type_name = cell("type").map(String::as_str).unwrap_or_default();
+        let kind = match MeasurementKind::ALL.into_iter().find(|kind| kind.type_name() == type_name) {
+            Some(kind) => kind,
+            None => {
+                errors.push(format!("Row {}: unknown type \"{}\"", row, type_name));
+                continue;
+            }
+        };
+        let number = |name: String| -> Result<f32, String> {
+            match cell(&name) {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| format!("Row {}: {} \"{}\" isn't a number", row, name, value)),
+                None => Err(format!("Row {}: missing {}", row, name)),
+            }
+        };
+        let points: Result<Vec<Pos2>, String> = (1..=kind.point_count())
+            .map(|i| Ok(Pos2::new(number(format!("canvas_x{}", i))?, number(format!("canvas_y{}", i))?)))
+            .collect();
+        match points.and_then(|points| {
+            Measurement::from_canvas_points(kind, &points).map_err(|err| format!("Row {}: {}", row, err))
+        }) {
+            Ok(measurement) => measurements.push(measurement),
+            Err(err) => errors.push(err),
+        }
+    }
+    if errors.is_empty() {
+        Ok(measurements)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Y-up system in millimetres with its origin at (100, 100)
+    fn to_mm(pos: Pos2) -> Pos2 {
+        Pos2::new((pos.x - 100.0) * 0.5, (100.0 - pos.y) * 0.5)
+    }
+
+    fn samples() -> [Measurement; 4] {
+        [
+            Measurement::Distance {
+                from: Pos2::new(100.0, 100.0),
+                to: Pos2::new(130.0, 60.0),
+            },
+            Measurement::Angle {
+                arm_a: Pos2::new(200.0, 100.0),
+                vertex: Pos2::new(100.0, 100.0),
+                arm_b: Pos2::new(100.0, 20.0),
+            },
+            Measurement::Rect {
+                rect: Rect::from_min_max(Pos2::new(110.0, 40.0), Pos2::new(150.0, 80.0)),
+            },
+            Measurement::Circle {
+                center: Pos2::new(120.0, 90.0),
+                radius: 12.5,
+            },
+        ]
+    }
+
+    fn json_round_trip(measurement: Measurement) -> Measurement {
+        let json = serde_json::to_string(&measurement.record(&to_mm)).unwrap();
+        serde_json::from_str::<MeasurementRecord>(&json).unwrap().to_measurement().unwrap()
+    }
+
+    fn csv_round_trip(measurement: Measurement) -> Measurement {
+        let csv = to_csv(&[measurement.record(&to_mm)], ",", true);
+        from_csv(&csv).unwrap()[0]
+    }
+
+    #[test]
+    fn distances_round_trip() {
+        let distance = samples()[0];
+        assert_eq!(json_round_trip(distance), distance);
+        assert_eq!(csv_round_trip(distance), distance);
+        match distance.record(&to_mm) {
+            MeasurementRecord::Distance { from, to, length, .. } => {
+                assert_eq!((from.x, from.y, to.x, to.y), (0.0, 0.0, 15.0, 20.0));
+                assert_eq!(length, 25.0);
+            }
+            _ => panic!("not a distance"),
+        }
+    }
+
+    #[test]
+    fn angles_round_trip() {
+        let angle = samples()[1];
+        assert_eq!(json_round_trip(angle), angle);
+        assert_eq!(csv_round_trip(angle), angle);
+        match angle.record(&to_mm) {
+            MeasurementRecord::Angle { vertex, degrees, .. } => {
+                assert_eq!((vertex.x, vertex.y), (0.0, 0.0));
+                assert!((degrees - 90.0).abs() < 1e-4);
+            }
+            _ => panic!("not an angle"),
+        }
+    }
+
+    #[test]
+    fn rects_round_trip() {
+        let rect = samples()[2];
+        assert_eq!(json_round_trip(rect), rect);
+        assert_eq!(csv_round_trip(rect), rect);
+        // Y is flipped, so the top of the rect on canvas has the larger system y
+        match rect.record(&to_mm) {
+            MeasurementRecord::Rect { x, y, w, h, .. } => assert_eq!((x, y, w, h), (5.0, 10.0, 20.0, 20.0)),
+            _ => panic!("not a rect"),
+        }
+    }
+
+    #[test]
+    fn circles_round_trip() {
+        let circle = samples()[3];
+        assert_eq!(json_round_trip(circle), circle);
+        assert_eq!(csv_round_trip(circle), circle);
+        match circle.record(&to_mm) {
+            MeasurementRecord::Circle { center, radius, .. } => {
+                assert_eq!((center.x, center.y, radius), (10.0, 5.0, 6.25));
+            }
+            _ => panic!("not a circle"),
+        }
+    }
+
+    #[test]
+    fn json_is_tagged_by_type() {
+        let json = serde_json::to_value(samples()[3].record(&to_mm)).unwrap();
+        assert_eq!(json["type"], "circle");
+        assert_eq!(json["canvas"][1]["x"], 132.5);
+    }
+
+    #[test]
+    fn csv_columns_keep_their_order() {
+        let records: Vec<MeasurementRecord> = samples().iter().map(|m| m.record(&to_mm)).collect();
+        let csv = to_csv(&records, ";", true);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_COLUMNS.join(";"));
+        assert_eq!(lines[1], "distance;0;0;15;20;;;25;;;;;100;100;130;60;;");
+        assert_eq!(lines[4], "circle;10;5;;;;;;;;;6.25;120;90;132.5;90;;");
+        assert!(lines.iter().all(|line| line.split(';').count() == CSV_COLUMNS.len()));
+        assert_eq!(from_csv(&csv).unwrap(), samples());
+    }
+
+    #[test]
+    fn csv_without_a_header_uses_the_column_order() {
+        let records: Vec<MeasurementRecord> = samples().iter().map(|m| m.record(&to_mm)).collect();
+        assert_eq!(from_csv(&to_csv(&records, "\t", false)).unwrap(), samples());
+    }
+
+    #[test]
+    fn bad_csv_rows_are_all_reported() {
+        let csv = format!(
+            "{}\nsquare,,,,,,,,,,,,,,,,,\ndistance,,,,,,,,,,,,1,2,x,4,,\nangle,,,,,,,,,,,,1,2,3,4,,\n",
+            CSV_COLUMNS.join(",")
+        );
+        assert_eq!(
+            from_csv(&csv).unwrap_err(),
+            [
+                "Row 2: unknown type \"square\"",
+                "Row 3: canvas_x2 \"x\" isn't a number",
+                "Row 4: missing canvas_x3",
+            ]
+        );
+    }
+
+    #[test]
+    fn canvas_points_must_match_the_type() {
+        assert!(Measurement::from_canvas_points(MeasurementKind::Angle, &[Pos2::ZERO, Pos2::ZERO]).is_err());
+        let nan = Pos2::new(f32::NAN, 0.0);
+        assert!(Measurement::from_canvas_points(MeasurementKind::Distance, &[nan, Pos2::ZERO]).is_err());
+    }
+}
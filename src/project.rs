@@ -0,0 +1,317 @@
+use crate::coordinate::{CoordinateSystem, OriginMode};
+use crate::group::GroupManager;
+use crate::marker::Marker;
+use crate::measurement::{Measurement, MeasurementRecord};
+use egui::{Color32, Pos2};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl From<Pos2> for Point {
+    fn from(pos: Pos2) -> Self {
+        Self { x: pos.x, y: pos.y }
+    }
+}
+
+impl From<Point> for Pos2 {
+    fn from(point: Point) -> Self {
+        Pos2::new(point.x, point.y)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectMarker {
+    pub position: Point,        // Canvas coordinates, top-left based
+    pub system_position: Point, // As shown in the app, following the origin
+    pub color: String,          // "#rrggbbaa"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(default)]
+    pub layer: u32, // Id of the entry in `layers`
+}
+
+// A marker group, called a layer in the file format
+#[derive(Serialize, Deserialize)]
+pub struct ProjectLayer {
+    pub id: u32,
+    pub name: String,
+    pub color: String, // "#rrggbbaa"
+    pub visible: bool,
+}
+
+// Markers together with the canvas they were recorded on, so another program
+// (or this one) can tell which resolution and origin the points belong to
+#[derive(Serialize, Deserialize)]
+pub struct ProjectExport {
+    pub width: f32,
+    pub height: f32,
+    pub origin_top_left: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub origin_center: bool, // Origin at the canvas center with Y up; `origin_top_left` is false
+    pub grid_size: f32,
+    #[serde(default)]
+    pub layers: Vec<ProjectLayer>,
+    pub markers: Vec<ProjectMarker>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub measurements: Vec<MeasurementRecord>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub measurement_units: String, // Unit label of the measurement values, empty for pixels
+}
+
+fn color_to_hex(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
+// Accepts "#rrggbb" or "#rrggbbaa"
+fn color_from_hex(hex: &str) -> Option<Color32> {
+    let digits = hex.strip_prefix('#')?;
+    if !digits.is_ascii() || (digits.len() != 6 && digits.len() != 8) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    let alpha = if digits.len() == 8 { channel(6)? } else { 255 };
+    Some(Color32::from_rgba_unmultiplied(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+impl ProjectExport {
+    pub fn new(
+        canvas_size: (f32, f32),
+        system: &CoordinateSystem,
+        grid_size: f32,
+        groups: &GroupManager,
+        markers: &[Marker],
+    ) -> Self {
+        Self {
+            width: canvas_size.0,
+            height: canvas_size.1,
+            origin_top_left: system.origin_mode() == OriginMode::TopLeft,
+            origin_center: system.origin_mode() == OriginMode::Center,
+            grid_size,
+            layers: groups
+                .groups()
+                .iter()
+                .map(|group| ProjectLayer {
+                    id: group.id,
+                    name: group.name.clone(),
+                    color: color_to_hex(group.color),
+                    visible: group.visible,
+                })
+                .collect(),
+            markers: markers
+                .iter()
+                .map(|marker| ProjectMarker {
+                    position: marker.position.into(),
+                    system_position: marker.system_position.into(),
+                    color: color_to_hex(marker.color),
+                    label: marker.label_text().map(str::to_string),
+                    note: marker.note.clone(),
+                    layer: marker.group,
+                })
+                .collect(),
+            measurements: Vec::new(),
+            measurement_units: String::new(),
+        }
+    }
+
+    /// Finds or creates a group for each layer in the file, matching by name.
+    /// Returns the group id to use for each layer id.
+    pub fn merge_layers(&self, groups: &mut GroupManager) -> HashMap<u32, u32> {
+        let mut ids = HashMap::new();
+        for layer in &self.layers {
+            let existing = groups.groups().iter().find(|group| group.name == layer.name).map(|group| group.id);
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = groups.add(layer.name.clone());
+                    if let Some(group) = groups.groups_mut().iter_mut().find(|group| group.id == id) {
+                        group.visible = layer.visible;
+                        if let Some(color) = color_from_hex(&layer.color) {
+                            group.color = color;
+                        }
+                    }
+                    id
+                }
+            };
+            ids.insert(layer.id, id);
+        }
+        ids
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| format!("Invalid project file: {}", err))
+    }
+
+    /// Markers in `group`, placed by canvas position. System positions are
+    /// recomputed so they follow the current origin rather than the file's.
+    /// Fails with one message per bad marker, including any outside `canvas_size`.
+    pub fn to_markers(
+        &self,
+        system: &CoordinateSystem,
+        group: u32,
+        canvas_size: (f32, f32),
+    ) -> Result<Vec<Marker>, Vec<String>> {
+        let mut markers = Vec::new();
+        let mut errors = Vec::new();
+        for (i, entry) in self.markers.iter().enumerate() {
+            let position = Pos2::from(entry.position);
+            if !(0.0..=canvas_size.0).contains(&position.x) || !(0.0..=canvas_size.1).contains(&position.y) {
+                errors.push(format!(
+                    "Marker {}: ({}, {}) is outside the {}x{} canvas",
+                    i + 1,
+                    position.x,
+                    position.y,
+                    canvas_size.0,
+                    canvas_size.1
+                ));
+            }
+            match color_from_hex(&entry.color) {
+                Some(color) => {
+                    let mut marker = Marker::new(position, system.to_system_coordinates(position), color, group);
+                    marker.label = entry.label.clone();
+                    marker.note = entry.note.clone();
+                    markers.push(marker);
+                }
+                None => errors.push(format!("Marker {}: invalid color \"{}\"", i + 1, entry.color)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(markers)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Measurements rebuilt from their canvas points, so they follow the
+    /// current origin and units. Fails with one message per bad measurement.
+    pub fn to_measurements(&self) -> Result<Vec<Measurement>, Vec<String>> {
+        let mut measurements = Vec::new();
+        let mut errors = Vec::new();
+        for (i, record) in self.measurements.iter().enumerate() {
+            match record.to_measurement() {
+                Ok(measurement) => measurements.push(measurement),
+                Err(err) => errors.push(format!("Measurement {}: {}", i + 1, err)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(measurements)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker_at(x: f32, y: f32) -> Marker {
+        Marker::new(Pos2::new(x, y), Pos2::new(x, y), Color32::from_rgb(255, 0, 128), GroupManager::DEFAULT_GROUP)
+    }
+
+    #[test]
+    fn markers_survive_a_json_round_trip() {
+        let system = CoordinateSystem::new(OriginMode::TopLeft);
+        let mut markers = vec![marker_at(10.0, 20.0), marker_at(30.5, 40.0)];
+        markers[1].label = Some("Button".to_string());
+        markers[1].note = "Primary action".to_string();
+        let json = ProjectExport::new((100.0, 50.0), &system, 10.0, &GroupManager::default(), &markers).to_json();
+
+        let project = ProjectExport::from_json(&json).unwrap();
+        assert_eq!((project.width, project.height, project.grid_size), (100.0, 50.0, 10.0));
+        let imported = project.to_markers(&system, GroupManager::DEFAULT_GROUP, (100.0, 50.0)).ok().unwrap();
+        // Creation times aren't part of the file
+        let saved = |marker: &Marker| (marker.position, marker.color, marker.label.clone(), marker.note.clone());
+        assert!(imported.iter().map(saved).eq(markers.iter().map(saved)));
+    }
+
+    #[test]
+    fn bad_markers_are_all_reported() {
+        let system = CoordinateSystem::new(OriginMode::TopLeft);
+        let markers = [marker_at(10.0, 20.0), marker_at(150.0, 20.0)];
+        let mut project = ProjectExport::new((200.0, 50.0), &system, 10.0, &GroupManager::default(), &markers);
+        project.markers[0].color = "red".to_string();
+        let errors = project.to_markers(&system, GroupManager::DEFAULT_GROUP, (100.0, 50.0)).err().unwrap();
+        assert_eq!(
+            errors,
+            ["Marker 1: invalid color \"red\"", "Marker 2: (150, 20) is outside the 100x50 canvas"]
+        );
+    }
+
+    #[test]
+    fn measurements_survive_a_json_round_trip() {
+        let system = CoordinateSystem::new(OriginMode::TopLeft);
+        let measurements = [
+            Measurement::Distance {
+                from: Pos2::new(1.0, 2.0),
+                to: Pos2::new(4.0, 6.0),
+            },
+            Measurement::Circle {
+                center: Pos2::new(50.0, 25.0),
+                radius: 10.0,
+            },
+        ];
+        let mut project = ProjectExport::new((100.0, 50.0), &system, 10.0, &GroupManager::default(), &[]);
+        project.measurements = measurements.iter().map(|measurement| measurement.record(&|pos| pos)).collect();
+
+        let imported = ProjectExport::from_json(&project.to_json()).unwrap().to_measurements().unwrap();
+        assert_eq!(imported, measurements);
+    }
+
+    #[test]
+    fn files_without_measurements_still_load() {
+        let json = r#"{"width": 100, "height": 50, "origin_top_left": true, "grid_size": 10, "markers": []}"#;
+        let project = ProjectExport::from_json(json).unwrap();
+        assert!(project.to_measurements().unwrap().is_empty());
+        assert!(!project.to_json().contains("measurements"));
+    }
+
+    #[test]
+    fn hex_colors_need_six_or_eight_digits() {
+        assert!(color_from_hex("#ff0080") == Some(Color32::from_rgb(255, 0, 128)));
+        assert!(color_from_hex("#ff008080") == Some(Color32::from_rgba_unmultiplied(255, 0, 128, 128)));
+        assert!(color_from_hex("ff0080").is_none());
+        assert!(color_from_hex("#ff00").is_none());
+        assert!(color_from_hex("#gg0080").is_none());
+    }
+
+    #[test]
+    fn layers_merge_into_groups_by_name() {
+        let mut groups = GroupManager::default();
+        let existing = groups.add("Buttons".to_string());
+        let mut project = ProjectExport::new(
+            (100.0, 50.0),
+            &CoordinateSystem::new(OriginMode::TopLeft),
+            10.0,
+            &GroupManager::default(),
+            &[],
+        );
+        project.layers = ["Buttons", "Icons"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| ProjectLayer {
+                id: i as u32 + 7,
+                name: name.to_string(),
+                color: "#00ff00ff".to_string(),
+                visible: false,
+            })
+            .collect();
+
+        let ids = project.merge_layers(&mut groups);
+        assert_eq!(ids[&7], existing);
+        let icons = groups.get(ids[&8]).unwrap();
+        assert_eq!((icons.name.as_str(), icons.visible, icons.color), ("Icons", false, Color32::GREEN));
+        assert_eq!(groups.groups().len(), 3);
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+
+pub const MAX_RECENT_FILES: usize = 10;
+
+// Moves `path` to the front of a recent files list, dropping the oldest
+// entries past MAX_RECENT_FILES
+pub fn push_recent_file(list: &mut Vec<PathBuf>, path: &Path) {
+    list.retain(|existing| existing != path);
+    list.insert(0, path.to_path_buf());
+    list.truncate(MAX_RECENT_FILES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_files_are_deduplicated_newest_first() {
+        let mut list = Vec::new();
+        push_recent_file(&mut list, Path::new("a.json"));
+        push_recent_file(&mut list, Path::new("b.json"));
+        push_recent_file(&mut list, Path::new("a.json"));
+        assert_eq!(list, [PathBuf::from("a.json"), PathBuf::from("b.json")]);
+    }
+
+    #[test]
+    fn recent_files_are_capped() {
+        let mut list = Vec::new();
+        for i in 0..15 {
+            push_recent_file(&mut list, &PathBuf::from(format!("{}.png", i)));
+        }
+        assert_eq!(list.len(), MAX_RECENT_FILES);
+        assert_eq!(list[0], PathBuf::from("14.png"));
+        assert_eq!(list[MAX_RECENT_FILES - 1], PathBuf::from("5.png"));
+    }
+}
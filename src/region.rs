@@ -0,0 +1,188 @@
+use egui::{Color32, Rect};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+pub const DOMINANT_COLORS: usize = 5;
+// Regions with more pixels than this are analyzed on a worker thread
+pub const BACKGROUND_PIXELS: usize = 250_000;
+
+/// Average and most common colors of an image region. Fully transparent
+/// pixels are skipped.
+pub struct RegionColors {
+    pub average: Color32,
+    pub dominant: Vec<(Color32, f32)>, // Mean color of each bucket and its share of the pixels, most common first
+    pub pixel_count: usize,
+}
+
+// Pixels of `image` inside `region`, which is in image px (one per canvas
+// px). Parts of the region outside the image are dropped; None when nothing
+// overlaps.
+pub fn crop(image: &RgbaImage, region: Rect) -> Option<RgbaImage> {
+    let min_x = region.min.x.floor().max(0.0) as u32;
+    let min_y = region.min.y.floor().max(0.0) as u32;
+    let max_x = region.max.x.ceil().min(image.width() as f32).max(0.0) as u32;
+    let max_y = region.max.y.ceil().min(image.height() as f32).max(0.0) as u32;
+    if min_x >= max_x || min_y >= max_y {
+        return None;
+    }
+    Some(image::imageops::crop_imm(image, min_x, min_y, max_x - min_x, max_y - min_y).to_image())
+}
+
+/// Averages all pixels and buckets them at 4 bits per channel for the
+/// dominant colors. `rows_done` counts finished rows for progress reporting.
+pub fn analyze(pixels: &RgbaImage, rows_done: &AtomicUsize) -> RegionColors {
+    let mut totals = [0u64; 3];
+    let mut buckets: HashMap<u16, ([u64; 3], usize)> = HashMap::new();
+    let mut pixel_count = 0;
+    for row in pixels.rows() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.0;
+            if a == 0 {
+                continue;
+            }
+            let key = (r as u16 >> 4) << 8 | (g as u16 >> 4) << 4 | b as u16 >> 4;
+            let (sums, count) = buckets.entry(key).or_insert(([0; 3], 0));
+            for (i, channel) in [r, g, b].into_iter().enumerate() {
+                sums[i] += channel as u64;
+                totals[i] += channel as u64;
+            }
+            *count += 1;
+            pixel_count += 1;
+        }
+        rows_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mean = |sums: [u64; 3], count: usize| {
+        let channel = |sum: u64| (sum as f64 / count as f64).round() as u8;
+        Color32::from_rgb(channel(sums[0]), channel(sums[1]), channel(sums[2]))
+    };
+    let mut dominant: Vec<(Color32, usize)> = buckets
+        .into_values()
+        .map(|(sums, count)| (mean(sums, count), count))
+        .collect();
+    // Ties are broken by color so the order doesn't depend on hashing
+    dominant.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_array().cmp(&b.0.to_array())));
+    dominant.truncate(DOMINANT_COLORS);
+
+    RegionColors {
+        average: if pixel_count == 0 { Color32::BLACK } else { mean(totals, pixel_count) },
+        dominant: dominant
+            .into_iter()
+            .map(|(color, count)| (color, count as f32 / pixel_count as f32))
+            .collect(),
+        pixel_count,
+    }
+}
+
+// Analysis of a large region on a worker thread. Poll it once per frame.
+pub struct RegionAnalysis {
+    rows: usize,
+    rows_done: Arc<AtomicUsize>,
+    receiver: Receiver<RegionColors>,
+}
+
+impl RegionAnalysis {
+    pub fn start(pixels: RgbaImage) -> Self {
+        let rows = pixels.height() as usize;
+        let rows_done = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+        let thread_rows_done = rows_done.clone();
+        thread::spawn(move || {
+            // The receiver is gone if the analysis was replaced or cancelled
+            let _ = sender.send(analyze(&pixels, &thread_rows_done));
+        });
+        Self {
+            rows,
+            rows_done,
+            receiver,
+        }
+    }
+
+    /// Fraction of rows processed so far, 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.rows_done.load(Ordering::Relaxed) as f32 / self.rows.max(1) as f32
+    }
+
+    /// The result once the worker is done
+    pub fn poll(&self) -> Option<RegionColors> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Pos2;
+    use image::Rgba;
+
+    #[test]
+    fn crop_keeps_only_the_overlap() {
+        let image = RgbaImage::new(10, 8);
+        let region = Rect::from_min_max(Pos2::new(-5.0, 6.5), Pos2::new(4.2, 20.0));
+        let cropped = crop(&image, region).unwrap();
+        assert_eq!(cropped.dimensions(), (5, 2));
+        assert!(crop(&image, Rect::from_min_max(Pos2::new(12.0, 0.0), Pos2::new(20.0, 5.0))).is_none());
+    }
+
+    #[test]
+    fn empty_crops_are_none() {
+        let image = RgbaImage::new(10, 8);
+        let point = Pos2::new(3.0, 3.0);
+        assert!(crop(&image, Rect::from_min_max(point, point)).is_none());
+        assert!(crop(&RgbaImage::new(0, 0), Rect::from_min_max(Pos2::ZERO, Pos2::new(5.0, 5.0))).is_none());
+    }
+
+    #[test]
+    fn single_color_region() {
+        let color = Rgba([200, 100, 50, 255]);
+        let cropped = crop(&RgbaImage::from_pixel(6, 6, color), Rect::from_min_max(Pos2::ZERO, Pos2::new(3.0, 3.0)));
+        let colors = analyze(&cropped.unwrap(), &AtomicUsize::new(0));
+        assert_eq!(colors.pixel_count, 9);
+        assert_eq!(colors.average, Color32::from_rgb(200, 100, 50));
+        assert_eq!(colors.dominant, [(Color32::from_rgb(200, 100, 50), 1.0)]);
+    }
+
+    #[test]
+    fn average_and_dominant_colors() {
+        let mut image = RgbaImage::from_pixel(4, 1, Rgba([255, 0, 0, 255]));
+        image.put_pixel(3, 0, Rgba([0, 0, 255, 255]));
+        let colors = analyze(&image, &AtomicUsize::new(0));
+        assert_eq!(colors.pixel_count, 4);
+        assert_eq!(colors.average, Color32::from_rgb(191, 0, 64));
+        assert_eq!(colors.dominant, [(Color32::RED, 0.75), (Color32::BLUE, 0.25)]);
+    }
+
+    #[test]
+    fn similar_colors_share_a_bucket() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([16, 32, 48, 255]));
+        image.put_pixel(1, 0, Rgba([18, 34, 50, 255]));
+        let colors = analyze(&image, &AtomicUsize::new(0));
+        assert_eq!(colors.dominant, [(Color32::from_rgb(17, 33, 49), 1.0)]);
+    }
+
+    #[test]
+    fn transparent_pixels_are_skipped() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([0, 255, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 0]));
+        let colors = analyze(&image, &AtomicUsize::new(0));
+        assert_eq!(colors.pixel_count, 1);
+        assert_eq!(colors.average, Color32::GREEN);
+    }
+
+    #[test]
+    fn worker_reports_the_result() {
+        let analysis = RegionAnalysis::start(RgbaImage::from_pixel(3, 3, Rgba([10, 20, 30, 255])));
+        let colors = loop {
+            if let Some(colors) = analysis.poll() {
+                break colors;
+            }
+            thread::yield_now();
+        };
+        assert_eq!(colors.average, Color32::from_rgb(10, 20, 30));
+        assert_eq!(analysis.progress(), 1.0);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::canvas::Canvas;
+use crate::coordinate::CoordinateSystem;
+use egui::epaint::TextShape;
+use egui::{Color32, FontId, Painter, Pos2, Rect, Stroke};
+
+// Major ticks are spaced at least this many screen pixels apart
+const MIN_MAJOR_SPACING: f32 = 80.0;
+
+// Stands out against both axis colors
+const SPAN_COLOR: Color32 = Color32::from_rgb(255, 200, 0);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RulerAxis {
+    X,
+    Y,
+}
+
+impl RulerAxis {
+    // XYZ = RGB, as in most 3D tools
+    pub fn color(&self) -> Color32 {
+        match self {
+            RulerAxis::X => Color32::from_rgb(235, 80, 80),
+            RulerAxis::Y => Color32::from_rgb(90, 200, 90),
+        }
+    }
+}
+
+/// Major and minor tick steps in system units, following a 1-2-5 sequence
+pub fn tick_steps(zoom: f32) -> (f32, f32) {
+    let min_step = MIN_MAJOR_SPACING / zoom;
+    let magnitude = 10f32.powf(min_step.log10().floor());
+    for (multiple, subdivisions) in [(1.0, 5.0), (2.0, 4.0), (5.0, 5.0)] {
+        let step = magnitude * multiple;
+        if step >= min_step {
+            return (step, step / subdivisions);
+        }
+    }
+    (magnitude * 10.0, magnitude * 2.0)
+}
+
+// Drops float noise such as 0.30000001 from tick labels
+fn format_tick(value: f32) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    // Avoid "-0"
+    if rounded == 0.0 {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+// Strip along one edge of the canvas view, labelled in system coordinates
+pub struct Ruler {
+    pub axis: RulerAxis,
+    pub rect: Rect,
+}
+
+impl Ruler {
+    /// Horizontal ruler along the top edge and vertical ruler along the left edge
+    pub fn for_view(view_rect: Rect, thickness: f32) -> [Ruler; 2] {
+        [
+            Ruler {
+                axis: RulerAxis::X,
+                rect: Rect::from_min_max(
+                    Pos2::new(view_rect.min.x + thickness, view_rect.min.y),
+                    Pos2::new(view_rect.max.x, view_rect.min.y + thickness),
+                ),
+            },
+            Ruler {
+                axis: RulerAxis::Y,
+                rect: Rect::from_min_max(
+                    Pos2::new(view_rect.min.x, view_rect.min.y + thickness),
+                    Pos2::new(view_rect.min.x + thickness, view_rect.max.y),
+                ),
+            },
+        ]
+    }
+
+    // Screen coordinate along the ruler for a system value
+    fn screen_position(&self, value: f32, canvas: &Canvas, view_rect: Rect, system: &CoordinateSystem) -> f32 {
+        let canvas_pos = system.from_system_coordinates(Pos2::new(value, value));
+        let screen_pos = canvas.canvas_to_screen_pos(canvas_pos, view_rect);
+        match self.axis {
+            RulerAxis::X => screen_pos.x,
+            RulerAxis::Y => screen_pos.y,
+        }
+    }
+
+    /// System value under a screen coordinate along the ruler
+    pub fn value_at_pos(&self, pos: Pos2, canvas: &Canvas, view_rect: Rect, system: &CoordinateSystem) -> f32 {
+        match self.axis {
+            RulerAxis::X => self.value_at(pos.x, canvas, view_rect, system),
+            RulerAxis::Y => self.value_at(pos.y, canvas, view_rect, system),
+        }
+    }
+
+    fn value_at(&self, screen: f32, canvas: &Canvas, view_rect: Rect, system: &CoordinateSystem) -> f32 {
+        let canvas_pos = canvas.screen_to_canvas_pos(Pos2::new(screen, screen), view_rect);
+        let system_pos = system.to_system_coordinates(canvas_pos);
+        match self.axis {
+            RulerAxis::X => system_pos.x,
+            RulerAxis::Y => system_pos.y,
+        }
+    }
+
+    pub fn draw(&self, painter: &Painter, canvas: &Canvas, view_rect: Rect, system: &CoordinateSystem) {
+        let painter = painter.with_clip_rect(self.rect.intersect(painter.clip_rect()));
+        painter.rect_filled(self.rect, 0.0, Color32::from_black_alpha(170));
+
+        let color = self.axis.color();
+        let stroke = Stroke::new(1.0, color);
+        let font = FontId::proportional((self.thickness() * 0.45).clamp(7.0, 14.0));
+
+        let (start, end) = match self.axis {
+            RulerAxis::X => (self.rect.min.x, self.rect.max.x),
+            RulerAxis::Y => (self.rect.min.y, self.rect.max.y),
+        };
+        let (a, b) = (
+            self.value_at(start, canvas, view_rect, system),
+            self.value_at(end, canvas, view_rect, system),
+        );
+        let (major, minor) = tick_steps(canvas.get_zoom());
+        let first = (a.min(b) / minor).floor() as i64;
+        let last = (a.max(b) / minor).ceil() as i64;
+
+        for i in first..=last {
+            let value = i as f32 * minor;
+            let is_major = (value / major - (value / major).round()).abs() < 1e-3;
+            let length = if is_major { self.thickness() } else { self.thickness() * 0.3 };
+            let screen = self.screen_position(value, canvas, view_rect, system);
+
+            match self.axis {
+                RulerAxis::X => {
+                    let bottom = self.rect.max.y;
+                    painter.line_segment([Pos2::new(screen, bottom - length), Pos2::new(screen, bottom)], stroke);
+                    if is_major {
+                        let galley = painter.layout_no_wrap(format_tick(value), font.clone(), color);
+                        painter.galley(Pos2::new(screen + 2.0, self.rect.min.y + 1.0), galley);
+                    }
+                }
+                RulerAxis::Y => {
+                    let right = self.rect.max.x;
+                    painter.line_segment([Pos2::new(right - length, screen), Pos2::new(right, screen)], stroke);
+                    if is_major {
+                        // Rotated to read bottom-to-top, running up from the tick
+                        let galley = painter.layout_no_wrap(format_tick(value), font.clone(), color);
+                        let mut text = TextShape::new(Pos2::new(self.rect.min.x + 1.0, screen - 2.0), galley);
+                        text.angle = -std::f32::consts::FRAC_PI_2;
+                        painter.add(text);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Highlights a measured span between two system values, with guide
+    /// lines across the view and a label next to the ruler
+    pub fn draw_span(
+        &self,
+        painter: &Painter,
+        canvas: &Canvas,
+        view_rect: Rect,
+        system: &CoordinateSystem,
+        span: (f32, f32),
+        label: &str,
+    ) {
+        let (a, b) = (
+            self.screen_position(span.0, canvas, view_rect, system),
+            self.screen_position(span.1, canvas, view_rect, system),
+        );
+        let (highlight, guides, label_pos, align) = match self.axis {
+            RulerAxis::X => (
+                Rect::from_x_y_ranges(a.min(b)..=a.max(b), self.rect.y_range()),
+                [a, b].map(|x| [Pos2::new(x, self.rect.max.y), Pos2::new(x, view_rect.max.y)]),
+                Pos2::new((a + b) / 2.0, self.rect.max.y + 4.0),
+                egui::Align2::CENTER_TOP,
+            ),
+            RulerAxis::Y => (
+                Rect::from_x_y_ranges(self.rect.x_range(), a.min(b)..=a.max(b)),
+                [a, b].map(|y| [Pos2::new(self.rect.max.x, y), Pos2::new(view_rect.max.x, y)]),
+                Pos2::new(self.rect.max.x + 4.0, (a + b) / 2.0),
+                egui::Align2::LEFT_CENTER,
+            ),
+        };
+
+        let clipped = painter.with_clip_rect(view_rect.intersect(painter.clip_rect()));
+        clipped.rect_filled(highlight, 0.0, SPAN_COLOR.gamma_multiply(0.5));
+        for guide in guides {
+            clipped.extend(egui::Shape::dashed_line(&guide, Stroke::new(1.0, SPAN_COLOR), 4.0, 4.0));
+        }
+        let galley = clipped.layout_no_wrap(label.to_string(), FontId::proportional(12.0), Color32::BLACK);
+        let label_rect = align.anchor_rect(Rect::from_min_size(label_pos, galley.size())).expand(3.0);
+        clipped.rect_filled(label_rect, 3.0, SPAN_COLOR);
+        clipped.galley(label_rect.shrink(3.0).min, galley);
+    }
+
+    fn thickness(&self) -> f32 {
+        match self.axis {
+            RulerAxis::X => self.rect.height(),
+            RulerAxis::Y => self.rect.width(),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use serde::ser::Serializer;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Context about the annotated screenshot (build, device, tester, ...),
+// carried into exports
+#[derive(Clone, Default, Serialize)]
+pub struct SessionInfo {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    #[serde(
+        rename = "metadata",
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_fields"
+    )]
+    pub fields: Vec<(String, String)>, // Custom key/value pairs in display order
+}
+
+fn serialize_fields<S: Serializer>(fields: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(valid_fields(fields))
+}
+
+// Rows with a key, skipping later rows that repeat an earlier key
+fn valid_fields(fields: &[(String, String)]) -> impl Iterator<Item = (&str, &str)> {
+    let mut seen = HashSet::new();
+    fields
+        .iter()
+        .map(|(key, value)| (key.trim(), value.as_str()))
+        .filter(move |(key, _)| !key.is_empty() && seen.insert(*key))
+}
+
+impl SessionInfo {
+    /// First key used by more than one row, if any
+    pub fn duplicate_key(&self) -> Option<&str> {
+        let mut seen = HashSet::new();
+        self.fields
+            .iter()
+            .map(|(key, _)| key.trim())
+            .filter(|key| !key.is_empty())
+            .find(|key| !seen.insert(*key))
+    }
+
+    /// "Label: value" lines for text export headers
+    pub fn header_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.title.is_empty() {
+            lines.push(format!("Title: {}", self.title));
+        }
+        if !self.author.is_empty() {
+            lines.push(format!("Author: {}", self.author));
+        }
+        lines.extend(valid_fields(&self.fields).map(|(key, value)| format!("{}: {}", key, value)));
+        if !self.description.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.description.lines().map(str::to_string));
+        }
+        lines
+    }
+}
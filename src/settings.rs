@@ -0,0 +1,121 @@
+use crate::export;
+use crate::grid::{GridPreset, GridStyle};
+use crate::input::MouseBindings;
+use crate::stamp::Stamp;
+use crate::ui::{CopyTemplate, PanelLayout, UiState, ViewBookmark};
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// The subset of UiState that survives restarts. Missing keys fall back to
+// the defaults, so older files keep loading as settings are added.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub grid_size: f32,
+    pub grid_size_y: Option<f32>, // Missing from files saved before per-axis sizes, which use grid_size for both
+    pub grid_style: GridStyle,
+    pub grid_subdivisions: u32,
+    pub grid_rotation: f32,
+    pub show_grid: bool,
+    pub enable_snapping: bool,
+    pub dark_mode: bool,
+    pub selected_resolution: String,
+    pub custom_width: f32,
+    pub custom_height: f32,
+    pub marker_color: [u8; 4], // Unmultiplied RGBA
+    pub panel_layout: PanelLayout,
+    pub mouse_bindings: MouseBindings,
+    pub view_bookmarks: Vec<ViewBookmark>,
+    pub stamps: Vec<Stamp>,
+    pub grid_presets: Vec<GridPreset>,
+    pub copy_template: CopyTemplate,
+    pub recent_sessions: Vec<PathBuf>,
+    pub recent_images: Vec<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_ui_state(&UiState::default())
+    }
+}
+
+impl Settings {
+    pub fn from_ui_state(ui_state: &UiState) -> Self {
+        Self {
+            grid_size: ui_state.grid_size_x,
+            grid_size_y: Some(ui_state.grid_size_y),
+            grid_style: ui_state.grid_style,
+            grid_subdivisions: ui_state.grid_subdivisions,
+            grid_rotation: ui_state.grid_rotation,
+            show_grid: ui_state.show_grid,
+            enable_snapping: ui_state.enable_snapping,
+            dark_mode: ui_state.dark_mode,
+            selected_resolution: ui_state.selected_resolution.clone(),
+            custom_width: ui_state.custom_width,
+            custom_height: ui_state.custom_height,
+            marker_color: ui_state.marker_color.to_srgba_unmultiplied(),
+            panel_layout: ui_state.panel_layout.clone(),
+            mouse_bindings: ui_state.mouse_bindings,
+            view_bookmarks: ui_state.view_bookmarks.clone(),
+            stamps: ui_state.stamps.clone(),
+            grid_presets: ui_state.grid_presets.clone(),
+            copy_template: ui_state.copy_template.clone(),
+            recent_sessions: ui_state.recent_sessions.clone(),
+            recent_images: ui_state.recent_images.clone(),
+        }
+    }
+
+    pub fn apply(&self, ui_state: &mut UiState) {
+        let [r, g, b, a] = self.marker_color;
+        ui_state.grid_size_x = self.grid_size;
+        ui_state.grid_size_y = self.grid_size_y.unwrap_or(self.grid_size);
+        ui_state.grid_style = self.grid_style;
+        ui_state.grid_subdivisions = self.grid_subdivisions.max(1);
+        ui_state.grid_rotation = self.grid_rotation;
+        ui_state.show_grid = self.show_grid;
+        ui_state.enable_snapping = self.enable_snapping;
+        ui_state.dark_mode = self.dark_mode;
+        ui_state.selected_resolution = self.selected_resolution.clone();
+        ui_state.custom_width = self.custom_width;
+        ui_state.custom_height = self.custom_height;
+        ui_state.marker_color = Color32::from_rgba_unmultiplied(r, g, b, a);
+        ui_state.panel_layout = self.panel_layout.clone();
+        ui_state.mouse_bindings = self.mouse_bindings;
+        ui_state.view_bookmarks = self.view_bookmarks.clone();
+        ui_state.stamps = self.stamps.clone();
+        ui_state.active_stamp = None; // May point past the loaded list
+        ui_state.grid_presets = self.grid_presets.clone();
+        ui_state.selected_grid_preset = 0;
+        ui_state.copy_template = self.copy_template.clone();
+        ui_state.recent_sessions = self.recent_sessions.clone();
+        ui_state.recent_images = self.recent_images.clone();
+    }
+
+    /// e.g. ~/.config/coordinate_picker/settings.json on Linux
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("coordinate_picker").join("settings.json"))
+    }
+
+    /// Saved settings, or None when there's no readable config file
+    pub fn load() -> Option<Self> {
+        let json = std::fs::read_to_string(Self::path()?).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path().ok_or("No config directory on this platform")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        export::write_atomic(&path, json.as_bytes()).map_err(|err| err.to_string())
+    }
+
+    pub fn delete() -> Result<(), String> {
+        match Self::path() {
+            Some(path) if path.exists() => std::fs::remove_file(path).map_err(|err| err.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
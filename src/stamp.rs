@@ -0,0 +1,60 @@
+use egui::{Pos2, Vec2};
+use serde::{Deserialize, Serialize};
+
+// A saved constellation of points, stored relative to its bounding box's
+// top-left corner so it can be stamped anywhere
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub name: String,
+    pub offsets: Vec<(f32, f32)>,
+}
+
+impl Stamp {
+    pub fn from_positions(name: String, positions: &[Pos2]) -> Option<Self> {
+        let first = positions.first()?;
+        let min = positions.iter().fold(*first, |min, pos| min.min(*pos));
+        let offsets = positions
+            .iter()
+            .map(|pos| (pos.x - min.x, pos.y - min.y))
+            .collect();
+        Some(Self { name, offsets })
+    }
+
+    /// Canvas positions with the stamp's top-left anchored at `anchor`
+    pub fn positions_at(&self, anchor: Pos2, scale: f32) -> Vec<Pos2> {
+        self.offsets
+            .iter()
+            .map(|&(x, y)| anchor + Vec2::new(x, y) * scale)
+            .collect()
+    }
+}
+
+pub fn stamps_to_json(stamps: &[Stamp]) -> String {
+    serde_json::to_string_pretty(stamps).unwrap_or_default()
+}
+
+pub fn stamps_from_json(json: &str) -> Result<Vec<Stamp>, String> {
+    serde_json::from_str(json).map_err(|err| format!("Invalid stamp file: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_are_placed_relative_to_their_top_left() {
+        let positions = [Pos2::new(30.0, 50.0), Pos2::new(10.0, 60.0), Pos2::new(20.0, 40.0)];
+        let stamp = Stamp::from_positions("Cluster".to_string(), &positions).unwrap();
+        assert_eq!(stamp.offsets, [(20.0, 10.0), (0.0, 20.0), (10.0, 0.0)]);
+        let placed = stamp.positions_at(Pos2::new(100.0, 100.0), 2.0);
+        assert_eq!(placed, [Pos2::new(140.0, 120.0), Pos2::new(100.0, 140.0), Pos2::new(120.0, 100.0)]);
+        assert!(Stamp::from_positions("Empty".to_string(), &[]).is_none());
+    }
+
+    #[test]
+    fn stamp_files_round_trip() {
+        let stamps = vec![Stamp::from_positions("Pair".to_string(), &[Pos2::ZERO, Pos2::new(5.0, 5.0)]).unwrap()];
+        assert!(stamps_from_json(&stamps_to_json(&stamps)).ok() == Some(stamps));
+        assert!(stamps_from_json("{}").is_err());
+    }
+}
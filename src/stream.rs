@@ -0,0 +1,119 @@
+use egui::Pos2;
+use serde_json::json;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+const HOVER_INTERVAL: f64 = 1.0 / 30.0;
+
+enum StreamMessage {
+    Hover(String),
+    Marker(String),
+}
+
+// Localhost WebSocket server that broadcasts the cursor position and newly
+// placed markers, e.g. for a live OBS overlay. Stops when dropped.
+pub struct PositionStream {
+    sender: Sender<StreamMessage>,
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    last_hover: Option<(f64, Pos2)>,
+}
+
+impl PositionStream {
+    pub fn start(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_shutdown = shutdown.clone();
+        let thread = thread::spawn(move || serve(listener, receiver, thread_shutdown));
+
+        Ok(Self {
+            sender,
+            address,
+            shutdown,
+            thread: Some(thread),
+            last_hover: None,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // Rate limited to ~30Hz and skipped when the position hasn't changed
+    pub fn send_hover(&mut self, position: Pos2, now: f64) {
+        if let Some((sent_at, sent_position)) = self.last_hover {
+            if sent_position == position || now - sent_at < HOVER_INTERVAL {
+                return;
+            }
+        }
+        self.last_hover = Some((now, position));
+        let message = json!({ "type": "hover", "x": position.x, "y": position.y });
+        let _ = self.sender.send(StreamMessage::Hover(message.to_string()));
+    }
+
+    pub fn send_marker(&self, position: Pos2, label: &str) {
+        let message = json!({ "type": "marker", "x": position.x, "y": position.y, "label": label });
+        let _ = self.sender.send(StreamMessage::Marker(message.to_string()));
+    }
+}
+
+impl Drop for PositionStream {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve(listener: TcpListener, receiver: Receiver<StreamMessage>, shutdown: Arc<AtomicBool>) {
+    let mut clients: Vec<WebSocket<TcpStream>> = Vec::new();
+    // Last hover and marker messages, replayed to new connections
+    let mut last_hover: Option<String> = None;
+    let mut last_marker: Option<String> = None;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        while let Ok((stream, _)) = listener.accept() {
+            if stream.set_nonblocking(false).is_err() {
+                continue;
+            }
+            let _ = stream.set_write_timeout(Some(Duration::from_millis(200)));
+            if let Ok(mut client) = tungstenite::accept(stream) {
+                let retained = last_marker.iter().chain(last_hover.iter());
+                let ok = retained.into_iter().all(|text| client.send(Message::Text(text.clone())).is_ok());
+                if ok {
+                    clients.push(client);
+                }
+            }
+        }
+
+        let mut outgoing = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            let text = match message {
+                StreamMessage::Hover(text) => last_hover.insert(text),
+                StreamMessage::Marker(text) => last_marker.insert(text),
+            };
+            outgoing.push(text.clone());
+        }
+        for text in outgoing {
+            clients.retain_mut(|client| client.send(Message::Text(text.clone())).is_ok());
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    for mut client in clients {
+        let _ = client.close(None);
+        let _ = client.flush();
+    }
+}
@@ -0,0 +1,66 @@
+use egui::Color32;
+
+// The native picker is reached through a helper process: AppleScript's
+// `choose color` on macOS and the WinForms ColorDialog on Windows.
+// Elsewhere the button is hidden and only the egui picker is offered.
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+// Parse "r, g, b" channels given on a 0..=max scale
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn parse_rgb(output: &str, max: f32) -> Option<Color32> {
+    let channels: Vec<u8> = output
+        .trim()
+        .split(',')
+        .map(|channel| channel.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?
+        .into_iter()
+        .map(|channel| (channel / max * 255.0).round().clamp(0.0, 255.0) as u8)
+        .collect();
+    match channels[..] {
+        [r, g, b] => Some(Color32::from_rgb(r, g, b)),
+        _ => None,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn run_picker(program: &str, args: &[&str], max: f32) -> Option<Color32> {
+    // Cancelling the dialog exits with an error or prints nothing
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_rgb(&String::from_utf8_lossy(&output.stdout), max)
+}
+
+/// Blocks while the system color dialog is open; None if it was cancelled
+#[cfg(target_os = "macos")]
+pub fn pick_color(initial: Color32) -> Option<Color32> {
+    // AppleScript colors use 16-bit channels
+    let script = format!(
+        "choose color default color {{{}, {}, {}}}",
+        initial.r() as u32 * 257,
+        initial.g() as u32 * 257,
+        initial.b() as u32 * 257
+    );
+    run_picker("osascript", &["-e", &script], 65535.0)
+}
+
+#[cfg(target_os = "windows")]
+pub fn pick_color(initial: Color32) -> Option<Color32> {
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $dialog = New-Object System.Windows.Forms.ColorDialog; \
+         $dialog.FullOpen = $true; \
+         $dialog.Color = [System.Drawing.Color]::FromArgb({}, {}, {}); \
+         if ($dialog.ShowDialog() -eq 'OK') {{ '{{0}},{{1}},{{2}}' -f $dialog.Color.R, $dialog.Color.G, $dialog.Color.B }}",
+        initial.r(),
+        initial.g(),
+        initial.b()
+    );
+    run_picker("powershell", &["-NoProfile", "-NonInteractive", "-Command", &script], 255.0)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn pick_color(_initial: Color32) -> Option<Color32> {
+    None
+}
@@ -1,30 +1,481 @@
 
-use egui::{Color32, Pos2};
+use crate::colormap::{ColorBy, Colormap};
+use crate::command::CommandEntry;
+use crate::coordinate::{AffineTransform, OriginMode, OutputTransform, PixelRatio};
+use crate::export::{ColorProfile, CompositeMode, CsvOptions, ExportFormat, PngLayout};
+use crate::grid::{GridLayer, GridPreset, GridStyle, RoundingMode, SnapMode};
+use crate::input::{KeyBindings, MouseBindings};
+use crate::marker::{Marker, MarkerKind};
+use crate::region::RegionColors;
+use crate::ruler::RulerAxis;
+use crate::session::SessionInfo;
+use crate::stamp::Stamp;
+use crate::undo;
+use crate::validation::ExpectedPoint;
+use egui::{Color32, Pos2, Rect};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Place,
+    Lasso,
+    Stamp,
+    Bezier,    // Four clicks: start, two control points, end
+    SetOrigin, // The next click becomes the custom origin
+    Measure,   // Click two markers for the distance and angle between them
+    Angle,     // Click an arm point, the vertex, then the other arm point
+}
+
+impl Tool {
+    pub const ALL: [Tool; 7] = [
+        Tool::Place,
+        Tool::Lasso,
+        Tool::Stamp,
+        Tool::Bezier,
+        Tool::SetOrigin,
+        Tool::Measure,
+        Tool::Angle,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Place => "Place",
+            Tool::Lasso => "Lasso",
+            Tool::Stamp => "Stamp",
+            Tool::Bezier => "Bezier",
+            Tool::SetOrigin => "Set Origin",
+            Tool::Measure => "Measure",
+            Tool::Angle => "Angle",
+        }
+    }
+}
+
+// Which pixel space the Copy buttons use when the dual readout is on
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CopyUnits {
+    Device,
+    Css,
+}
+
+impl CopyUnits {
+    pub const ALL: [CopyUnits; 2] = [CopyUnits::Device, CopyUnits::Css];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CopyUnits::Device => "device px",
+            CopyUnits::Css => "css px",
+        }
+    }
+}
+
+// Corner of the canvas view the minimap sits in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MinimapCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl MinimapCorner {
+    pub const ALL: [MinimapCorner; 4] = [
+        MinimapCorner::TopLeft,
+        MinimapCorner::TopRight,
+        MinimapCorner::BottomLeft,
+        MinimapCorner::BottomRight,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MinimapCorner::TopLeft => "Top-Left",
+            MinimapCorner::TopRight => "Top-Right",
+            MinimapCorner::BottomLeft => "Bottom-Left",
+            MinimapCorner::BottomRight => "Bottom-Right",
+        }
+    }
+
+    pub fn align(&self) -> egui::Align2 {
+        match self {
+            MinimapCorner::TopLeft => egui::Align2::LEFT_TOP,
+            MinimapCorner::TopRight => egui::Align2::RIGHT_TOP,
+            MinimapCorner::BottomLeft => egui::Align2::LEFT_BOTTOM,
+            MinimapCorner::BottomRight => egui::Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CrosshairShape {
+    Cross,
+    Horizontal,
+    Vertical,
+    TShape,  // Full horizontal, downward vertical
+    LShape,  // Right half of horizontal, downward vertical
+    DotOnly,
+}
+
+impl CrosshairShape {
+    pub const ALL: [CrosshairShape; 6] = [
+        CrosshairShape::Cross,
+        CrosshairShape::Horizontal,
+        CrosshairShape::Vertical,
+        CrosshairShape::TShape,
+        CrosshairShape::LShape,
+        CrosshairShape::DotOnly,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CrosshairShape::Cross => "Cross",
+            CrosshairShape::Horizontal => "Horizontal",
+            CrosshairShape::Vertical => "Vertical",
+            CrosshairShape::TShape => "T-shape",
+            CrosshairShape::LShape => "L-shape",
+            CrosshairShape::DotOnly => "Dot only",
+        }
+    }
+}
+
+pub struct CrosshairSettings {
+    pub shape: CrosshairShape,
+    pub size: f32, // Arm length in screen pixels
+    pub color: Color32,
+}
+
+// User-defined copy format: `item` is rendered per marker, and Copy All
+// joins the items with `separator` between `prefix` and `suffix`
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyTemplate {
+    pub enabled: bool,
+    pub item: String,
+    pub separator: String,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl Default for CopyTemplate {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            item: "{i}. ({x}, {y})".to_string(),
+            separator: "\\n".to_string(),
+            prefix: String::new(),
+            suffix: String::new(),
+        }
+    }
+}
+
+// 1px grid for pixel-art sized canvases
+pub struct PixelGridMode {
+    pub enabled: bool,
+    pub show_pixel_index: bool,
+}
+
+// Short-lived confirmation message shown over the canvas
+pub struct Toast {
+    pub message: String,
+    pub expires_at: f64,
+}
+
+// How markers follow a switch between top-left and bottom-left origins
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OriginMigration {
+    KeepVisual,  // Points stay put, their numbers change
+    KeepNumeric, // Numbers stay, the points move
+}
+
+impl OriginMigration {
+    pub const ALL: [OriginMigration; 2] = [OriginMigration::KeepVisual, OriginMigration::KeepNumeric];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OriginMigration::KeepVisual => "Keep visual positions",
+            OriginMigration::KeepNumeric => "Keep numeric values",
+        }
+    }
+}
+
+// Origin switch waiting for the user to pick a migration
+pub struct PendingOriginChange {
+    pub origin_mode: OriginMode,
+    pub remember: bool,
+}
+
+// Sections reachable from the compact mode icon strip
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PanelSection {
+    Grid,
+    Markers,
+    Export,
+    Appearance,
+}
+
+impl PanelSection {
+    pub const ALL: [PanelSection; 4] = [
+        PanelSection::Grid,
+        PanelSection::Markers,
+        PanelSection::Export,
+        PanelSection::Appearance,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PanelSection::Grid => "Grid",
+            PanelSection::Markers => "Markers",
+            PanelSection::Export => "Export",
+            PanelSection::Appearance => "Appearance",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            PanelSection::Grid => "#",
+            PanelSection::Markers => "📍",
+            PanelSection::Export => "💾",
+            PanelSection::Appearance => "🎨",
+        }
+    }
+}
+
+// Side panel arrangement, saved between sessions
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelLayout {
+    pub pin_current_position: bool, // Keep the cursor readout above the scrolling settings
+    pub open_sections: HashMap<String, bool>, // Collapsing section title -> open; sections start closed
+}
+
+impl PanelLayout {
+    pub fn is_open(&self, section: &str) -> bool {
+        self.open_sections.get(section).copied().unwrap_or(false)
+    }
+
+    pub fn toggle(&mut self, section: &str) {
+        let open = self.is_open(section);
+        self.open_sections.insert(section.to_string(), !open);
+    }
+}
+
+// Steps through markers in order, as if one object visited each in turn
+pub struct PlaybackState {
+    pub previewing: bool, // Other markers are dimmed while the preview is shown
+    pub playing: bool,
+    pub frame_idx: usize,
+    pub fps: f32,
+    pub last_advance: f64, // egui time of the last frame step
+}
+
+impl Default for PlaybackState {
+    fn default() -> Self {
+        Self {
+            previewing: false,
+            playing: false,
+            frame_idx: 0,
+            fps: 4.0,
+            last_advance: 0.0,
+        }
+    }
+}
+
+// Options of the open "Quantize markers" dialog
+pub struct QuantizeSettings {
+    pub step_x: f32,
+    pub step_y: f32,
+    pub selection_only: bool,
+    pub align_to_grid_lines: bool, // Canvas lattice under the drawn grid instead of multiples of the origin
+    pub rounding: RoundingMode,
+}
+
+// Marker being edited numerically after a double-click
+pub struct MarkerEdit {
+    pub index: usize,
+    pub system_position: Pos2,
+    pub just_opened: bool,
+}
+
+// Named pan/zoom state, stored in canvas space so it survives resolution changes
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewBookmark {
+    pub name: String,
+    pub center: Pos2,
+    pub zoom: f32,
+}
 
 pub struct UiState {
     // Canvas/resolution settings
     pub selected_resolution: String,
     pub custom_width: f32,
     pub custom_height: f32,
+    pub rescale_on_resize: bool, // Scale marker positions with the canvas when its size changes
+    pub pixel_ratio: PixelRatio,
+    pub dual_readout: bool, // Show CSS and device pixels side by side
+    pub copy_units: CopyUnits,
+    pub normalized_decimals: usize, // Digits after the point in normalized units, 2 to 6
+    pub scale_decimals: usize,      // Digits after the point for pixels with a scale other than 1
+    pub percent_decimals: [usize; 2], // Per axis (x, y) in percentage units
+    pub copy_template: CopyTemplate,
+    pub inset_readout: bool, // Distances to the canvas edges and nearest guides
+    pub code_integers: bool,  // "Copy as" code uses whole numbers
+    pub code_decimals: usize, // Float digits for pixel values in "Copy as" code
+    pub code_labels: bool,    // Include marker labels where the code format allows
+    pub python_dicts: bool,   // Python as [{"x": x, "y": y}, ...] instead of [(x, y), ...]
+    pub json_arrays: bool,    // JSON as [[x, y], ...] instead of [{"x": x, "y": y}, ...]
 
     // Grid settings
     pub show_grid: bool,
-    pub grid_size: f32,
+    pub grid_size_x: f32,
+    pub grid_size_y: f32,
+    pub grid_style: GridStyle,
+    pub grid_subdivisions: u32, // Minor cells per grid cell along each axis
+    pub grid_rotation: f32,     // Degrees
+    pub grid_min_spacing: f32, // On-screen px between drawn lines before they're thinned out
+    pub grid_max_spacing: f32, // On-screen px between drawn lines before they're subdivided
+    pub force_integer_positions: bool, // Stored marker positions are whole canvas pixels
+    pub grid_presets: Vec<GridPreset>,
+    pub selected_grid_preset: usize,
+    pub new_grid_preset_name: String,
     pub enable_snapping: bool,
+    pub snap_mode: SnapMode,
+    pub show_snap_scores: bool, // Debug overlay for snap-to-marker candidates
+    pub snap_to_markers: bool,      // Line up with other markers' X or Y, independent of the grid
+    pub marker_snap_threshold: f32, // Screen px
+    pub pixel_grid: PixelGridMode,
 
     // Coordinate system settings
-    pub origin_top_left: bool,
+    pub origin_mode: OriginMode,
+    pub origin_migration: Option<OriginMigration>, // Remembered choice; None asks every time
+    pub pending_origin_change: Option<PendingOriginChange>,
+    pub output_transform: OutputTransform,
+    pub output_matrix: AffineTransform, // Applied after the output transform
+    pub normalise_preserve_aspect: bool,
 
     // Marker settings
     pub marker_color: Color32,
+    pub show_marker_dots: bool,
+    pub color_by: ColorBy, // Overrides stored marker colors while not `Stored`
+    pub colormap: Colormap,
+    pub show_marker_labels: bool,
+    pub show_offscreen_indicators: bool, // Arrows on the view edge towards markers panned out of view
+    pub show_nested_sizes: bool,         // Outline the secondary canvas sizes around the active one
+    pub new_nested_size: String,         // Preset picked in the "Multiple Canvas Sizes" combo
+    pub marker_mesh_threshold: usize,
+    pub undo_depth: usize, // Marker changes kept for undo
+    pub max_markers: usize, // usize::MAX when the limit is disabled
+    pub duplicate_offset_x: f32,
+    pub duplicate_offset_y: f32,
+    pub quantize: Option<QuantizeSettings>, // Some while the quantize dialog is open
+    pub expected_points: Option<Vec<ExpectedPoint>>, // Some while validating against a golden file
+    pub validation_tolerance: f32,                   // px, unless an expected point sets its own
+
+    // Tool and selection state
+    pub active_tool: Tool,
+    pub place_kind: MarkerKind, // Kind of marker the Place tool creates
+    pub selected_markers: HashSet<usize>,
+    pub expanded_notes: HashSet<usize>, // Marker rows showing their detail area
+    pub lasso_path: Vec<Pos2>, // Canvas coordinates of the lasso being drawn
+    pub marquee: Option<(Pos2, Pos2)>, // Canvas-space start and end of a zoom-region drag
+    pub selection_rect: Option<(Pos2, Pos2)>, // Canvas-space start and end of a Shift+drag selection
+    pub analysis_region: Option<Rect>,        // Last finished Shift+drag selection, for Analyze Region
+    pub region_colors: Option<RegionColors>,
+    pub move_selection: bool,                 // Dragging moves the selected markers as a group
+    pub moving_selection: Option<(Pos2, Vec<Marker>)>, // Drag start and the markers before the move
+    pub text_edit_before: Option<Vec<Marker>>, // Markers when a label or note field took focus
+    pub command_entry: CommandEntry,
+
+    // Input settings
+    pub mouse_bindings: MouseBindings,
+    pub key_bindings: KeyBindings,
+    pub show_shortcuts_overlay: bool,
+    pub overlays_suppressed: bool, // Everything over the canvas background hidden by the hide-overlays key
+    pub latch_hide_overlays: bool, // The key toggles instead of hiding only while held
+    pub binding_warning: Option<String>,
 
     // Current position tracking
     pub current_position: Pos2,
     pub current_position_raw: Pos2,
+    pub view_rect: Rect, // Screen rect of the canvas area from the last frame
+    pub dragging_origin: bool,
+    pub origin_before_drag: Option<Option<Pos2>>, // Custom origin when the current origin drag started
+    pub dragging_marker: Option<(usize, Pos2)>, // Index and start position of the marker being dragged
+    pub editing_marker: Option<MarkerEdit>,
 
     // Theme settings
     pub dark_mode: bool,
-    pub recalculate_markers: bool,
+    pub crosshair: CrosshairSettings,
+    pub show_rulers: bool,
+    pub ruler_thickness: f32,
+    pub show_minimap: bool,
+    pub minimap_corner: MinimapCorner,
+    pub smooth_zoom: bool, // Ease scroll-wheel zoom over ~150 ms instead of jumping
+    pub ruler_measure_axis: RulerAxis,
+    pub ruler_measure_start: Option<f32>, // System value where the span drag began
+    pub ruler_measure_end: Option<f32>,
+    pub ruler_measuring: bool, // A span drag is in progress
+    pub gamma: f32,
+    pub color_profile: ColorProfile,
+
+    // Session metadata, included in exports
+    pub session: SessionInfo,
+    pub place_at: Pos2,                 // Typed position in the current coordinate system
+    pub place_at_error: Option<String>, // Why the last Place/Go to was rejected
+    pub settings_error: Option<String>, // Why the config file couldn't be written or removed
+    pub csv_options: CsvOptions,
+    pub show_csv_dialog: bool,
+    pub import_errors: Vec<String>, // Shown in a dialog after a failed marker import
+    pub share_summary: Option<String>, // Some while the share preview is open; editable before copying
+    pub panel_layout: PanelLayout,
+    pub playback: PlaybackState,
+    pub compact_mode: bool,                    // Settings panel shrunk to an icon strip
+    pub compact_section: Option<PanelSection>, // Section popped out from the icon strip
+
+    // Export settings
+    pub export_format: ExportFormat,
+    pub export_dpi: f32,
+    pub html_area_radius: u32,
+    pub android_dp_scale: f32, // 1.0 at 160 dpi (mdpi), 0.5 at 320 dpi (xhdpi)
+    pub png_layout: PngLayout,
+    pub export_status: Option<String>,
+    pub export_split_by_group: bool,
+    pub export_hidden_groups: bool,
+    pub export_basename: String,
+    pub css_grid_fractional: bool,
+    pub clip_path_decimals: usize, // Digits after the point in clip-path percentages
+    pub svg_close_path: bool,      // SVG outline as a <polygon> rather than a <polyline>
+    pub svg_marker_circles: bool,  // SVG outline also gets a colored <circle> per marker
+
+    pub stamps: Vec<Stamp>,
+    pub active_stamp: Option<usize>, // Index into `stamps` placed by the Stamp tool
+    pub bezier_points: Vec<Pos2>,    // Points clicked so far by the Bezier tool
+    pub measure_first: Option<usize>, // Marker picked first by the Measure tool
+    pub measurement: Option<(usize, usize)>,
+    pub angle_points: Vec<Pos2>, // Arm, vertex, arm in canvas coordinates, as clicked so far
+    pub show_measurements: bool, // Draw the kept measurements on the canvas
+    pub stamp_scale: f32,
+    pub new_stamp_name: String,
+
+    pub view_bookmarks: Vec<ViewBookmark>, // Slots 1-9 follow list order
+    pub new_bookmark_name: String,
+
+    // Live position stream
+    #[cfg(feature = "websocket")]
+    pub stream_enabled: bool,
+    #[cfg(feature = "websocket")]
+    pub stream_port: u16,
+    #[cfg(feature = "websocket")]
+    pub stream_error: Option<String>,
+
+    pub toast: Option<Toast>,
+    pub background_error: Option<String>, // Why the last image load failed
+    pub background_opacity: f32,          // Percent
+    pub composite_mode: CompositeMode,
+    pub grid_layer: GridLayer,
+    pub grid_auto_contrast: bool, // Grid color picked from the image's brightness instead of the theme
+    pub recent_sessions: Vec<PathBuf>,    // Project files saved, newest first
+    pub recent_images: Vec<PathBuf>,      // Background images loaded, newest first
+    pub outside_canvas_count: usize, // Markers found outside the canvas by the last check
 }
 
 impl Default for UiState {
@@ -33,15 +484,160 @@ impl Default for UiState {
             selected_resolution: "Full HD (1920x1080)".to_string(),
             custom_width: 1920.0,
             custom_height: 1080.0,
+            rescale_on_resize: false,
+            pixel_ratio: PixelRatio {
+                ratio: 2.0,
+                keep_half_pixels: false,
+            },
+            dual_readout: false,
+            copy_units: CopyUnits::Device,
+            normalized_decimals: 3,
+            scale_decimals: 2,
+            percent_decimals: [1, 1],
+            inset_readout: false,
+            code_integers: true,
+            code_decimals: 1,
+            code_labels: false,
+            python_dicts: false,
+            json_arrays: false,
+            copy_template: CopyTemplate::default(),
             show_grid: true,
-            grid_size: 45.0, // Grid size of 45px works better for a 1920x1080 canvas
+            grid_size_x: 45.0, // Grid size of 45px works better for a 1920x1080 canvas
+            grid_size_y: 45.0,
+            grid_style: GridStyle::Lines,
+            grid_subdivisions: 1,
+            grid_rotation: 0.0,
+            grid_min_spacing: 20.0,
+            grid_max_spacing: 80.0,
+            force_integer_positions: false,
+            grid_presets: GridPreset::built_in(),
+            selected_grid_preset: 0,
+            new_grid_preset_name: String::new(),
             enable_snapping: true,
-            origin_top_left: true,
+            snap_mode: SnapMode::Grid,
+            show_snap_scores: false,
+            snap_to_markers: false,
+            marker_snap_threshold: 8.0,
+            pixel_grid: PixelGridMode {
+                enabled: false,
+                show_pixel_index: true,
+            },
+            origin_mode: OriginMode::TopLeft,
+            origin_migration: None,
+            pending_origin_change: None,
+            output_transform: OutputTransform::None,
+            output_matrix: AffineTransform::IDENTITY,
+            normalise_preserve_aspect: true,
             marker_color: Color32::from_rgb(0, 120, 255),
+            show_marker_dots: true,
+            color_by: ColorBy::Stored,
+            colormap: Colormap::Viridis,
+            show_marker_labels: true,
+            show_offscreen_indicators: true,
+            show_nested_sizes: false,
+            new_nested_size: "HD (1280x720)".to_string(),
+            marker_mesh_threshold: 500,
+            undo_depth: undo::DEFAULT_DEPTH,
+            max_markers: 500,
+            duplicate_offset_x: 10.0,
+            duplicate_offset_y: 10.0,
+            quantize: None,
+            expected_points: None,
+            validation_tolerance: 2.0,
+            active_tool: Tool::Place,
+            place_kind: MarkerKind::Point,
+            selected_markers: HashSet::new(),
+            expanded_notes: HashSet::new(),
+            lasso_path: Vec::new(),
+            marquee: None,
+            selection_rect: None,
+            analysis_region: None,
+            region_colors: None,
+            move_selection: false,
+            moving_selection: None,
+            text_edit_before: None,
+            command_entry: CommandEntry::default(),
+            mouse_bindings: MouseBindings::default(),
+            key_bindings: KeyBindings::default(),
+            show_shortcuts_overlay: false,
+            overlays_suppressed: false,
+            latch_hide_overlays: false,
+            binding_warning: None,
             current_position: Pos2::ZERO,
             current_position_raw: Pos2::ZERO,
+            view_rect: Rect::NOTHING,
+            dragging_origin: false,
+            origin_before_drag: None,
+            dragging_marker: None,
+            editing_marker: None,
             dark_mode: true,
-            recalculate_markers: true,
+            show_rulers: true,
+            ruler_thickness: 20.0,
+            show_minimap: false,
+            minimap_corner: MinimapCorner::BottomRight,
+            smooth_zoom: true,
+            ruler_measure_axis: RulerAxis::X,
+            ruler_measure_start: None,
+            ruler_measure_end: None,
+            ruler_measuring: false,
+            crosshair: CrosshairSettings {
+                shape: CrosshairShape::Cross,
+                size: 10.0,
+                color: Color32::from_rgb(255, 0, 0),
+            },
+            gamma: 2.2,
+            color_profile: ColorProfile::Srgb,
+            session: SessionInfo::default(),
+            place_at: Pos2::ZERO,
+            place_at_error: None,
+            settings_error: None,
+            csv_options: CsvOptions::default(),
+            show_csv_dialog: false,
+            import_errors: Vec::new(),
+            share_summary: None,
+            panel_layout: PanelLayout::default(),
+            playback: PlaybackState::default(),
+            compact_mode: false,
+            compact_section: None,
+            export_format: ExportFormat::FigmaPixels,
+            export_dpi: 96.0,
+            html_area_radius: 10,
+            android_dp_scale: 1.0,
+            png_layout: PngLayout::default(),
+            export_status: None,
+            export_split_by_group: false,
+            export_hidden_groups: false,
+            export_basename: "coordinates".to_string(),
+            css_grid_fractional: false,
+            clip_path_decimals: 1,
+            svg_close_path: true,
+            svg_marker_circles: false,
+            stamps: Vec::new(),
+            bezier_points: Vec::new(),
+            measure_first: None,
+            measurement: None,
+            angle_points: Vec::new(),
+            show_measurements: true,
+            active_stamp: None,
+            stamp_scale: 1.0,
+            new_stamp_name: String::new(),
+            view_bookmarks: Vec::new(),
+            new_bookmark_name: String::new(),
+            #[cfg(feature = "websocket")]
+            stream_enabled: false,
+            #[cfg(feature = "websocket")]
+            stream_port: 9001,
+            #[cfg(feature = "websocket")]
+            stream_error: None,
+            toast: None,
+            background_error: None,
+            background_opacity: 100.0,
+            composite_mode: CompositeMode::Normal,
+            grid_layer: GridLayer::AboveImage,
+            grid_auto_contrast: false,
+            recent_sessions: Vec::new(),
+            recent_images: Vec::new(),
+            outside_canvas_count: 0,
         }
     }
 }
@@ -0,0 +1,201 @@
+use crate::annotation::Annotation;
+use crate::group::Group;
+use crate::marker::Marker;
+use crate::measurement::Measurement;
+use egui::Pos2;
+
+pub const DEFAULT_DEPTH: usize = 50;
+
+// Markers with the groups and measurements they came with
+#[derive(Clone)]
+pub struct Snapshot {
+    pub markers: Vec<Marker>,
+    pub groups: Vec<Group>,
+    pub measurements: Vec<Measurement>,
+}
+
+// A reversible change to the markers, the origin their values are measured from,
+// or the annotations and measurements drawn with them.
+// Positions are in canvas coordinates.
+#[derive(Clone)]
+pub enum MarkerCommand {
+    Add { index: usize, marker: Marker },
+    Remove { index: usize, marker: Marker },
+    Move { index: usize, from: Pos2, to: Pos2 },
+    Replace { before: Vec<Marker>, after: Vec<Marker> }, // Whole-list changes such as Clear Markers
+    Origin { before: Option<Pos2>, after: Option<Pos2> }, // Custom origin moves, which change every marker's values
+    Annotations { before: Vec<Annotation>, after: Vec<Annotation> },
+    Measurements { before: Vec<Measurement>, after: Vec<Measurement> },
+    // Whole-list changes that also created groups or measurements, such as project imports and stamps
+    Snapshot { before: Snapshot, after: Snapshot },
+}
+
+impl MarkerCommand {
+    /// The command that reverts this one
+    pub fn inverse(&self) -> MarkerCommand {
+        match self {
+            MarkerCommand::Add { index, marker } => MarkerCommand::Remove {
+                index: *index,
+                marker: marker.clone(),
+            },
+            MarkerCommand::Remove { index, marker } => MarkerCommand::Add {
+                index: *index,
+                marker: marker.clone(),
+            },
+            MarkerCommand::Move { index, from, to } => MarkerCommand::Move {
+                index: *index,
+                from: *to,
+                to: *from,
+            },
+            MarkerCommand::Replace { before, after } => MarkerCommand::Replace {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            MarkerCommand::Origin { before, after } => MarkerCommand::Origin {
+                before: *after,
+                after: *before,
+            },
+            MarkerCommand::Annotations { before, after } => MarkerCommand::Annotations {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            MarkerCommand::Measurements { before, after } => MarkerCommand::Measurements {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            MarkerCommand::Snapshot { before, after } => MarkerCommand::Snapshot {
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+}
+
+// Done commands, newest last, plus the undone ones that can still be redone
+pub struct UndoStack {
+    undo: Vec<MarkerCommand>,
+    redo: Vec<MarkerCommand>,
+    depth: usize,
+}
+
+impl UndoStack {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            depth,
+        }
+    }
+
+    /// Records a command that was just applied. Anything undone is no longer redoable.
+    pub fn push(&mut self, command: MarkerCommand) {
+        self.redo.clear();
+        self.undo.push(command);
+        self.trim();
+    }
+
+    /// The command to apply to revert the most recent change
+    pub fn undo(&mut self) -> Option<MarkerCommand> {
+        let command = self.undo.pop()?;
+        let inverse = command.inverse();
+        self.redo.push(command);
+        Some(inverse)
+    }
+
+    /// The command to apply to repeat the most recently undone change
+    pub fn redo(&mut self) -> Option<MarkerCommand> {
+        let command = self.redo.pop()?;
+        self.undo.push(command.clone());
+        Some(command)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        self.trim();
+    }
+
+    // Drops the oldest commands beyond the configured depth
+    fn trim(&mut self) {
+        if self.undo.len() > self.depth {
+            let excess = self.undo.len() - self.depth;
+            self.undo.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+
+    fn move_command(index: usize) -> MarkerCommand {
+        MarkerCommand::Move {
+            index,
+            from: Pos2::ZERO,
+            to: Pos2::new(1.0, 1.0),
+        }
+    }
+
+    fn moved_index(command: Option<MarkerCommand>) -> Option<usize> {
+        match command {
+            Some(MarkerCommand::Move { index, .. }) => Some(index),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn undo_returns_the_inverse_and_redo_the_original() {
+        let mut stack = UndoStack::new(DEFAULT_DEPTH);
+        stack.push(move_command(0));
+        match stack.undo() {
+            Some(MarkerCommand::Move { from, to, .. }) => assert_eq!((from, to), (Pos2::new(1.0, 1.0), Pos2::ZERO)),
+            _ => panic!("expected a move"),
+        }
+        assert!(!stack.can_undo() && stack.can_redo());
+        match stack.redo() {
+            Some(MarkerCommand::Move { from, to, .. }) => assert_eq!((from, to), (Pos2::ZERO, Pos2::new(1.0, 1.0))),
+            _ => panic!("expected a move"),
+        }
+        assert!(stack.can_undo() && !stack.can_redo());
+    }
+
+    #[test]
+    fn adds_and_removes_invert_each_other() {
+        let marker = Marker::new(Pos2::new(5.0, 5.0), Pos2::new(5.0, 5.0), Color32::RED, 0);
+        let add = MarkerCommand::Add { index: 2, marker };
+        match add.inverse() {
+            MarkerCommand::Remove { index, marker } => assert_eq!((index, marker.position), (2, Pos2::new(5.0, 5.0))),
+            _ => panic!("expected a remove"),
+        }
+    }
+
+    #[test]
+    fn new_changes_clear_the_redo_history() {
+        let mut stack = UndoStack::new(DEFAULT_DEPTH);
+        stack.push(move_command(0));
+        stack.undo();
+        stack.push(move_command(1));
+        assert!(!stack.can_redo());
+        assert_eq!(moved_index(stack.undo()), Some(1));
+    }
+
+    #[test]
+    fn the_oldest_changes_are_dropped_beyond_the_depth() {
+        let mut stack = UndoStack::new(3);
+        for index in 0..5 {
+            stack.push(move_command(index));
+        }
+        stack.set_depth(2);
+        assert_eq!(moved_index(stack.undo()), Some(4));
+        assert_eq!(moved_index(stack.undo()), Some(3));
+        assert!(stack.undo().is_none());
+    }
+}
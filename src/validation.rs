@@ -0,0 +1,182 @@
+use egui::Pos2;
+use serde::Deserialize;
+
+// An expected point from a golden file, in top-left based output pixels
+pub struct ExpectedPoint {
+    pub name: String,
+    pub position: Pos2,
+    pub tolerance: Option<f32>, // Overrides the global tolerance
+}
+
+// The Figma JSON export, plus an optional per-point tolerance
+#[derive(Deserialize)]
+struct ExpectedFile {
+    width: f32,
+    height: f32,
+    units: String,
+    points: Vec<ExpectedFilePoint>,
+}
+
+#[derive(Deserialize)]
+struct ExpectedFilePoint {
+    name: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    tolerance: Option<f32>,
+}
+
+pub fn expected_from_json(json: &str) -> Result<Vec<ExpectedPoint>, String> {
+    let file: ExpectedFile = serde_json::from_str(json).map_err(|err| format!("Invalid expected file: {}", err))?;
+    let scale = match file.units.as_str() {
+        "px" => (1.0, 1.0),
+        "percent" => (file.width / 100.0, file.height / 100.0),
+        units => return Err(format!("Unknown units \"{}\"", units)),
+    };
+    Ok(file
+        .points
+        .into_iter()
+        .map(|point| ExpectedPoint {
+            name: point.name,
+            position: Pos2::new(point.x * scale.0, point.y * scale.1),
+            tolerance: point.tolerance,
+        })
+        .collect())
+}
+
+/// Rows of `name,x,y[,tolerance]` in pixels. A header row is skipped.
+pub fn expected_from_csv(csv: &str) -> Result<Vec<ExpectedPoint>, String> {
+    let mut points = Vec::new();
+    for (line_number, line) in csv.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if line.trim().is_empty() || (line_number == 0 && fields.get(1).and_then(|x| x.parse::<f32>().ok()).is_none()) {
+            continue;
+        }
+        let number = |index: usize| fields.get(index).and_then(|value| value.parse::<f32>().ok());
+        let (x, y) = match (number(1), number(2)) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return Err(format!("Line {}: expected name,x,y", line_number + 1)),
+        };
+        points.push(ExpectedPoint {
+            name: fields[0].to_string(),
+            position: Pos2::new(x, y),
+            tolerance: number(3),
+        });
+    }
+    Ok(points)
+}
+
+pub struct ValidationResult {
+    pub name: String,
+    pub marker: Option<usize>,
+    pub delta: Option<(f32, f32)>, // Actual minus expected
+    pub tolerance: f32,
+}
+
+impl ValidationResult {
+    pub fn distance(&self) -> Option<f32> {
+        self.delta.map(|(dx, dy)| dx.hypot(dy))
+    }
+
+    pub fn passed(&self) -> bool {
+        match self.distance() {
+            Some(distance) => distance <= self.tolerance,
+            None => false,
+        }
+    }
+}
+
+// Exported points are named "Marker N", so that name maps back to index N - 1.
+// Any other name falls back to the entry's position in the file.
+fn marker_index(name: &str, entry_index: usize) -> usize {
+    match name.strip_prefix("Marker ").and_then(|number| number.parse::<usize>().ok()) {
+        Some(number) if number > 0 => number - 1,
+        _ => entry_index,
+    }
+}
+
+/// Compares each expected point with the marker it names. `actual` holds
+/// marker positions in the same output space as the expected file.
+pub fn validate(expected: &[ExpectedPoint], actual: &[Pos2], default_tolerance: f32) -> Vec<ValidationResult> {
+    expected
+        .iter()
+        .enumerate()
+        .map(|(entry_index, point)| {
+            let index = marker_index(&point.name, entry_index);
+            let marker = actual.get(index).map(|_| index);
+            ValidationResult {
+                name: point.name.clone(),
+                marker,
+                delta: marker.map(|index| {
+                    let delta = actual[index] - point.position;
+                    (delta.x, delta.y)
+                }),
+                tolerance: point.tolerance.unwrap_or(default_tolerance),
+            }
+        })
+        .collect()
+}
+
+pub fn results_to_csv(results: &[ValidationResult]) -> String {
+    let mut csv = String::from("name,marker,dx,dy,distance,tolerance,result\n");
+    for result in results {
+        let marker = result.marker.map(|index| (index + 1).to_string()).unwrap_or_default();
+        let (dx, dy, distance) = match (result.delta, result.distance()) {
+            (Some((dx, dy)), Some(distance)) => (dx.to_string(), dy.to_string(), distance.to_string()),
+            _ => (String::new(), String::new(), String::new()),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            result.name,
+            marker,
+            dx,
+            dy,
+            distance,
+            result.tolerance,
+            if result.passed() { "pass" } else { "fail" }
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_files_are_scaled_to_pixels() {
+        let json = r#"{"width": 200, "height": 100, "units": "percent",
+            "points": [{"name": "Marker 1", "x": 50, "y": 25, "tolerance": 3}]}"#;
+        let points = expected_from_json(json).unwrap();
+        assert_eq!(points[0].position, Pos2::new(100.0, 25.0));
+        assert_eq!(points[0].tolerance, Some(3.0));
+        let inches = r#"{"width": 1, "height": 1, "units": "in", "points": []}"#;
+        assert_eq!(expected_from_json(inches).err().unwrap(), "Unknown units \"in\"");
+    }
+
+    #[test]
+    fn csv_skips_the_header_and_reports_bad_lines() {
+        let points = expected_from_csv("name,x,y\nstart,1,2\n\nend,3,4,0.5\n").unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[1].name.as_str(), points[1].tolerance), ("end", Some(0.5)));
+        assert_eq!(expected_from_csv("a,1,2\nb,1\n").err().unwrap(), "Line 2: expected name,x,y");
+    }
+
+    #[test]
+    fn points_are_matched_by_marker_name_then_order() {
+        let expected = expected_from_csv("Marker 2,10,10\nlogo,0,0,1\nMarker 9,5,5\n").unwrap();
+        let actual = [Pos2::new(0.5, 0.0), Pos2::new(13.0, 14.0)];
+        let results = validate(&expected, &actual, 2.0);
+        assert_eq!(results[0].marker, Some(1));
+        assert_eq!(results[0].distance(), Some(5.0));
+        assert!(!results[0].passed());
+        // Second in the file, so it's checked against the second marker
+        assert_eq!((results[1].marker, results[1].passed()), (Some(1), false));
+        assert_eq!((results[2].marker, results[2].passed()), (None, false));
+        assert!(validate(&expected[1..2], &actual, 2.0)[0].passed());
+        assert_eq!(
+            results_to_csv(&results[2..]),
+            "name,marker,dx,dy,distance,tolerance,result\nMarker 9,,,,,2,fail\n"
+        );
+    }
+}